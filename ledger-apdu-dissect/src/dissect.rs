@@ -0,0 +1,440 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Decode commands and answers with [Schema]s
+
+use std::{collections::HashMap, fmt};
+
+use ledger_apdu::{APDUCommand, APDUErrorCode};
+use serde::{Serialize, Serializer};
+
+use crate::schema::{CommandSchema, FieldLen, FieldSchema, FieldType, Schema, StatusWordSchema};
+
+const HARDENED: u32 = 0x8000_0000;
+
+/// Renders commands and answers using the built-in and added [Schema]s
+#[derive(Clone, Debug)]
+pub struct Dissector {
+    schemas: Vec<Schema>,
+}
+
+impl Default for Dissector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dissector {
+    /// Dissector knowing the dashboard and Zondax generic commands
+    pub fn new() -> Self {
+        let schemas = [
+            include_str!("../schemas/dashboard.json"),
+            include_str!("../schemas/bolos.json"),
+            include_str!("../schemas/zondax.json"),
+        ]
+        .into_iter()
+        .map(|json| Schema::from_json(json).expect("valid built-in schema"))
+        .collect();
+
+        Self { schemas }
+    }
+
+    /// Dissector without any schema
+    pub fn empty() -> Self {
+        Self { schemas: Vec::new() }
+    }
+
+    /// Add an app schema, taking precedence over the schemas already known
+    pub fn with_schema(
+        mut self,
+        schema: Schema,
+    ) -> Self {
+        self.schemas.insert(0, schema);
+        self
+    }
+
+    /// Known schemas, by precedence
+    pub fn schemas(&self) -> &[Schema] {
+        &self.schemas
+    }
+
+    /// Schemas for `cla`, the ones for this CLA first, then the ones for any CLA
+    fn schemas_for(
+        &self,
+        cla: u8,
+    ) -> impl Iterator<Item = &Schema> {
+        let exact = self
+            .schemas
+            .iter()
+            .filter(move |schema| schema.cla == Some(cla));
+        let any = self
+            .schemas
+            .iter()
+            .filter(|schema| schema.cla.is_none());
+        exact.chain(any)
+    }
+
+    fn lookup(
+        &self,
+        cla: u8,
+        ins: u8,
+    ) -> Option<(&Schema, &CommandSchema)> {
+        self.schemas_for(cla)
+            .find_map(|schema| {
+                schema
+                    .command(ins)
+                    .map(|command| (schema, command))
+            })
+    }
+
+    /// Dissect a serialized command: CLA, INS, P1, P2, then the length and the payload
+    pub fn command(
+        &self,
+        raw: &[u8],
+    ) -> CommandDissection {
+        match APDUCommand::from_bytes(raw) {
+            Ok(command) => self.dissect(&command),
+            Err(err) => {
+                CommandDissection { error: Some(err.to_string()), trailing: raw.to_vec(), ..Default::default() }
+            },
+        }
+    }
+
+    /// Dissect a command
+    pub fn dissect<B: std::ops::Deref<Target = [u8]>>(
+        &self,
+        command: &APDUCommand<B>,
+    ) -> CommandDissection {
+        let mut dissection = CommandDissection {
+            cla: command.cla,
+            ins: command.ins,
+            p1: command.p1,
+            p2: command.p2,
+            ..Default::default()
+        };
+
+        let Some((schema, schema_command)) = self.lookup(command.cla, command.ins) else {
+            dissection.trailing = command.data.to_vec();
+            return dissection;
+        };
+        dissection.app = Some(schema.name.clone());
+        dissection.name = Some(schema_command.name.clone());
+        dissection.p1_meaning = schema_command
+            .p1
+            .get(&command.p1)
+            .cloned();
+        dissection.p2_meaning = schema_command
+            .p2
+            .get(&command.p2)
+            .cloned();
+
+        if schema_command.chunked && command.p1 != 0 {
+            dissection.trailing = command.data.to_vec();
+        } else {
+            let decoded = decode(&schema_command.fields, &command.data);
+            dissection.fields = decoded.fields;
+            dissection.trailing = decoded.trailing;
+            dissection.error = decoded.error;
+        }
+
+        dissection
+    }
+
+    /// Dissect the answer to a serialized command, status word included
+    pub fn answer(
+        &self,
+        command: &[u8],
+        raw: &[u8],
+    ) -> AnswerDissection {
+        let Some((data, status)) = raw.split_last_chunk::<2>() else {
+            return AnswerDissection {
+                error: Some(format!("answer too short, {} bytes", raw.len())),
+                trailing: raw.to_vec(),
+                ..Default::default()
+            };
+        };
+        let status_word = u16::from_be_bytes(*status);
+        let mut dissection = AnswerDissection { status_word, ..Default::default() };
+
+        let (cla, ins) = match command {
+            [cla, ins, ..] => (*cla, *ins),
+            _ => {
+                dissection.status = describe_status_word(None, status_word);
+                dissection.trailing = data.to_vec();
+                return dissection;
+            },
+        };
+
+        let status_schema = self
+            .schemas_for(cla)
+            .find_map(|schema| schema.status_word(status_word));
+        dissection.status = describe_status_word(status_schema, status_word);
+
+        match self.lookup(cla, ins) {
+            Some((_, command)) if !data.is_empty() => {
+                let decoded = decode(&command.answer, data);
+                dissection.fields = decoded.fields;
+                dissection.trailing = decoded.trailing;
+                dissection.error = decoded.error;
+            },
+            _ => dissection.trailing = data.to_vec(),
+        }
+
+        dissection
+    }
+}
+
+fn describe_status_word(
+    schema: Option<&StatusWordSchema>,
+    code: u16,
+) -> Option<String> {
+    match schema {
+        Some(status) if status.description.is_empty() => Some(status.name.clone()),
+        Some(status) => Some(format!("{}: {}", status.name, status.description)),
+        None => APDUErrorCode::try_from(code)
+            .ok()
+            .map(|code| code.description()),
+    }
+}
+
+/// Decoded value of a field
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    /// Integer
+    Uint(u64),
+    /// Raw bytes
+    Bytes(Vec<u8>),
+    /// Text
+    Ascii(String),
+    /// BIP32 path components
+    Path(Vec<u32>),
+}
+
+/// Integers as numbers, everything else as displayed
+impl Serialize for Value {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Uint(value) => serializer.serialize_u64(*value),
+            Value::Ascii(text) => serializer.serialize_str(text),
+            value => serializer.collect_str(value),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Value::Uint(value) => write!(f, "{value} (0x{value:x})"),
+            Value::Bytes(bytes) => write!(f, "{}", hex::encode(bytes)),
+            Value::Ascii(text) => write!(f, "{text:?}"),
+            Value::Path(components) => {
+                write!(f, "m")?;
+                for component in components {
+                    match component & HARDENED {
+                        0 => write!(f, "/{component}")?,
+                        _ => write!(f, "/{}'", component & !HARDENED)?,
+                    }
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// A decoded field
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Field {
+    /// Name from the schema
+    pub name: String,
+    /// Decoded value
+    pub value: Value,
+}
+
+/// A dissected command, see [Dissector::command]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CommandDissection {
+    /// App of the command, if known
+    pub app: Option<String>,
+    /// Command name, if known
+    pub name: Option<String>,
+    /// Class
+    pub cla: u8,
+    /// Instruction
+    pub ins: u8,
+    /// First parameter
+    pub p1: u8,
+    /// Meaning of P1, if known
+    #[serde(rename(serialize = "p1Meaning"))]
+    pub p1_meaning: Option<String>,
+    /// Second parameter
+    pub p2: u8,
+    /// Meaning of P2, if known
+    #[serde(rename(serialize = "p2Meaning"))]
+    pub p2_meaning: Option<String>,
+    /// Decoded payload fields
+    pub fields: Vec<Field>,
+    /// Payload bytes that weren't decoded
+    pub trailing: Vec<u8>,
+    /// Why the payload couldn't be fully decoded
+    pub error: Option<String>,
+}
+
+impl fmt::Display for CommandDissection {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match (&self.app, &self.name) {
+            (Some(app), Some(name)) => write!(f, "{app} {name}")?,
+            _ => write!(f, "unknown command")?,
+        }
+        write!(f, " cla=0x{:02x} ins=0x{:02x} p1=0x{:02x}", self.cla, self.ins, self.p1)?;
+        if let Some(meaning) = &self.p1_meaning {
+            write!(f, " ({meaning})")?;
+        }
+        write!(f, " p2=0x{:02x}", self.p2)?;
+        if let Some(meaning) = &self.p2_meaning {
+            write!(f, " ({meaning})")?;
+        }
+        write_body(f, &self.fields, &self.trailing, &self.error)
+    }
+}
+
+/// A dissected answer, see [Dissector::answer]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct AnswerDissection {
+    /// Status word
+    #[serde(rename(serialize = "statusWord"))]
+    pub status_word: u16,
+    /// Name or description of the status word, if known
+    pub status: Option<String>,
+    /// Decoded payload fields
+    pub fields: Vec<Field>,
+    /// Payload bytes that weren't decoded
+    pub trailing: Vec<u8>,
+    /// Why the payload couldn't be fully decoded
+    pub error: Option<String>,
+}
+
+impl fmt::Display for AnswerDissection {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "status=0x{:04x}", self.status_word)?;
+        if let Some(status) = &self.status {
+            write!(f, " ({status})")?;
+        }
+        write_body(f, &self.fields, &self.trailing, &self.error)
+    }
+}
+
+fn write_body(
+    f: &mut fmt::Formatter<'_>,
+    fields: &[Field],
+    trailing: &[u8],
+    error: &Option<String>,
+) -> fmt::Result {
+    for field in fields {
+        write!(f, "\n  {}: {}", field.name, field.value)?;
+    }
+    if !trailing.is_empty() {
+        let label = if fields.is_empty() { "data" } else { "trailing" };
+        write!(f, "\n  {label}: {}", hex::encode(trailing))?;
+    }
+    if let Some(error) = error {
+        write!(f, "\n  error: {error}")?;
+    }
+    Ok(())
+}
+
+/// Result of decoding a payload
+struct Decoded {
+    fields: Vec<Field>,
+    trailing: Vec<u8>,
+    error: Option<String>,
+}
+
+/// Decode `data` field by field, stopping at the first field that doesn't fit
+fn decode(
+    layout: &[FieldSchema],
+    mut data: &[u8],
+) -> Decoded {
+    let mut fields = Vec::new();
+    let mut integers = HashMap::new();
+
+    for field in layout {
+        let len = match (&field.len, field.ty) {
+            (_, FieldType::Bip32be) => data
+                .first()
+                .map_or(1, |count| 1 + 4 * *count as usize),
+            (_, ty) if ty.size().is_some() => ty.size().unwrap_or_default(),
+            (Some(FieldLen::Fixed(len)), _) => *len,
+            (Some(FieldLen::Field(name)), _) => integers
+                .get(name.as_str())
+                .copied()
+                .unwrap_or_default() as usize,
+            (None, _) => data.len(),
+        };
+        let Some((bytes, rest)) = data.split_at_checked(len) else {
+            let error = format!("`{}` needs {len} bytes, {} left", field.name, data.len());
+            return Decoded { fields, trailing: data.to_vec(), error: Some(error) };
+        };
+        data = rest;
+
+        let value = match field.ty {
+            FieldType::U8 => Value::Uint(bytes[0] as u64),
+            FieldType::U16be => Value::Uint(u16::from_be_bytes([bytes[0], bytes[1]]) as u64),
+            FieldType::U16le => Value::Uint(u16::from_le_bytes([bytes[0], bytes[1]]) as u64),
+            FieldType::U32be | FieldType::U32le | FieldType::U64be | FieldType::U64le => {
+                let mut buf = [0u8; 8];
+                let value = if matches!(field.ty, FieldType::U32be | FieldType::U64be) {
+                    buf[8 - len ..].copy_from_slice(bytes);
+                    u64::from_be_bytes(buf)
+                } else {
+                    buf[.. len].copy_from_slice(bytes);
+                    u64::from_le_bytes(buf)
+                };
+                Value::Uint(value)
+            },
+            FieldType::Bytes => Value::Bytes(bytes.to_vec()),
+            FieldType::Ascii => Value::Ascii(String::from_utf8_lossy(bytes).into_owned()),
+            FieldType::Bip32be => Value::Path(
+                bytes[1 ..]
+                    .chunks_exact(4)
+                    .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect(),
+            ),
+            FieldType::Bip32le => Value::Path(
+                bytes
+                    .chunks_exact(4)
+                    .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect(),
+            ),
+        };
+        if let Value::Uint(value) = value {
+            integers.insert(field.name.as_str(), value);
+        }
+        fields.push(Field { name: field.name.clone(), value });
+    }
+
+    Decoded { fields, trailing: data.to_vec(), error: None }
+}
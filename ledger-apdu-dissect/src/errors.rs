@@ -0,0 +1,42 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use thiserror::Error;
+
+/// Error loading a [Schema](crate::Schema)
+#[derive(Debug, Error)]
+pub enum SchemaError {
+    /// Invalid JSON
+    #[error("invalid schema: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A length refers to a field that isn't an earlier integer field
+    #[error("{command}: length of `{field}` refers to unknown integer field `{len}`")]
+    UnknownLength {
+        /// Command name
+        command: String,
+        /// Field with the length
+        field: String,
+        /// Name of the length field
+        len: String,
+    },
+    /// A length is given for a fixed size field
+    #[error("{command}: `{field}` has a fixed size and can't have a length")]
+    UnexpectedLength {
+        /// Command name
+        command: String,
+        /// Field with the length
+        field: String,
+    },
+}
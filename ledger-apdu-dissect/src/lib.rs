@@ -0,0 +1,41 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Render APDU commands and answers in human readable form
+//!
+//! Dashboard and Zondax generic commands are built in, app commands are described by JSON [Schema]s
+//!
+//! ```
+//! use ledger_apdu_dissect::Dissector;
+//!
+//! let dissector = Dissector::new();
+//! let command = hex::decode("e0d8000006436f736d6f73").unwrap();
+//! assert_eq!(
+//!     dissector.command(&command).to_string(),
+//!     "Dashboard OPEN_APP cla=0xe0 ins=0xd8 p1=0x00 p2=0x00\n  app_name: \"Cosmos\""
+//! );
+//! ```
+
+#![deny(warnings, unused_qualifications, missing_docs)]
+
+pub mod dissect;
+mod errors;
+pub mod schema;
+#[cfg(test)]
+mod tests;
+
+pub use dissect::{AnswerDissection, CommandDissection, Dissector, Field, Value};
+pub use errors::SchemaError;
+pub use schema::{CommandSchema, FieldLen, FieldSchema, FieldType, Schema, StatusWordSchema};
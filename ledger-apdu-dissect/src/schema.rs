@@ -0,0 +1,231 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! App schemas, describing commands and their payloads
+
+use std::collections::BTreeMap;
+
+use ledger_apdu::num::{de_opt_u8, de_u16, de_u8, parse_num};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::errors::SchemaError;
+
+/// Description of an app's commands
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Schema {
+    /// App name
+    pub name: String,
+    /// App's APDU CLA, the schema applies to any CLA if `None`
+    #[serde(default, deserialize_with = "de_opt_u8")]
+    pub cla: Option<u8>,
+    /// Commands of the app
+    #[serde(default)]
+    pub commands: Vec<CommandSchema>,
+    /// Status words defined by the app
+    #[serde(default, rename(serialize = "statusWords"), alias = "statusWords")]
+    pub status_words: Vec<StatusWordSchema>,
+}
+
+/// Description of a command
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CommandSchema {
+    /// Instruction code
+    #[serde(deserialize_with = "de_u8")]
+    pub ins: u8,
+    /// Command name
+    pub name: String,
+    /// Meaning of the P1 values
+    #[serde(default, deserialize_with = "de_meanings")]
+    pub p1: BTreeMap<u8, String>,
+    /// Meaning of the P2 values
+    #[serde(default, deserialize_with = "de_meanings")]
+    pub p2: BTreeMap<u8, String>,
+    /// Whether the payload is sent in chunks, with P1 the [ChunkPayloadType](https://docs.rs/ledger-zondax-generic)
+    ///
+    /// Only the init chunk (P1 = 0) is decoded with [CommandSchema::fields]
+    #[serde(default)]
+    pub chunked: bool,
+    /// Layout of the command payload
+    #[serde(default)]
+    pub fields: Vec<FieldSchema>,
+    /// Layout of the answer payload
+    #[serde(default)]
+    pub answer: Vec<FieldSchema>,
+}
+
+/// Description of a payload field
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FieldSchema {
+    /// Field name
+    pub name: String,
+    /// How the field is encoded
+    #[serde(rename = "type")]
+    pub ty: FieldType,
+    /// Length of `bytes` and `ascii` fields, the rest of the payload if `None`
+    #[serde(default)]
+    pub len: Option<FieldLen>,
+}
+
+/// Encoding of a payload field
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    /// Unsigned byte
+    U8,
+    /// Big endian u16
+    U16be,
+    /// Little endian u16
+    U16le,
+    /// Big endian u32
+    U32be,
+    /// Little endian u32
+    U32le,
+    /// Big endian u64
+    U64be,
+    /// Little endian u64
+    U64le,
+    /// Raw bytes, shown in hex
+    Bytes,
+    /// ASCII text
+    Ascii,
+    /// BIP32 path, as a length byte followed by big endian u32s
+    Bip32be,
+    /// BIP32 path, as 5 little endian u32s (Zondax apps)
+    Bip32le,
+}
+
+impl FieldType {
+    /// Size of fixed size types
+    pub fn size(self) -> Option<usize> {
+        match self {
+            FieldType::U8 => Some(1),
+            FieldType::U16be | FieldType::U16le => Some(2),
+            FieldType::U32be | FieldType::U32le => Some(4),
+            FieldType::U64be | FieldType::U64le => Some(8),
+            FieldType::Bip32le => Some(20),
+            FieldType::Bytes | FieldType::Ascii | FieldType::Bip32be => None,
+        }
+    }
+
+    /// Whether the type is an integer
+    pub fn is_integer(self) -> bool {
+        matches!(
+            self,
+            FieldType::U8
+                | FieldType::U16be
+                | FieldType::U16le
+                | FieldType::U32be
+                | FieldType::U32le
+                | FieldType::U64be
+                | FieldType::U64le
+        )
+    }
+}
+
+/// Length of a field
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum FieldLen {
+    /// Fixed number of bytes
+    Fixed(usize),
+    /// Value of an earlier integer field
+    Field(String),
+}
+
+/// Description of a status word
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StatusWordSchema {
+    /// Raw code
+    #[serde(deserialize_with = "de_u16")]
+    pub code: u16,
+    /// Short name
+    pub name: String,
+    /// Human readable description
+    #[serde(default)]
+    pub description: String,
+}
+
+impl Schema {
+    /// Parse a JSON schema
+    pub fn from_json(json: &str) -> Result<Self, SchemaError> {
+        let schema: Schema = serde_json::from_str(json)?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// Check that every length refers to an earlier integer field
+    pub fn validate(&self) -> Result<(), SchemaError> {
+        for command in &self.commands {
+            for fields in [&command.fields, &command.answer] {
+                for (idx, field) in fields.iter().enumerate() {
+                    match &field.len {
+                        Some(FieldLen::Field(name)) => {
+                            let found = fields[.. idx]
+                                .iter()
+                                .any(|earlier| &earlier.name == name && earlier.ty.is_integer());
+                            if !found {
+                                return Err(SchemaError::UnknownLength {
+                                    command: command.name.clone(),
+                                    field: field.name.clone(),
+                                    len: name.clone(),
+                                });
+                            }
+                        },
+                        Some(_) if field.ty.size().is_some() || field.ty == FieldType::Bip32be => {
+                            return Err(SchemaError::UnexpectedLength {
+                                command: command.name.clone(),
+                                field: field.name.clone(),
+                            });
+                        },
+                        _ => {},
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Command with the given instruction
+    pub fn command(
+        &self,
+        ins: u8,
+    ) -> Option<&CommandSchema> {
+        self.commands
+            .iter()
+            .find(|command| command.ins == ins)
+    }
+
+    /// Status word with the given code
+    pub fn status_word(
+        &self,
+        code: u16,
+    ) -> Option<&StatusWordSchema> {
+        self.status_words
+            .iter()
+            .find(|status| status.code == code)
+    }
+}
+
+fn de_meanings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<u8, String>, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, meaning)| {
+            let value = parse_num(&key)
+                .and_then(|value| u8::try_from(value).ok())
+                .ok_or_else(|| de::Error::custom(format!("invalid parameter value `{key}`")))?;
+            Ok((value, meaning))
+        })
+        .collect()
+}
@@ -0,0 +1,152 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use super::*;
+
+const COSMOS: &str = r#"{
+  "name": "Cosmos",
+  "cla": "0x55",
+  "commands": [
+    {
+      "ins": "0x04",
+      "name": "GET_ADDR_SECP256K1",
+      "p1": { "0x00": "silent", "0x01": "show on device" },
+      "fields": [
+        { "name": "hrp_len", "type": "u8" },
+        { "name": "hrp", "type": "ascii", "len": "hrp_len" },
+        { "name": "path", "type": "bip32le" }
+      ],
+      "answer": [
+        { "name": "pubkey", "type": "bytes", "len": 33 },
+        { "name": "address", "type": "ascii" }
+      ]
+    },
+    {
+      "ins": 2,
+      "name": "SIGN_SECP256K1",
+      "chunked": true,
+      "fields": [
+        { "name": "path", "type": "bip32le" }
+      ]
+    }
+  ],
+  "statusWords": [
+    { "code": "0x6988", "name": "InvalidTxType", "description": "invalid transaction type" }
+  ]
+}"#;
+
+fn cosmos() -> Dissector {
+    Dissector::new().with_schema(Schema::from_json(COSMOS).expect("valid schema"))
+}
+
+fn zondax_path() -> Vec<u8> {
+    [44u32 | 0x8000_0000, 118 | 0x8000_0000, 0x8000_0000, 0, 5]
+        .iter()
+        .flat_map(|c| c.to_le_bytes())
+        .collect()
+}
+
+#[test]
+fn app_command() {
+    let payload = [&[6][..], b"cosmos", &zondax_path()].concat();
+    let command = [&[0x55, 0x04, 0x01, 0x00, payload.len() as u8][..], &payload].concat();
+
+    let dissection = cosmos().command(&command);
+    assert_eq!(dissection.name.as_deref(), Some("GET_ADDR_SECP256K1"));
+    assert_eq!(
+        dissection.to_string(),
+        "Cosmos GET_ADDR_SECP256K1 cla=0x55 ins=0x04 p1=0x01 (show on device) p2=0x00\n  hrp_len: 6 (0x6)\n  hrp: \
+         \"cosmos\"\n  path: m/44'/118'/0'/0/5"
+    );
+
+    // payload too short for the schema
+    let short = [&[0x55, 0x04, 0x01, 0x00, 10][..], &payload[.. 10]].concat();
+    let dissection = cosmos().command(&short);
+    assert_eq!(dissection.fields.len(), 2);
+    assert_eq!(dissection.error.as_deref(), Some("`path` needs 20 bytes, 3 left"));
+
+    // truncated command
+    let dissection = cosmos().command(&command[.. 15]);
+    assert!(dissection.fields.is_empty());
+    assert_eq!(dissection.error.as_deref(), Some("length byte is 27, but 10 bytes of payload follow"));
+    assert_eq!(dissection.trailing, command[.. 15]);
+}
+
+#[test]
+fn chunked_command() {
+    let init = [&[0x55, 0x02, 0x00, 0x00, 20][..], &zondax_path()].concat();
+    let dissection = cosmos().command(&init);
+    assert_eq!(dissection.fields[0].value, Value::Path(vec![0x8000_002c, 0x8000_0076, 0x8000_0000, 0, 5]));
+
+    let dissection = cosmos().command(&[0x55, 0x02, 0x01, 0x00, 0x02, 0xab, 0xcd]);
+    assert!(dissection.fields.is_empty());
+    assert_eq!(dissection.to_string(), "Cosmos SIGN_SECP256K1 cla=0x55 ins=0x02 p1=0x01 p2=0x00\n  data: abcd");
+}
+
+#[test]
+fn answers() {
+    let dissector = cosmos();
+    let command = [0x55, 0x04, 0x00, 0x00];
+
+    let answer = [&[0x02; 33][..], b"cosmos1abc", &[0x90, 0x00]].concat();
+    let dissection = dissector.answer(&command, &answer);
+    assert_eq!(dissection.status.as_deref(), Some("success"));
+    assert_eq!(dissection.fields[1].value, Value::Ascii("cosmos1abc".to_string()));
+
+    let dissection = dissector.answer(&command, &[0x69, 0x88]);
+    assert_eq!(dissection.to_string(), "status=0x6988 (InvalidTxType: invalid transaction type)");
+
+    // app status words only apply to the app CLA
+    let dissection = dissector.answer(&[0xe0, 0x01], &[0x69, 0x88]);
+    assert_eq!(dissection.status, None);
+
+    let dissection = dissector.answer(&command, &[0x69]);
+    assert_eq!(dissection.error.as_deref(), Some("answer too short, 1 bytes"));
+}
+
+#[test]
+fn builtin_commands() {
+    let dissector = Dissector::new();
+
+    let dissection = dissector.command(&[0x55, 0x00, 0x00, 0x00, 0x00]);
+    assert_eq!((dissection.app.as_deref(), dissection.name.as_deref()), (Some("Zondax"), Some("GET_VERSION")));
+
+    let dissection =
+        dissector.answer(&[0xb0, 0x01, 0x00, 0x00], &hex::decode("0105424f4c4f5305312e302e3001029000").unwrap());
+    assert_eq!(
+        dissection.to_string(),
+        "status=0x9000 (success)\n  format: 1 (0x1)\n  app_name_len: 5 (0x5)\n  app_name: \"BOLOS\"\n  \
+         app_version_len: 5 (0x5)\n  app_version: \"1.0.0\"\n  flags_len: 1 (0x1)\n  flags: 02"
+    );
+
+    let dissection = Dissector::empty().command(&[0xe0, 0x01, 0x00, 0x00]);
+    assert_eq!(dissection.to_string(), "unknown command cla=0xe0 ins=0x01 p1=0x00 p2=0x00");
+}
+
+#[test]
+fn invalid_schemas() {
+    let schema = r#"{ "name": "App", "commands": [{ "ins": 1, "name": "CMD", "fields": [
+        { "name": "data", "type": "bytes", "len": "data_len" }
+    ]}]}"#;
+    assert!(matches!(Schema::from_json(schema), Err(SchemaError::UnknownLength { .. })));
+
+    let schema = r#"{ "name": "App", "commands": [{ "ins": 1, "name": "CMD", "fields": [
+        { "name": "value", "type": "u32be", "len": 2 }
+    ]}]}"#;
+    assert!(matches!(Schema::from_json(schema), Err(SchemaError::UnexpectedLength { .. })));
+
+    let schema = r#"{ "name": "App", "cla": "0x155" }"#;
+    assert!(matches!(Schema::from_json(schema), Err(SchemaError::Json(_))));
+}
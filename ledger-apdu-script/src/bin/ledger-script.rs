@@ -0,0 +1,105 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! `ledger-script` runs APDU scripts on a device connected over HID
+
+use std::process::ExitCode;
+
+use futures::executor::block_on;
+use ledger_apdu_script::{Runner, Script};
+use ledger_transport_hid::{hidapi::HidApi, TransportNativeHID};
+
+const USAGE: &str = "usage: ledger-script [--stop-on-failure] [--var <name>=<hex>]... <script>...";
+
+fn main() -> ExitCode {
+    let mut stop_on_failure = false;
+    let mut vars = Vec::new();
+    let mut files = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stop-on-failure" => stop_on_failure = true,
+            "--var" => {
+                let var = args.next().and_then(|var| {
+                    let (name, value) = var.split_once('=')?;
+                    Some((name.to_string(), hex::decode(value).ok()?))
+                });
+                match var {
+                    Some(var) => vars.push(var),
+                    None => {
+                        eprintln!("error: expected `--var <name>=<hex>`\n{USAGE}");
+                        return ExitCode::from(2);
+                    },
+                }
+            },
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            },
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    }
+
+    let mut scripts = Vec::new();
+    for file in &files {
+        let script = std::fs::read_to_string(file)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Script::parse(&text).map_err(|err| err.to_string()));
+        match script {
+            Ok(script) => scripts.push(script),
+            Err(err) => {
+                eprintln!("error: {file}: {err}");
+                return ExitCode::from(2);
+            },
+        }
+    }
+
+    let transport = match HidApi::new()
+        .map_err(Into::into)
+        .and_then(|api| TransportNativeHID::new(&api))
+    {
+        Ok(transport) => transport,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let mut runner = Runner::new(&transport).with_stop_on_failure(stop_on_failure);
+    for (name, value) in vars {
+        runner = runner.with_var(name, value);
+    }
+
+    let mut success = true;
+    for (file, script) in files.iter().zip(&scripts) {
+        let report = block_on(runner.run(script));
+        println!("{file}\n{report}\n");
+        success &= report.success();
+        if !success && stop_on_failure {
+            break;
+        }
+    }
+
+    if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
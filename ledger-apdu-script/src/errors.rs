@@ -0,0 +1,58 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use thiserror::Error;
+
+/// Error parsing a script
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("line {line}: {kind}")]
+pub struct ParseError {
+    /// Line of the error, 1-based
+    pub line: usize,
+    /// What went wrong
+    pub kind: ParseErrorKind,
+}
+
+/// Kind of [ParseError]
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A line isn't a known directive
+    #[error("unknown directive `{0}`")]
+    UnknownDirective(String),
+    /// Invalid hex or pattern syntax
+    #[error("invalid syntax at `{0}`")]
+    Syntax(String),
+    /// An odd number of hex digits
+    #[error("odd number of hex digits")]
+    OddDigits,
+    /// A command shorter than CLA, INS, P1 and P2
+    #[error("command too short, {0} bytes")]
+    CommandTooShort(usize),
+    /// An expectation without a command before it
+    #[error("expectation without a command")]
+    UnexpectedAnswer,
+    /// Invalid `repeat` count
+    #[error("invalid repeat count `{0}`")]
+    InvalidCount(String),
+    /// Invalid `sleep` duration
+    #[error("invalid duration `{0}`, use e.g. `500ms` or `2s`")]
+    InvalidDuration(String),
+    /// `end` without `repeat`
+    #[error("`end` without `repeat`")]
+    UnexpectedEnd,
+    /// `repeat` without `end`
+    #[error("`repeat` without `end`")]
+    MissingEnd,
+}
@@ -0,0 +1,37 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Run APDU transcripts against any [Exchange](ledger_transport::Exchange), checking every answer
+//!
+//! ```
+//! use ledger_apdu_script::Script;
+//!
+//! let script = Script::parse("=> e0 01 00 00 00\n<= {name} * 9000").unwrap();
+//! assert_eq!(script.steps.len(), 1);
+//! ```
+
+#![deny(warnings, unused_qualifications, missing_docs)]
+
+mod errors;
+pub mod pattern;
+pub mod runner;
+pub mod script;
+#[cfg(test)]
+mod tests;
+
+pub use errors::{ParseError, ParseErrorKind};
+pub use pattern::{Pattern, Template, Vars};
+pub use runner::{Outcome, Report, Runner, StepReport};
+pub use script::{Script, Step};
@@ -0,0 +1,312 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Answer patterns and command templates
+
+use std::{collections::HashMap, fmt};
+
+use crate::errors::ParseErrorKind;
+
+/// Variables captured from answers or set by the script
+pub type Vars = HashMap<String, Vec<u8>>;
+
+/// Hex bytes and `${name}` variables, e.g. a command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    text: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Bytes(Vec<u8>),
+    Var(String),
+}
+
+impl Template {
+    /// Parse a template
+    pub fn parse(text: &str) -> Result<Self, ParseErrorKind> {
+        let mut parts = Vec::new();
+        let mut bytes = Vec::new();
+
+        for token in lex(text)? {
+            match token {
+                Token::Byte { value, mask: 0xff } => bytes.push(value),
+                Token::Var(name) => {
+                    if !bytes.is_empty() {
+                        parts.push(Part::Bytes(std::mem::take(&mut bytes)));
+                    }
+                    parts.push(Part::Var(name));
+                },
+                _ => return Err(ParseErrorKind::Syntax(text.to_string())),
+            }
+        }
+        if !bytes.is_empty() {
+            parts.push(Part::Bytes(bytes));
+        }
+
+        Ok(Self { text: text.to_string(), parts })
+    }
+
+    /// Bytes of the template, `Err` with the name of the first undefined variable
+    pub fn render(
+        &self,
+        vars: &Vars,
+    ) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        for part in &self.parts {
+            match part {
+                Part::Bytes(bytes) => out.extend_from_slice(bytes),
+                Part::Var(name) => out.extend_from_slice(
+                    vars.get(name)
+                        .ok_or_else(|| name.clone())?,
+                ),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Length of the template, if it uses no variables
+    pub(crate) fn static_len(&self) -> Option<usize> {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Bytes(bytes) => Some(bytes.len()),
+                Part::Var(_) => None,
+            })
+            .sum()
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Expected answer, status word included
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    text: String,
+    tokens: Vec<Token>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    /// A byte, only the bits set in `mask` are compared
+    Byte {
+        value: u8,
+        mask: u8,
+    },
+    /// Any number of bytes
+    Any,
+    /// The bytes of a variable
+    Var(String),
+    /// Capture `len` bytes, or as many as possible
+    Capture {
+        name: String,
+        len: Option<usize>,
+    },
+    /// 2 bytes not matching `value` under `mask`
+    NotWord {
+        value: u16,
+        mask: u16,
+    },
+}
+
+impl Pattern {
+    /// Parse a pattern
+    pub fn parse(text: &str) -> Result<Self, ParseErrorKind> {
+        Ok(Self { text: text.to_string(), tokens: lex(text)? })
+    }
+
+    /// Match `answer`, returning the captured variables
+    ///
+    /// `Err` with the name of the first undefined variable
+    pub fn matches(
+        &self,
+        answer: &[u8],
+        vars: &Vars,
+    ) -> Result<Option<Vars>, String> {
+        for token in &self.tokens {
+            if let Token::Var(name) = token {
+                if !vars.contains_key(name) {
+                    return Err(name.clone());
+                }
+            }
+        }
+
+        let mut captures = Vec::new();
+        if !match_tokens(&self.tokens, answer, vars, &mut captures) {
+            return Ok(None);
+        }
+        Ok(Some(
+            captures
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_vec()))
+                .collect(),
+        ))
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Match `data` against `tokens`, backtracking on variable length tokens, longest first
+fn match_tokens<'a>(
+    tokens: &'a [Token],
+    data: &'a [u8],
+    vars: &Vars,
+    captures: &mut Vec<(&'a str, &'a [u8])>,
+) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return data.is_empty();
+    };
+
+    match token {
+        Token::Byte { value, mask } => match data.split_first() {
+            Some((byte, data)) if byte & mask == value & mask => match_tokens(rest, data, vars, captures),
+            _ => false,
+        },
+        Token::Any => (0 ..= data.len())
+            .rev()
+            .any(|len| match_tokens(rest, &data[len ..], vars, captures)),
+        Token::Var(name) => match data.strip_prefix(&vars[name][..]) {
+            Some(data) => match_tokens(rest, data, vars, captures),
+            None => false,
+        },
+        Token::Capture { name, len } => {
+            let lens = match len {
+                Some(len) if *len <= data.len() => *len ..= *len,
+                Some(_) => return false,
+                None => 0 ..= data.len(),
+            };
+            for len in lens.rev() {
+                captures.push((name, &data[.. len]));
+                if match_tokens(rest, &data[len ..], vars, captures) {
+                    return true;
+                }
+                captures.pop();
+            }
+            false
+        },
+        Token::NotWord { value, mask } => match data {
+            [hi, lo, data @ ..] => {
+                u16::from_be_bytes([*hi, *lo]) & mask != value & mask && match_tokens(rest, data, vars, captures)
+            },
+            _ => false,
+        },
+    }
+}
+
+/// Split a template or pattern into tokens, whitespace is ignored
+fn lex(text: &str) -> Result<Vec<Token>, ParseErrorKind> {
+    let syntax = |at: &str| ParseErrorKind::Syntax(at.to_string());
+    let mut tokens = Vec::new();
+    // pending nibble: (value, mask)
+    let mut nibble: Option<(u8, u8)> = None;
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let at = rest;
+        rest = &rest[c.len_utf8() ..];
+
+        let digit = match c {
+            c if c.is_whitespace() => continue,
+            '?' | 'x' | 'X' => Some((0, 0)),
+            c => c.to_digit(16).map(|d| (d as u8, 0xf)),
+        };
+        if let Some((value, mask)) = digit {
+            nibble = match nibble.take() {
+                Some((hi, hi_mask)) => {
+                    tokens.push(Token::Byte { value: (hi << 4) | value, mask: (hi_mask << 4) | mask });
+                    None
+                },
+                None => Some((value, mask)),
+            };
+            continue;
+        }
+        if nibble.is_some() {
+            return Err(ParseErrorKind::OddDigits);
+        }
+
+        match c {
+            '*' => tokens.push(Token::Any),
+            '$' => {
+                let (name, after) = braced(rest)
+                    .filter(|(name, _)| !name.contains(':'))
+                    .ok_or_else(|| syntax(at))?;
+                tokens.push(Token::Var(name.to_string()));
+                rest = after;
+            },
+            '{' => {
+                let (inner, after) = braced(at).ok_or_else(|| syntax(at))?;
+                let (name, len) = match inner.split_once(':') {
+                    Some((name, len)) => (name, Some(len.parse().map_err(|_| syntax(at))?)),
+                    None => (inner, None),
+                };
+                tokens.push(Token::Capture { name: name.to_string(), len });
+                rest = after;
+            },
+            '!' => {
+                let word = rest
+                    .get(.. 4)
+                    .ok_or_else(|| syntax(at))?;
+                let (mut value, mut mask) = (0u16, 0u16);
+                for c in word.chars() {
+                    let (v, m) = match c {
+                        '?' | 'x' | 'X' => (0, 0),
+                        c => (
+                            c.to_digit(16)
+                                .ok_or_else(|| syntax(at))? as u16,
+                            0xf,
+                        ),
+                    };
+                    value = (value << 4) | v;
+                    mask = (mask << 4) | m;
+                }
+                tokens.push(Token::NotWord { value, mask });
+                rest = &rest[4 ..];
+            },
+            _ => return Err(syntax(at)),
+        }
+    }
+
+    if nibble.is_some() {
+        return Err(ParseErrorKind::OddDigits);
+    }
+    Ok(tokens)
+}
+
+/// Split `{name}rest` into `name` and `rest`
+fn braced(text: &str) -> Option<(&str, &str)> {
+    let (inner, rest) = text
+        .strip_prefix('{')?
+        .split_once('}')?;
+    let valid = !inner.is_empty()
+        && inner
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+    valid.then_some((inner, rest))
+}
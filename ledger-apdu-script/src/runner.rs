@@ -0,0 +1,251 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Run a [Script] over an [Exchange]
+
+use std::fmt;
+
+use futures_timer::Delay;
+use ledger_transport::{APDUCommand, Exchange};
+
+use crate::{
+    pattern::Vars,
+    script::{Script, Step},
+};
+
+/// Result of an exchange
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The answer matched
+    Passed,
+    /// The answer didn't match the expected pattern
+    Failed {
+        /// Expected pattern
+        expected: String,
+    },
+    /// The command couldn't be sent or answered
+    Error(String),
+}
+
+/// Report of a single exchange
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepReport {
+    /// Line of the command in the script
+    pub line: usize,
+    /// Command sent, empty if it couldn't be built
+    pub command: Vec<u8>,
+    /// Answer received, status word included
+    pub answer: Option<Vec<u8>>,
+    /// Result of the exchange
+    pub outcome: Outcome,
+}
+
+/// Report of a script run, see [Runner::run]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Report of every exchange, in order
+    pub steps: Vec<StepReport>,
+}
+
+impl Report {
+    /// Number of exchanges that passed
+    pub fn passed(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| step.outcome == Outcome::Passed)
+            .count()
+    }
+
+    /// Number of exchanges that failed or errored
+    pub fn failed(&self) -> usize {
+        self.steps.len() - self.passed()
+    }
+
+    /// Whether every exchange passed
+    pub fn success(&self) -> bool {
+        self.failed() == 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        for step in &self.steps {
+            let answer = step
+                .answer
+                .as_deref()
+                .map(hex::encode)
+                .unwrap_or_default();
+            match &step.outcome {
+                Outcome::Passed => {
+                    writeln!(f, "PASS line {}: => {} <= {}", step.line, hex::encode(&step.command), answer)?
+                },
+                Outcome::Failed { expected } => writeln!(
+                    f,
+                    "FAIL line {}: => {} <= {}, expected {}",
+                    step.line,
+                    hex::encode(&step.command),
+                    answer,
+                    expected
+                )?,
+                Outcome::Error(err) => writeln!(f, "ERROR line {}: {}", step.line, err)?,
+            }
+        }
+        write!(f, "{} passed, {} failed", self.passed(), self.failed())
+    }
+}
+
+/// Runs scripts over a transport, keeping variables between scripts
+pub struct Runner<'a, E> {
+    transport: &'a E,
+    vars: Vars,
+    stop_on_failure: bool,
+}
+
+impl<'a, E> Runner<'a, E>
+where
+    E: Exchange + Send + Sync,
+    E::Error: std::error::Error,
+{
+    /// Run scripts over `transport`
+    pub fn new(transport: &'a E) -> Self {
+        Self { transport, vars: Vars::new(), stop_on_failure: false }
+    }
+
+    /// Define a variable before running
+    pub fn with_var(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.vars
+            .insert(name.into(), value.into());
+        self
+    }
+
+    /// Stop at the first exchange that doesn't pass
+    pub fn with_stop_on_failure(
+        mut self,
+        stop: bool,
+    ) -> Self {
+        self.stop_on_failure = stop;
+        self
+    }
+
+    /// Variables set or captured so far
+    pub fn vars(&self) -> &Vars {
+        &self.vars
+    }
+
+    /// Run `script`, reporting every exchange
+    pub async fn run(
+        &mut self,
+        script: &Script,
+    ) -> Report {
+        let mut report = Report::default();
+        // each running block with its next step and remaining iterations
+        let mut stack: Vec<(&[Step], usize, usize)> = vec![(&script.steps, 0, 1)];
+
+        while let Some((steps, next, remaining)) = stack.last_mut() {
+            let Some(step) = steps.get(*next) else {
+                *remaining -= 1;
+                if *remaining == 0 {
+                    stack.pop();
+                } else {
+                    *next = 0;
+                }
+                continue;
+            };
+            *next += 1;
+
+            match step {
+                Step::Exchange { .. } => {
+                    let step = self.exchange(step).await;
+                    let passed = step.outcome == Outcome::Passed;
+                    report.steps.push(step);
+                    if !passed && self.stop_on_failure {
+                        break;
+                    }
+                },
+                Step::Set { line, name, value } => match value.render(&self.vars) {
+                    Ok(value) => {
+                        self.vars.insert(name.clone(), value);
+                    },
+                    Err(var) => {
+                        report.steps.push(StepReport {
+                            line: *line,
+                            command: Vec::new(),
+                            answer: None,
+                            outcome: Outcome::Error(format!("undefined variable `{var}`")),
+                        });
+                        if self.stop_on_failure {
+                            break;
+                        }
+                    },
+                },
+                Step::Repeat { steps, count, .. } if *count > 0 => stack.push((steps, 0, *count)),
+                Step::Repeat { .. } => {},
+                Step::Sleep { duration, .. } => Delay::new(*duration).await,
+            }
+        }
+
+        report
+    }
+
+    async fn exchange(
+        &mut self,
+        step: &Step,
+    ) -> StepReport {
+        let Step::Exchange { line, command, expect } = step else {
+            unreachable!("only called on exchanges");
+        };
+        let mut report = StepReport { line: *line, command: Vec::new(), answer: None, outcome: Outcome::Passed };
+
+        report.command = match command.render(&self.vars) {
+            Ok(command) => command,
+            Err(var) => {
+                report.outcome = Outcome::Error(format!("undefined variable `{var}`"));
+                return report;
+            },
+        };
+        let command = match APDUCommand::from_bytes(&report.command) {
+            Ok(command) => command,
+            Err(err) => {
+                report.outcome = Outcome::Error(err.to_string());
+                return report;
+            },
+        };
+
+        let answer = match self.transport.exchange(&command).await {
+            Ok(answer) => [answer.data(), &answer.retcode().to_be_bytes()].concat(),
+            Err(err) => {
+                report.outcome = Outcome::Error(err.to_string());
+                return report;
+            },
+        };
+
+        if let Some(expect) = expect {
+            match expect.matches(&answer, &self.vars) {
+                Ok(Some(captures)) => self.vars.extend(captures),
+                Ok(None) => report.outcome = Outcome::Failed { expected: expect.to_string() },
+                Err(var) => report.outcome = Outcome::Error(format!("undefined variable `{var}`")),
+            }
+        }
+        report.answer = Some(answer);
+        report
+    }
+}
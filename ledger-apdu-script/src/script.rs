@@ -0,0 +1,183 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Script format
+//!
+//! One directive per line, `#` starts a comment:
+//!
+//! * `=> <template>` sends a command
+//! * `<= <pattern>` checks the answer to the previous command
+//! * `set <name> = <template>` sets a variable
+//! * `repeat <count>` ... `end` runs the enclosed lines `count` times
+//! * `sleep <duration>` waits, e.g. `sleep 500ms` or `sleep 2s`
+
+use std::time::Duration;
+
+use crate::{
+    errors::{ParseError, ParseErrorKind},
+    pattern::{Pattern, Template},
+};
+
+/// A parsed script
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Script {
+    /// Top level steps
+    pub steps: Vec<Step>,
+}
+
+/// A step of a [Script]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Send a command and check its answer
+    Exchange {
+        /// Line of the command
+        line: usize,
+        /// Serialized APDU command
+        command: Template,
+        /// Expected answer, any answer is accepted if `None`
+        expect: Option<Pattern>,
+    },
+    /// Set a variable
+    Set {
+        /// Line of the directive
+        line: usize,
+        /// Variable name
+        name: String,
+        /// Variable value
+        value: Template,
+    },
+    /// Run steps repeatedly
+    Repeat {
+        /// Line of the directive
+        line: usize,
+        /// Number of iterations
+        count: usize,
+        /// Steps to repeat
+        steps: Vec<Step>,
+    },
+    /// Wait before the next step
+    Sleep {
+        /// Line of the directive
+        line: usize,
+        /// How long to wait
+        duration: Duration,
+    },
+}
+
+impl Script {
+    /// Parse a script
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        // each open `repeat` with its line, count and steps so far
+        let mut blocks: Vec<(usize, usize, Vec<Step>)> = Vec::new();
+        let mut steps = Vec::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line_no = idx + 1;
+            let err = |kind| ParseError { line: line_no, kind };
+            let line = line
+                .split_once('#')
+                .map_or(line, |(code, _)| code)
+                .trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (directive, args) = line
+                .split_once(char::is_whitespace)
+                .map_or((line, ""), |(directive, args)| (directive, args.trim()));
+            let steps_mut = match blocks.last_mut() {
+                Some((_, _, steps)) => steps,
+                None => &mut steps,
+            };
+
+            match directive {
+                "=>" => {
+                    let command = Template::parse(args).map_err(err)?;
+                    if let Some(len) = command
+                        .static_len()
+                        .filter(|len| *len < 4)
+                    {
+                        return Err(err(ParseErrorKind::CommandTooShort(len)));
+                    }
+                    steps_mut.push(Step::Exchange { line: line_no, command, expect: None });
+                },
+                "<=" => match steps_mut.last_mut() {
+                    Some(Step::Exchange { expect: expect @ None, .. }) => {
+                        *expect = Some(Pattern::parse(args).map_err(err)?);
+                    },
+                    _ => return Err(err(ParseErrorKind::UnexpectedAnswer)),
+                },
+                "set" => {
+                    let (name, value) = args
+                        .split_once('=')
+                        .ok_or_else(|| err(ParseErrorKind::Syntax(args.to_string())))?;
+                    let name = name.trim();
+                    if name.is_empty()
+                        || !name
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        return Err(err(ParseErrorKind::Syntax(name.to_string())));
+                    }
+                    let value = Template::parse(value).map_err(err)?;
+                    steps_mut.push(Step::Set { line: line_no, name: name.to_string(), value });
+                },
+                "repeat" => {
+                    let count = args
+                        .parse()
+                        .map_err(|_| err(ParseErrorKind::InvalidCount(args.to_string())))?;
+                    blocks.push((line_no, count, Vec::new()));
+                },
+                "end" if args.is_empty() => {
+                    let (line, count, block) = blocks
+                        .pop()
+                        .ok_or_else(|| err(ParseErrorKind::UnexpectedEnd))?;
+                    let steps_mut = match blocks.last_mut() {
+                        Some((_, _, steps)) => steps,
+                        None => &mut steps,
+                    };
+                    steps_mut.push(Step::Repeat { line, count, steps: block });
+                },
+                "sleep" => {
+                    let duration =
+                        parse_duration(args).ok_or_else(|| err(ParseErrorKind::InvalidDuration(args.to_string())))?;
+                    steps_mut.push(Step::Sleep { line: line_no, duration });
+                },
+                _ => return Err(err(ParseErrorKind::UnknownDirective(line.to_string()))),
+            }
+        }
+
+        if let Some((line, ..)) = blocks.pop() {
+            return Err(ParseError { line, kind: ParseErrorKind::MissingEnd });
+        }
+        Ok(Script { steps })
+    }
+}
+
+/// Parse `500ms` or `2s`
+fn parse_duration(text: &str) -> Option<Duration> {
+    if let Some(ms) = text.strip_suffix("ms") {
+        return ms
+            .trim()
+            .parse()
+            .ok()
+            .map(Duration::from_millis);
+    }
+    text.strip_suffix('s')?
+        .trim()
+        .parse()
+        .ok()
+        .and_then(|secs: f64| Duration::try_from_secs_f64(secs).ok())
+}
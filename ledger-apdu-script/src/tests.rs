@@ -0,0 +1,148 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::time::Duration;
+
+use futures::executor::block_on;
+use ledger_transport::mock::MockTransport;
+
+use super::*;
+
+#[test]
+fn parse_script() {
+    let script = Script::parse(
+        "# header
+        => e0 01 00 00 00   # version
+        <= * 9000
+        set account = 00000001
+        repeat 2
+            => e0 02 00 00 04 ${account}
+            sleep 10ms
+        end",
+    )
+    .expect("valid script");
+
+    assert_eq!(script.steps.len(), 3);
+    assert!(matches!(script.steps[0], Step::Exchange { line: 2, expect: Some(_), .. }));
+    match &script.steps[2] {
+        Step::Repeat { line: 5, count: 2, steps } => {
+            assert!(matches!(steps[1], Step::Sleep { line: 7, duration } if duration == Duration::from_millis(10)))
+        },
+        step => panic!("unexpected step {step:?}"),
+    }
+}
+
+#[test]
+fn parse_errors() {
+    let kind = |script: &str| {
+        Script::parse(script)
+            .map(|_| ())
+            .map_err(|err| err.kind)
+    };
+
+    assert_eq!(kind("=> e0 01 0"), Err(ParseErrorKind::OddDigits));
+    assert_eq!(kind("=> e0 01"), Err(ParseErrorKind::CommandTooShort(2)));
+    assert_eq!(kind("<= 9000"), Err(ParseErrorKind::UnexpectedAnswer));
+    assert_eq!(kind("=> e0010000\n<= 9000\n<= 9000"), Err(ParseErrorKind::UnexpectedAnswer));
+    assert_eq!(kind("repeat 2\n=> e0010000"), Err(ParseErrorKind::MissingEnd));
+    assert_eq!(kind("end"), Err(ParseErrorKind::UnexpectedEnd));
+    assert_eq!(kind("sleep soon"), Err(ParseErrorKind::InvalidDuration("soon".to_string())));
+    assert_eq!(kind("=> e0010000\n<= zz"), Err(ParseErrorKind::Syntax("zz".to_string())));
+    assert_eq!(
+        Script::parse("\n\nreboot")
+            .unwrap_err()
+            .line,
+        3
+    );
+}
+
+#[test]
+fn patterns() {
+    let vars = Vars::from([("id".to_string(), vec![0xaa, 0xbb])]);
+    let matches = |pattern: &str, answer: &[u8]| {
+        Pattern::parse(pattern)
+            .expect("valid pattern")
+            .matches(answer, &vars)
+            .expect("defined variables")
+    };
+
+    assert!(matches("01 02 9000", &[1, 2, 0x90, 0x00]).is_some());
+    assert!(matches("01 ?? 9000", &[1, 7, 0x90, 0x00]).is_some());
+    assert!(matches("* 69xx", &[1, 2, 3, 0x69, 0x85]).is_some());
+    assert!(matches("* 9000", &[0x69, 0x85]).is_none());
+    assert!(matches("* !9000", &[0x69, 0x85]).is_some());
+    assert!(matches("* !9000", &[0x90, 0x00]).is_none());
+    assert!(matches("${id} 9000", &[0xaa, 0xbb, 0x90, 0x00]).is_some());
+    assert!(matches("01 9000", &[1, 2, 0x90, 0x00]).is_none());
+
+    let captures = matches("{head:1} {body} 9000", &[1, 2, 3, 0x90, 0x00]).expect("matches");
+    assert_eq!(captures["head"], vec![1]);
+    assert_eq!(captures["body"], vec![2, 3]);
+
+    assert_eq!(
+        Pattern::parse("${missing}")
+            .unwrap()
+            .matches(&[], &vars),
+        Err("missing".to_string())
+    );
+}
+
+#[test]
+fn run_script() {
+    let script = Script::parse(
+        "=> e0 01 00 00 00
+        <= {major:1} * 9000
+        repeat 2
+            => e0 02 00 00 01 ${major}
+            <= 9000
+        end
+        => e0 03 00 00 00
+        <= * 9000",
+    )
+    .unwrap();
+    let transport = MockTransport::new(&[&[0x02, 0x05, 0x90, 0x00], &[0x90, 0x00], &[0x90, 0x00], &[0x69, 0x85]]);
+
+    let mut runner = Runner::new(&transport);
+    let report = block_on(runner.run(&script));
+
+    assert_eq!(transport.sent()[1], vec![0xe0, 0x02, 0x00, 0x00, 0x01, 0x02]);
+    assert_eq!(runner.vars()["major"], vec![0x02]);
+    assert_eq!((report.passed(), report.failed()), (3, 1));
+    assert_eq!(report.steps[3].line, 7);
+    assert_eq!(report.steps[3].outcome, Outcome::Failed { expected: "* 9000".to_string() });
+    assert!(report
+        .to_string()
+        .ends_with("FAIL line 7: => e003000000 <= 6985, expected * 9000\n3 passed, 1 failed"));
+}
+
+#[test]
+fn run_stop_on_failure() {
+    let script = Script::parse("=> e0010000\n<= 9000\n=> e0${missing}0000\n=> e0020000").unwrap();
+    let transport = MockTransport::new(&[&[0x69, 0x85], &[0x90, 0x00]]);
+
+    let report = block_on(Runner::new(&transport).run(&script));
+    assert_eq!(report.steps.len(), 3);
+    assert_eq!(report.steps[1].outcome, Outcome::Error("undefined variable `missing`".to_string()));
+    assert!(report.steps[2].outcome == Outcome::Passed);
+
+    let transport = MockTransport::new(&[&[0x69, 0x85], &[0x90, 0x00]]);
+    let report = block_on(
+        Runner::new(&transport)
+            .with_stop_on_failure(true)
+            .run(&script),
+    );
+    assert_eq!(report.steps.len(), 1);
+    assert!(!report.success());
+}
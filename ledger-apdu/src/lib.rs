@@ -24,6 +24,9 @@ use std::convert::{TryFrom, TryInto};
 
 use snafu::prelude::*;
 
+#[cfg(feature = "serde")]
+pub mod num;
+
 #[cfg(test)]
 mod tests;
 
@@ -44,6 +47,45 @@ pub struct APDUCommand<B> {
     pub data: B,
 }
 
+#[derive(Debug, Snafu, PartialEq, Eq)]
+/// Error interpreting bytes as an APDU command
+pub enum APDUCommandError {
+    #[snafu(display("command of {len} bytes is shorter than an APDU header"))]
+    /// Less than the 4 bytes of CLA, INS, P1 and P2
+    HeaderTooShort {
+        /// Length of the command
+        len: usize,
+    },
+    #[snafu(display("length byte is {expected}, but {got} bytes of payload follow"))]
+    /// The length byte doesn't match the payload, which can't be longer than 255 bytes
+    LengthMismatch {
+        /// Value of the length byte
+        expected: usize,
+        /// Length of the payload
+        got: usize,
+    },
+}
+
+impl<'a> APDUCommand<&'a [u8]> {
+    /// Attempt to interpret the given slice as a serialized APDU command
+    ///
+    /// CLA, INS, P1, P2, then the payload length and the payload. The length byte can be omitted when there's no payload
+    pub fn from_bytes(command: &'a [u8]) -> Result<Self, APDUCommandError> {
+        let [cla, ins, p1, p2, ref rest @ ..] = *command else {
+            return HeaderTooShortSnafu { len: command.len() }.fail();
+        };
+        let data = match rest {
+            [] => rest,
+            [len, data @ ..] => {
+                ensure!(*len as usize == data.len(), LengthMismatchSnafu { expected: *len as usize, got: data.len() });
+                data
+            },
+        };
+
+        Ok(APDUCommand { cla, ins, p1, p2, data })
+    }
+}
+
 #[cfg(feature = "std")]
 impl<B> APDUCommand<B>
 where
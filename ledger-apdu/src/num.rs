@@ -0,0 +1,76 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Deserialization of APDU fields written as numbers or as decimal or `0x` hex strings
+//!
+//! Meant for `#[serde(deserialize_with = "...")]` in configuration files, e.g. `cla = "0x55"`
+
+use std::{string::String, vec::Vec};
+
+use serde::{de, Deserialize, Deserializer};
+
+/// A number, or its text in decimal or `0x` hex
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Num {
+    Int(u64),
+    Str(String),
+}
+
+impl Num {
+    fn value<E: de::Error>(self) -> Result<u64, E> {
+        match self {
+            Num::Int(value) => Ok(value),
+            Num::Str(text) => parse_num(&text).ok_or_else(|| E::custom(std::format!("invalid number `{text}`"))),
+        }
+    }
+
+    fn byte<E: de::Error>(self) -> Result<u8, E> {
+        let value = self.value()?;
+        u8::try_from(value).map_err(|_| E::custom(std::format!("{value} is not a byte")))
+    }
+}
+
+/// Parse a number in decimal or `0x` hex
+pub fn parse_num(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Deserialize a byte
+pub fn de_u8<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    Num::deserialize(deserializer)?.byte()
+}
+
+/// Deserialize a byte into `Some`, for optional fields with `#[serde(default)]`
+pub fn de_opt_u8<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u8>, D::Error> {
+    de_u8(deserializer).map(Some)
+}
+
+/// Deserialize a 16 bits number, e.g. a status word
+pub fn de_u16<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    let value = Num::deserialize(deserializer)?.value()?;
+    u16::try_from(value).map_err(|_| de::Error::custom(std::format!("{value} doesn't fit 16 bits")))
+}
+
+/// Deserialize a list of bytes
+pub fn de_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    Vec::<Num>::deserialize(deserializer)?
+        .into_iter()
+        .map(Num::byte)
+        .collect()
+}
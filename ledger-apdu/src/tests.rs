@@ -35,6 +35,32 @@ fn apdu_command_slice() {
     let _ = APDUCommand { cla: 0xFF, ins: 0x00, p1: 0, p2: 0, data };
 }
 
+#[test]
+fn apdu_command_from_bytes() {
+    let command = APDUCommand::from_bytes(SERIALIZED_APDU).expect("valid command");
+    assert_eq!((command.cla, command.ins, command.data), (0xFF, 0x00, &SERIALIZED_APDU[5 ..]));
+
+    let command = APDUCommand::from_bytes(&[0xe0, 0x01, 0, 0]).expect("length byte omitted");
+    assert!(command.data.is_empty());
+    assert!(APDUCommand::from_bytes(&[0xe0, 0x01, 0, 0, 0])
+        .expect("empty payload")
+        .data
+        .is_empty());
+
+    assert_eq!(APDUCommand::from_bytes(&[0xe0, 0x01, 0]).unwrap_err(), APDUCommandError::HeaderTooShort { len: 3 });
+    assert_eq!(APDUCommand::from_bytes(&SERIALIZED_APDU[.. 7]).unwrap_err(), APDUCommandError::LengthMismatch {
+        expected: 3,
+        got: 2
+    });
+
+    let mut long = [0x42; 5 + 300];
+    long[4] = 44;
+    assert_eq!(APDUCommand::from_bytes(&long).unwrap_err(), APDUCommandError::LengthMismatch {
+        expected: 44,
+        got: 300
+    });
+}
+
 #[test]
 fn apdu_answer_success() {
     let answer = APDUAnswer::from_answer(APDU_RESPONSE).expect("valid answer length >= 2");
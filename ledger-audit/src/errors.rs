@@ -0,0 +1,88 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::{io, sync::Arc};
+
+use ledger_transport::{TransportError, TransportErrorKind};
+use thiserror::Error;
+
+/// Error of an [AuditedTransport](crate::AuditedTransport) exchange
+#[derive(Clone, Debug, Error)]
+pub enum AuditError<E> {
+    /// The wrapped transport failed
+    #[error("Transport | {0}")]
+    Transport(E),
+    /// The audit log could not be written, the command was not sent or its answer is withheld
+    #[error("audit log | {0}")]
+    Log(#[source] Arc<io::Error>),
+}
+
+impl<E: TransportError> TransportError for AuditError<E> {
+    fn kind(&self) -> TransportErrorKind {
+        match self {
+            Self::Transport(err) => err.kind(),
+            Self::Log(err) => err.kind().into(),
+        }
+    }
+}
+
+/// Why an audit log failed verification
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum VerifyErrorKind {
+    /// The line is not a record
+    #[error("malformed record | {0}")]
+    Malformed(String),
+    /// A record is missing or out of order
+    #[error("expected record {expected}, found {got}")]
+    Sequence {
+        /// Expected `seq`
+        expected: u64,
+        /// `seq` of the record
+        got: u64,
+    },
+    /// `prev_hash` is not the hash of the previous record
+    #[error("broken hash chain")]
+    Chain,
+    /// The record content doesn't match its hash
+    #[error("record hash mismatch")]
+    Hash,
+    /// The log ends before the expected head
+    #[error("log truncated, head is {found}")]
+    Truncated {
+        /// Hash of the last record found
+        found: String,
+    },
+}
+
+/// Audit log verification failure
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[error("line {line}: {kind}")]
+pub struct VerifyError {
+    /// Line of the log, from 1
+    pub line: usize,
+    /// What is wrong
+    pub kind: VerifyErrorKind,
+}
+
+/// Error opening an existing audit log
+#[derive(Debug, Error)]
+pub enum OpenError {
+    /// The file could not be read or opened for appending
+    #[error("I/O | {0}")]
+    Io(#[from] io::Error),
+    /// The existing records failed verification
+    #[error("{0}")]
+    Verify(#[from] VerifyError),
+}
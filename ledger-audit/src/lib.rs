@@ -0,0 +1,46 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Tamper-evident audit log of the APDUs exchanged with Ledger devices
+//!
+//! [AuditedTransport] wraps any [Exchange](ledger_transport::Exchange) and appends every command
+//! and answer to an [AuditLog], one JSON record per line. Each record carries the SHA-256 of the
+//! previous one, so [verify] detects modified, reordered or removed records, and [verify_head]
+//! detects a truncated log given a head hash kept elsewhere.
+//!
+//! ```
+//! use ledger_audit::{verify, AuditLog, Event, Payload};
+//!
+//! let mut log = AuditLog::new(Vec::new());
+//! let event = Event::Command { cla: 0xb0, ins: 0x01, p1: 0, p2: 0, payload: Payload::new(&[], false) };
+//! log.append(Some("usb-1".into()), None, event).unwrap();
+//!
+//! let head = verify(log.into_inner().as_slice()).unwrap();
+//! assert_eq!(head.records, 1);
+//! ```
+
+#![deny(warnings, unused_qualifications, missing_docs)]
+
+mod errors;
+pub mod log;
+pub mod record;
+#[cfg(test)]
+mod tests;
+pub mod transport;
+
+pub use errors::{AuditError, OpenError, VerifyError, VerifyErrorKind};
+pub use log::{verify, verify_head, AuditLog, Head};
+pub use record::{AppId, AuditRecord, Event, Payload, GENESIS_HASH};
+pub use transport::{AuditedTransport, Redaction};
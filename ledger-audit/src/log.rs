@@ -0,0 +1,191 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Append-only audit log and its verifier
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{
+    errors::OpenError,
+    record::{AppId, AuditRecord, Event, GENESIS_HASH},
+    VerifyError, VerifyErrorKind,
+};
+
+/// End of a verified log
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Head {
+    /// Number of records
+    pub records: u64,
+    /// Hash of the last record, [GENESIS_HASH] for an empty log
+    pub hash: String,
+}
+
+impl Default for Head {
+    fn default() -> Self {
+        Self { records: 0, hash: GENESIS_HASH.to_string() }
+    }
+}
+
+/// Writer appending hash-chained [AuditRecord]s, one JSON object per line
+///
+/// Every record is flushed before [AuditLog::append] returns
+pub struct AuditLog<W> {
+    writer: W,
+    head: Head,
+}
+
+impl<W: Write> AuditLog<W> {
+    /// Start a new log
+    pub fn new(writer: W) -> Self {
+        Self { writer, head: Head::default() }
+    }
+
+    /// Continue a log ending at `head`, as returned by [verify]
+    pub fn resume(
+        writer: W,
+        head: Head,
+    ) -> Self {
+        Self { writer, head }
+    }
+
+    /// End of the log
+    ///
+    /// Keep it out of reach of the log writer, e.g. publish it periodically:
+    /// [verify_head] with it detects a truncated log
+    pub fn head(&self) -> &Head {
+        &self.head
+    }
+
+    /// Append a record for `event` and return it
+    pub fn append(
+        &mut self,
+        device: Option<String>,
+        app: Option<AppId>,
+        event: Event,
+    ) -> io::Result<AuditRecord> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+
+        let record = AuditRecord {
+            seq: self.head.records,
+            timestamp_ms,
+            device,
+            app,
+            event,
+            prev_hash: self.head.hash.clone(),
+            hash: String::new(),
+        }
+        .seal();
+
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        self.writer.flush()?;
+
+        self.head = Head { records: record.seq + 1, hash: record.hash.clone() };
+        Ok(record)
+    }
+
+    /// The underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl AuditLog<File> {
+    /// Open the log at `path` for appending, creating it if needed
+    ///
+    /// The existing records are verified first
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, OpenError> {
+        let path = path.as_ref();
+        let head = match File::open(path) {
+            Ok(file) => verify(BufReader::new(file))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Head::default(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self::resume(file, head))
+    }
+}
+
+/// Check the records of a log: their sequence, their hashes and the chain between them
+///
+/// Detects modified, reordered, inserted or removed records, but not records removed from the
+/// end of the log; use [verify_head] for that
+pub fn verify<R: BufRead>(reader: R) -> Result<Head, VerifyError> {
+    walk(reader, |_| {}).map(|(head, _)| head)
+}
+
+/// [verify] a log and check it reaches `expected`, a hash previously taken from [AuditLog::head]
+///
+/// Records appended since `expected` was taken are accepted
+pub fn verify_head<R: BufRead>(
+    reader: R,
+    expected: &str,
+) -> Result<Head, VerifyError> {
+    let mut reached = expected == GENESIS_HASH;
+    let (head, lines) = walk(reader, |record| reached |= record.hash == expected)?;
+
+    if !reached {
+        return Err(VerifyError { line: lines + 1, kind: VerifyErrorKind::Truncated { found: head.hash } });
+    }
+    Ok(head)
+}
+
+/// Verify every record, passing them to `visit`, and return the head and the number of lines
+fn walk<R: BufRead>(
+    reader: R,
+    mut visit: impl FnMut(&AuditRecord),
+) -> Result<(Head, usize), VerifyError> {
+    let mut head = Head::default();
+    let mut lines = 0;
+
+    for line in reader.lines() {
+        lines += 1;
+        let fail = |kind| VerifyError { line: lines, kind };
+
+        let line = line.map_err(|err| fail(VerifyErrorKind::Malformed(err.to_string())))?;
+        let record: AuditRecord =
+            serde_json::from_str(&line).map_err(|err| fail(VerifyErrorKind::Malformed(err.to_string())))?;
+
+        if record.seq != head.records {
+            return Err(fail(VerifyErrorKind::Sequence { expected: head.records, got: record.seq }));
+        }
+        if record.prev_hash != head.hash {
+            return Err(fail(VerifyErrorKind::Chain));
+        }
+        if record.compute_hash() != record.hash {
+            return Err(fail(VerifyErrorKind::Hash));
+        }
+
+        visit(&record);
+        head = Head { records: record.seq + 1, hash: record.hash };
+    }
+
+    Ok((head, lines))
+}
@@ -0,0 +1,155 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Audit records and their hash chain
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// `prev_hash` of the first record of a log
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// App open on the device
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppId {
+    /// App name
+    pub name: String,
+    /// App version, if known
+    pub version: Option<String>,
+}
+
+/// APDU payload, in clear or redacted
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind", deny_unknown_fields)]
+pub enum Payload {
+    /// Payload bytes in hex
+    Clear {
+        /// Payload in hex
+        hex: String,
+    },
+    /// Payload replaced by its hash
+    Redacted {
+        /// SHA-256 of the payload, in hex
+        sha256: String,
+        /// Payload length
+        len: usize,
+    },
+}
+
+impl Payload {
+    /// Payload `data`, redacted or not
+    pub fn new(
+        data: &[u8],
+        redact: bool,
+    ) -> Self {
+        if redact {
+            Payload::Redacted { sha256: hex::encode(Sha256::digest(data)), len: data.len() }
+        } else {
+            Payload::Clear { hex: hex::encode(data) }
+        }
+    }
+}
+
+/// What a record is about
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type", deny_unknown_fields)]
+pub enum Event {
+    /// A command about to be sent
+    #[serde(rename_all = "camelCase")]
+    Command {
+        /// Class
+        cla: u8,
+        /// Instruction
+        ins: u8,
+        /// First parameter
+        p1: u8,
+        /// Second parameter
+        p2: u8,
+        /// Command payload
+        payload: Payload,
+    },
+    /// The answer to a command
+    #[serde(rename_all = "camelCase")]
+    Answer {
+        /// `seq` of the command record
+        command_seq: u64,
+        /// Status word
+        status_word: u16,
+        /// Answer payload, without the status word
+        payload: Payload,
+    },
+    /// A command refused before reaching the device
+    #[serde(rename_all = "camelCase")]
+    Denied {
+        /// Class
+        cla: u8,
+        /// Instruction
+        ins: u8,
+        /// First parameter
+        p1: u8,
+        /// Second parameter
+        p2: u8,
+        /// Command payload
+        payload: Payload,
+        /// Why the command was refused
+        reason: String,
+    },
+    /// The command failed at the transport level
+    #[serde(rename_all = "camelCase")]
+    Error {
+        /// `seq` of the command record
+        command_seq: u64,
+        /// Transport error
+        error: String,
+    },
+}
+
+/// A line of the audit log
+///
+/// Unknown fields are rejected when reading a record, as they wouldn't be covered by its hash
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AuditRecord {
+    /// Position in the log, from 0
+    pub seq: u64,
+    /// Milliseconds since the unix epoch
+    pub timestamp_ms: u64,
+    /// Device the APDU was exchanged with, e.g. its HID path
+    pub device: Option<String>,
+    /// App open on the device, when known
+    pub app: Option<AppId>,
+    /// What happened
+    pub event: Event,
+    /// `hash` of the previous record, [GENESIS_HASH] for the first one
+    pub prev_hash: String,
+    /// SHA-256 of the record, with an empty `hash`
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// Hash of the record, computed over its JSON serialization with an empty `hash`
+    pub fn compute_hash(&self) -> String {
+        let unsealed = AuditRecord { hash: String::new(), ..self.clone() };
+        let json = serde_json::to_vec(&unsealed).expect("records serialize");
+        hex::encode(Sha256::digest(json))
+    }
+
+    /// Set `hash`
+    pub(crate) fn seal(mut self) -> Self {
+        self.hash = self.compute_hash();
+        self
+    }
+}
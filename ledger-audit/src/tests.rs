@@ -0,0 +1,245 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::io::{self, Write};
+
+use futures::executor::block_on;
+use ledger_transport::{mock::MockTransport, APDUCommand, Exchange};
+
+use super::*;
+
+/// Writer failing on every write
+struct BrokenWriter;
+
+impl Write for BrokenWriter {
+    fn write(
+        &mut self,
+        _: &[u8],
+    ) -> io::Result<usize> {
+        Err(io::Error::other("disk full"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn command(
+    cla: u8,
+    ins: u8,
+    data: &[u8],
+) -> APDUCommand<Vec<u8>> {
+    APDUCommand { cla, ins, p1: 0, p2: 0, data: data.to_vec() }
+}
+
+/// Log of a GET_APP_INFO, a sign command and a failed exchange
+fn sample_log(redaction: Redaction) -> Vec<u8> {
+    let transport = MockTransport::new(&[b"\x01\x06Cosmos\x052.1.0\x01\x00\x90\x00", b"\x30\x44\x90\x00"]);
+    let audited = AuditedTransport::new(&transport, AuditLog::new(Vec::new()))
+        .with_device("1-1:1.0")
+        .with_redaction(redaction);
+
+    block_on(async {
+        audited
+            .exchange(&command(0xb0, 0x01, &[]))
+            .await
+            .unwrap();
+        audited
+            .exchange(&command(0x55, 0x02, b"secret"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            audited
+                .exchange(&command(0x55, 0x02, b"again"))
+                .await,
+            Err(AuditError::Transport(_))
+        ));
+    });
+
+    audited.into_parts().1.into_inner()
+}
+
+fn records(log: &[u8]) -> Vec<AuditRecord> {
+    log.split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect()
+}
+
+fn lines(log: &[u8]) -> Vec<String> {
+    String::from_utf8(log.to_vec())
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+fn join(lines: &[String]) -> Vec<u8> {
+    lines
+        .iter()
+        .flat_map(|line| format!("{}\n", line).into_bytes())
+        .collect()
+}
+
+#[test]
+fn audit_records_exchanges() {
+    let log = sample_log(Redaction::None);
+    let records = records(&log);
+    assert_eq!(records.len(), 6);
+
+    let cosmos = AppId { name: "Cosmos".to_string(), version: Some("2.1.0".to_string()) };
+    assert_eq!(records[0].app, None);
+    assert_eq!(records[2].app, Some(cosmos));
+    assert!(records
+        .iter()
+        .all(|record| record.device.as_deref() == Some("1-1:1.0")));
+
+    assert_eq!(records[2].event, Event::Command {
+        cla: 0x55,
+        ins: 0x02,
+        p1: 0,
+        p2: 0,
+        payload: Payload::Clear { hex: hex::encode("secret") }
+    });
+    assert_eq!(records[3].event, Event::Answer {
+        command_seq: 2,
+        status_word: 0x9000,
+        payload: Payload::Clear { hex: "3044".to_string() }
+    });
+    assert_eq!(records[5].event, Event::Error { command_seq: 4, error: "mock transport error".to_string() });
+
+    let head = verify(log.as_slice()).unwrap();
+    assert_eq!(head, Head { records: 6, hash: records[5].hash.clone() });
+    assert_eq!(records[0].prev_hash, GENESIS_HASH);
+    assert_eq!(records[1].prev_hash, records[0].hash);
+}
+
+#[test]
+fn audit_redaction() {
+    let records = records(&sample_log(Redaction::Only(vec![(0x55, 0x02)])));
+
+    assert!(matches!(records[0].event, Event::Command { payload: Payload::Clear { .. }, .. }));
+    match &records[2].event {
+        Event::Command { payload: Payload::Redacted { sha256, len }, .. } => {
+            assert_eq!(*len, 6);
+            assert_eq!(sha256, "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b");
+        },
+        event => panic!("unexpected {:?}", event),
+    }
+    assert!(matches!(records[3].event, Event::Answer { payload: Payload::Redacted { len: 2, .. }, .. }));
+
+    let log = sample_log(Redaction::All);
+    assert!(!String::from_utf8_lossy(&log).contains(&hex::encode("secret")));
+    verify(log.as_slice()).unwrap();
+}
+
+#[test]
+fn audit_detects_tampering() {
+    let log = sample_log(Redaction::None);
+    let original = lines(&log);
+    let head = verify(log.as_slice()).unwrap();
+
+    // modified payload
+    let mut tampered = original.clone();
+    tampered[3] = tampered[3].replace("3044", "3045");
+    let err = verify(join(&tampered).as_slice()).unwrap_err();
+    assert_eq!(err, VerifyError { line: 4, kind: VerifyErrorKind::Hash });
+
+    // modified payload with its hash recomputed
+    let mut record: AuditRecord = serde_json::from_str(&original[3]).unwrap();
+    record.event = Event::Answer { command_seq: 2, status_word: 0x9000, payload: Payload::new(&[0x30, 0x45], false) };
+    let record = AuditRecord { hash: String::new(), ..record }.seal();
+    let mut tampered = original.clone();
+    tampered[3] = serde_json::to_string(&record).unwrap();
+    let err = verify(join(&tampered).as_slice()).unwrap_err();
+    assert_eq!(err, VerifyError { line: 5, kind: VerifyErrorKind::Chain });
+
+    // added field, not covered by the hash
+    let mut tampered = original.clone();
+    tampered[2] = tampered[2].replacen('{', "{\"note\":\"approved\",", 1);
+    let err = verify(join(&tampered).as_slice()).unwrap_err();
+    assert!(matches!(err, VerifyError { line: 3, kind: VerifyErrorKind::Malformed(_) }));
+    let mut tampered = original.clone();
+    tampered[2] = tampered[2].replacen("\"payload\":{", "\"payload\":{\"note\":1,", 1);
+    let err = verify(join(&tampered).as_slice()).unwrap_err();
+    assert!(matches!(err, VerifyError { line: 3, kind: VerifyErrorKind::Malformed(_) }));
+
+    // removed record
+    let mut tampered = original.clone();
+    tampered.remove(2);
+    let err = verify(join(&tampered).as_slice()).unwrap_err();
+    assert_eq!(err, VerifyError { line: 3, kind: VerifyErrorKind::Sequence { expected: 2, got: 3 } });
+
+    // partial line
+    let mut tampered = original.clone();
+    tampered[5].truncate(20);
+    let err = verify(join(&tampered).as_slice()).unwrap_err();
+    assert!(matches!(err, VerifyError { line: 6, kind: VerifyErrorKind::Malformed(_) }));
+
+    // removed last records
+    let truncated = join(&original[.. 4]);
+    assert_eq!(
+        verify(truncated.as_slice())
+            .unwrap()
+            .records,
+        4
+    );
+    let err = verify_head(truncated.as_slice(), &head.hash).unwrap_err();
+    assert!(matches!(err.kind, VerifyErrorKind::Truncated { .. }));
+
+    // records appended after the head was taken
+    let earlier: AuditRecord = serde_json::from_str(&original[1]).unwrap();
+    assert_eq!(verify_head(log.as_slice(), &earlier.hash).unwrap(), head);
+}
+
+#[test]
+fn audit_log_failure_blocks_command() {
+    let transport = MockTransport::new(&[&[0x90, 0x00]]);
+    let audited = AuditedTransport::new(&transport, AuditLog::new(BrokenWriter));
+
+    let result = block_on(audited.exchange(&command(0x55, 0x02, b"secret")));
+    assert!(matches!(result, Err(AuditError::Log(_))));
+    assert!(transport.sent().is_empty());
+}
+
+#[test]
+fn audit_log_resume() {
+    let path = std::env::temp_dir().join(format!("ledger-audit-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let event = || Event::Command { cla: 0xe0, ins: 0x01, p1: 0, p2: 0, payload: Payload::new(&[], false) };
+
+    let mut log = AuditLog::open(&path).unwrap();
+    log.append(None, None, event()).unwrap();
+    let head = log.head().clone();
+    drop(log);
+
+    let mut log = AuditLog::open(&path).unwrap();
+    assert_eq!(log.head(), &head);
+    let record = log.append(None, None, event()).unwrap();
+    assert_eq!((record.seq, record.prev_hash), (1, head.hash.clone()));
+    drop(log);
+
+    let file = std::fs::read(&path).unwrap();
+    assert_eq!(
+        verify_head(file.as_slice(), &head.hash)
+            .unwrap()
+            .records,
+        2
+    );
+
+    std::fs::write(&path, &file[1 ..]).unwrap();
+    assert!(matches!(AuditLog::open(&path), Err(OpenError::Verify(_))));
+    std::fs::remove_file(&path).unwrap();
+}
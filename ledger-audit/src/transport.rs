@@ -0,0 +1,307 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! [Exchange] wrapper recording every APDU in an [AuditLog]
+
+use std::{
+    io::Write,
+    ops::Deref,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use ledger_transport::{async_trait, APDUAnswer, APDUCommand, Exchange, SessionGuard, SessionLock};
+
+use crate::{
+    log::AuditLog,
+    record::{AppId, Event, Payload},
+    AuditError,
+};
+
+/// CLA of the BOLOS commands
+const CLA_BOLOS: u8 = 0xb0;
+/// Get the name and version of the open app
+const INS_GET_APP_INFO: u8 = 0x01;
+/// Quit the open app
+const INS_QUIT_APP: u8 = 0xa7;
+/// CLA of [INS_OPEN_APP]
+const CLA_DASHBOARD: u8 = 0xe0;
+/// Open an app by name
+const INS_OPEN_APP: u8 = 0xd8;
+/// Success status word
+const SW_OK: u16 = 0x9000;
+
+/// Which payloads are replaced by their SHA-256 and length in the log
+///
+/// CLA, INS, P1, P2 and status words are always logged
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Redaction {
+    /// Log every payload
+    #[default]
+    None,
+    /// Redact every payload
+    All,
+    /// Redact the payloads of the given `(CLA, INS)` commands and of their answers
+    Only(Vec<(u8, u8)>),
+}
+
+impl Redaction {
+    /// Whether the payloads of the `(cla, ins)` commands and of their answers are redacted
+    pub fn applies(
+        &self,
+        cla: u8,
+        ins: u8,
+    ) -> bool {
+        match self {
+            Self::None => false,
+            Self::All => true,
+            Self::Only(commands) => commands.contains(&(cla, ins)),
+        }
+    }
+}
+
+/// Transport appending every command and answer to an [AuditLog]
+///
+/// The command record is written before the command is sent: if the log can't be written,
+/// nothing reaches the device and [AuditError::Log] is returned. The app open on the device
+/// is tracked from the dashboard commands going through, or set with [AuditedTransport::set_app].
+///
+/// ```ignore
+/// let log = AuditLog::open("/var/log/ledger-audit.jsonl")?;
+/// let transport = AuditedTransport::new(TransportNativeHID::open_device(&api, &device)?, log)
+///     .with_device(device.path().to_string_lossy())
+///     .with_redaction(Redaction::All);
+/// ```
+pub struct AuditedTransport<E, W> {
+    inner: E,
+    log: Mutex<AuditLog<W>>,
+    device: Option<String>,
+    app: Mutex<Option<AppId>>,
+    redaction: Redaction,
+}
+
+impl<E, W: Write> AuditedTransport<E, W> {
+    /// Record the exchanges of `inner` in `log`
+    pub fn new(
+        inner: E,
+        log: AuditLog<W>,
+    ) -> Self {
+        Self { inner, log: Mutex::new(log), device: None, app: Mutex::new(None), redaction: Redaction::default() }
+    }
+
+    /// Identify the device in the records, e.g. with its HID path
+    pub fn with_device(
+        mut self,
+        device: impl Into<String>,
+    ) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    /// Set which payloads are redacted
+    pub fn with_redaction(
+        mut self,
+        redaction: Redaction,
+    ) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    /// Set the app open on the device, for the next records
+    pub fn set_app(
+        &self,
+        app: Option<AppId>,
+    ) {
+        *lock(&self.app) = app;
+    }
+
+    /// App open on the device, as far as known
+    pub fn app(&self) -> Option<AppId> {
+        lock(&self.app).clone()
+    }
+
+    /// The wrapped transport
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    /// The wrapped transport and the log
+    pub fn into_parts(self) -> (E, AuditLog<W>) {
+        let log = self
+            .log
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        (self.inner, log)
+    }
+
+    fn append<T>(
+        &self,
+        event: Event,
+    ) -> Result<u64, AuditError<T>> {
+        lock(&self.log)
+            .append(self.device.clone(), self.app(), event)
+            .map(|record| record.seq)
+            .map_err(|err| AuditError::Log(Arc::new(err)))
+    }
+
+    fn record_command<I, T>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<u64, AuditError<T>>
+    where
+        I: Deref<Target = [u8]>,
+    {
+        let redact = self
+            .redaction
+            .applies(command.cla, command.ins);
+        self.append(Event::Command {
+            cla: command.cla,
+            ins: command.ins,
+            p1: command.p1,
+            p2: command.p2,
+            payload: Payload::new(&command.data, redact),
+        })
+    }
+
+    fn record_answer<I, A, T>(
+        &self,
+        command_seq: u64,
+        command: &APDUCommand<I>,
+        answer: Result<APDUAnswer<A>, T>,
+    ) -> Result<APDUAnswer<A>, AuditError<T>>
+    where
+        I: Deref<Target = [u8]>,
+        A: Deref<Target = [u8]>,
+        T: ToString,
+    {
+        match answer {
+            Ok(answer) => {
+                let redact = self
+                    .redaction
+                    .applies(command.cla, command.ins);
+                self.append(Event::Answer {
+                    command_seq,
+                    status_word: answer.retcode(),
+                    payload: Payload::new(answer.data(), redact),
+                })?;
+                self.track_app(command, &answer);
+                Ok(answer)
+            },
+            Err(err) => {
+                self.append(Event::Error { command_seq, error: err.to_string() })?;
+                Err(AuditError::Transport(err))
+            },
+        }
+    }
+
+    /// Follow the app open on the device from the dashboard commands
+    fn track_app<I, A>(
+        &self,
+        command: &APDUCommand<I>,
+        answer: &APDUAnswer<A>,
+    ) where
+        I: Deref<Target = [u8]>,
+        A: Deref<Target = [u8]>,
+    {
+        if answer.retcode() != SW_OK {
+            return;
+        }
+        match (command.cla, command.ins) {
+            (CLA_BOLOS, INS_GET_APP_INFO) => {
+                if let Some(app) = parse_app_info(answer.data()) {
+                    self.set_app(Some(app));
+                }
+            },
+            (CLA_BOLOS, INS_QUIT_APP) => self.set_app(None),
+            (CLA_DASHBOARD, INS_OPEN_APP) => {
+                let name = String::from_utf8_lossy(&command.data).into_owned();
+                self.set_app(Some(AppId { name, version: None }));
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Name and version from a GET_APP_INFO answer: format, then length-prefixed name and version
+fn parse_app_info(data: &[u8]) -> Option<AppId> {
+    let (&format, rest) = data.split_first()?;
+    if format != 1 {
+        return None;
+    }
+    let (&len, rest) = rest.split_first()?;
+    let name = rest.get(.. len as usize)?;
+    let rest = &rest[len as usize ..];
+    let (&len, rest) = rest.split_first()?;
+    let version = rest.get(.. len as usize)?;
+
+    Some(AppId {
+        name: String::from_utf8_lossy(name).into_owned(),
+        version: Some(String::from_utf8_lossy(version).into_owned()),
+    })
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[async_trait]
+impl<E, W> Exchange for AuditedTransport<E, W>
+where
+    E: Exchange + Send + Sync,
+    E::Error: ToString + Send,
+    W: Write + Send,
+{
+    type Error = AuditError<E::Error>;
+    type AnswerType = E::AnswerType;
+
+    async fn exchange<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        let seq = self.record_command(command)?;
+        let answer = self.inner.exchange(command).await;
+        self.record_answer(seq, command, answer)
+    }
+
+    async fn exchange_in_session<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        let seq = self.record_command(command)?;
+        let answer = self
+            .inner
+            .exchange_in_session(command)
+            .await;
+        self.record_answer(seq, command, answer)
+    }
+
+    fn session_lock(&self) -> Option<&SessionLock> {
+        self.inner.session_lock()
+    }
+
+    async fn session_guard(&self) -> Result<Option<SessionGuard<'_>>, Self::Error> {
+        self.inner
+            .session_guard()
+            .await
+            .map_err(AuditError::Transport)
+    }
+}
@@ -0,0 +1,92 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! `ledger-bridge` serves a device connected over HID with the Speculos APDU protocol
+
+use std::{process::ExitCode, sync::Mutex, time::Duration};
+
+use ledger_bridge::{Bridge, DEFAULT_ADDRESS, DEFAULT_IDLE_TIMEOUT};
+use ledger_transport_hid::{hidapi::HidApi, LedgerHIDError, TransportNativeHID};
+
+const USAGE: &str = "usage: ledger-bridge [--bind <address>] [--idle-timeout <seconds>]";
+
+fn connect(api: &Mutex<HidApi>) -> Result<TransportNativeHID, LedgerHIDError> {
+    let mut api = api
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    api.refresh_devices()?;
+    TransportNativeHID::new(&api)
+}
+
+fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let mut bind = DEFAULT_ADDRESS.to_string();
+    let mut idle_timeout = DEFAULT_IDLE_TIMEOUT;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--bind", Some(address)) => bind = address,
+            ("--idle-timeout", Some(seconds)) => match seconds.parse() {
+                Ok(seconds) => idle_timeout = Duration::from_secs(seconds),
+                Err(_) => {
+                    eprintln!("{USAGE}");
+                    return ExitCode::from(2);
+                },
+            },
+            ("-h" | "--help", _) => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            },
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::from(2);
+            },
+        }
+    }
+
+    let api = match HidApi::new() {
+        Ok(api) => Mutex::new(api),
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        },
+    };
+    let transport = match connect(&api) {
+        Ok(transport) => transport,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let bridge = Bridge::bind(&bind, transport).map(|bridge| {
+        bridge
+            .with_idle_timeout(Some(idle_timeout))
+            .with_reconnect(move || connect(&api))
+    });
+    let result = bridge.and_then(|mut bridge| {
+        log::info!("serving APDUs on {}", bridge.local_addr()?);
+        bridge.serve()
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        },
+    }
+}
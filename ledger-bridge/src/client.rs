@@ -0,0 +1,91 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! [Exchange] over the Speculos APDU protocol, to reach a [Bridge](crate::Bridge) or Speculos itself
+
+use std::{
+    io::{self, ErrorKind},
+    net::{TcpStream, ToSocketAddrs},
+    ops::Deref,
+    sync::Mutex,
+};
+
+use ledger_transport::{async_trait, APDUAnswer, APDUCommand, Exchange, SessionLock};
+
+use crate::protocol::{read_answer, write_command};
+
+/// Transport talking to a Speculos compatible APDU server
+pub struct TransportTcp {
+    stream: Mutex<TcpStream>,
+    session: SessionLock,
+}
+
+impl TransportTcp {
+    /// Connect to the server at `addr`, e.g. `127.0.0.1:9999`
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self { stream: Mutex::new(stream), session: SessionLock::new() })
+    }
+
+    fn exchange_unlocked<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> io::Result<APDUAnswer<Vec<u8>>>
+    where
+        I: Deref<Target = [u8]>,
+    {
+        let mut stream = self
+            .stream
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        write_command(&mut *stream, &command.serialize())?;
+        let answer = read_answer(&mut *stream)?;
+
+        APDUAnswer::from_answer(answer).map_err(|_| io::Error::new(ErrorKind::InvalidData, "answer too short"))
+    }
+}
+
+#[async_trait]
+impl Exchange for TransportTcp {
+    type Error = io::Error;
+    type AnswerType = Vec<u8>;
+
+    async fn exchange<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        let _session = self.session.lock().await;
+        self.exchange_unlocked(command)
+    }
+
+    async fn exchange_in_session<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        self.exchange_unlocked(command)
+    }
+
+    fn session_lock(&self) -> Option<&SessionLock> {
+        Some(&self.session)
+    }
+}
@@ -0,0 +1,31 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Bridge exposing a local Ledger device over the Speculos APDU TCP protocol
+//!
+//! [Bridge] owns any [Exchange](ledger_transport::Exchange) transport, e.g. `TransportNativeHID`,
+//! and serves it to one TCP client at a time, so VMs and containers can use a device attached to
+//! the host with any Speculos client, or [TransportTcp].
+
+#![deny(warnings, unused_qualifications, missing_docs)]
+
+pub mod client;
+pub mod protocol;
+pub mod server;
+#[cfg(test)]
+mod tests;
+
+pub use client::TransportTcp;
+pub use server::{Bridge, DEFAULT_ADDRESS, DEFAULT_IDLE_TIMEOUT};
@@ -0,0 +1,86 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Speculos APDU framing
+//!
+//! Commands are sent as a 4 bytes big endian length followed by the APDU. Answers are sent as
+//! a 4 bytes big endian length of the answer data, the data, then the 2 bytes status word.
+
+use std::io::{self, ErrorKind, Read, Write};
+
+/// Largest command accepted, an extended APDU with the largest payload
+pub const MAX_COMMAND_LEN: usize = 7 + 0xffff + 2;
+
+/// Read a command, `None` if the peer closed the connection before sending one
+pub fn read_command<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_COMMAND_LEN {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("command of {len} bytes is too long")));
+    }
+
+    let mut command = vec![0; len];
+    reader.read_exact(&mut command)?;
+    Ok(Some(command))
+}
+
+/// Write a serialized command
+pub fn write_command<W: Write>(
+    writer: &mut W,
+    command: &[u8],
+) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(4 + command.len());
+    frame.extend_from_slice(&(command.len() as u32).to_be_bytes());
+    frame.extend_from_slice(command);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+/// Read an answer, data followed by the status word
+pub fn read_answer<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_COMMAND_LEN {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("answer of {len} bytes is too long")));
+    }
+
+    let mut answer = vec![0; len + 2];
+    reader.read_exact(&mut answer)?;
+    Ok(answer)
+}
+
+/// Write an answer, data followed by the status word
+pub fn write_answer<W: Write>(
+    writer: &mut W,
+    answer: &[u8],
+) -> io::Result<()> {
+    let data_len = answer
+        .len()
+        .checked_sub(2)
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "answer without status word"))?;
+
+    let mut frame = Vec::with_capacity(4 + answer.len());
+    frame.extend_from_slice(&(data_len as u32).to_be_bytes());
+    frame.extend_from_slice(answer);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
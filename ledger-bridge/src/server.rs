@@ -0,0 +1,200 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Server exposing an [Exchange] transport over the Speculos APDU protocol
+
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration,
+};
+
+use futures::executor::block_on;
+use ledger_transport::{APDUCommand, Exchange, TransportError};
+use log::{info, warn};
+
+use crate::protocol::{read_command, write_answer};
+
+/// Address Speculos serves APDUs on by default
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:9999";
+
+/// How long a client may stay connected without sending a command, see [Bridge::with_idle_timeout]
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Delay before accepting again after a failed accept, e.g. when running out of file descriptors
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Function reconnecting to the device, see [Bridge::with_reconnect]
+type Connect<E> = Box<dyn FnMut() -> Result<E, <E as Exchange>::Error> + Send>;
+
+/// Serves a transport to one TCP client at a time
+///
+/// Clients connecting while another one is served wait in the listen backlog and are served in
+/// order, so a client idle for longer than the [idle timeout](Bridge::with_idle_timeout) is
+/// disconnected. A client is also disconnected when its command can't be exchanged with the device,
+/// the bridge itself keeps running: with [Bridge::with_reconnect] the device is reconnected for the
+/// next command after it went away.
+///
+/// ```ignore
+/// let api = Mutex::new(HidApi::new()?);
+/// let transport = TransportNativeHID::new(&api.lock().unwrap())?;
+/// Bridge::bind("127.0.0.1:9999", transport)?
+///     .with_reconnect(move || {
+///         let mut api = api.lock().unwrap();
+///         api.refresh_devices()?;
+///         TransportNativeHID::new(&api)
+///     })
+///     .serve()?;
+/// ```
+pub struct Bridge<E: Exchange> {
+    listener: TcpListener,
+    transport: Option<E>,
+    connect: Option<Connect<E>>,
+    idle_timeout: Option<Duration>,
+}
+
+impl<E> Bridge<E>
+where
+    E: Exchange + Send + Sync,
+    E::Error: TransportError,
+{
+    /// Listen on `addr` and serve `transport` there
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        transport: E,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(Self { listener, transport: Some(transport), connect: None, idle_timeout: Some(DEFAULT_IDLE_TIMEOUT) })
+    }
+
+    /// Disconnect clients not sending a command for `timeout`, [DEFAULT_IDLE_TIMEOUT] by default
+    ///
+    /// `None` or a zero duration lets clients stay connected as long as they want, blocking the others
+    pub fn with_idle_timeout(
+        mut self,
+        timeout: Option<Duration>,
+    ) -> Self {
+        self.idle_timeout = timeout.filter(|timeout| !timeout.is_zero());
+        self
+    }
+
+    /// Replace the transport with `connect` when the device goes away
+    pub fn with_reconnect(
+        mut self,
+        connect: impl FnMut() -> Result<E, E::Error> + Send + 'static,
+    ) -> Self {
+        self.connect = Some(Box::new(connect));
+        self
+    }
+
+    /// Address the bridge listens on
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve the clients, one after the other
+    ///
+    /// Failures to accept a connection are logged and retried, it only returns if the listener
+    /// itself is unusable
+    pub fn serve(&mut self) -> io::Result<()> {
+        loop {
+            let (stream, peer) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(err) if is_fatal_accept_error(&err) => return Err(err),
+                Err(err) => {
+                    warn!("couldn't accept a client: {}", err);
+                    thread::sleep(ACCEPT_RETRY_DELAY);
+                    continue;
+                },
+            };
+            info!("client {} connected", peer);
+            match self.serve_client(stream) {
+                Ok(()) => info!("client {} disconnected", peer),
+                Err(err) => warn!("client {} dropped: {}", peer, err),
+            }
+        }
+    }
+
+    /// Serve the commands of a client until it disconnects or a command fails
+    pub fn serve_client(
+        &mut self,
+        mut stream: TcpStream,
+    ) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(self.idle_timeout)?;
+
+        while let Some(command) = read_command(&mut stream).map_err(|err| match err.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                io::Error::new(io::ErrorKind::TimedOut, "client idle for too long")
+            },
+            _ => err,
+        })? {
+            let answer = self.exchange(&command)?;
+            write_answer(&mut stream, &answer)?;
+        }
+        Ok(())
+    }
+
+    /// Exchange a serialized command, returning the answer with its status word
+    fn exchange(
+        &mut self,
+        command: &[u8],
+    ) -> io::Result<Vec<u8>> {
+        let command = APDUCommand::from_bytes(command)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+
+        if self.transport.is_none() {
+            // the transport is only dropped when it can be reconnected
+            if let Some(connect) = &mut self.connect {
+                let transport = connect().map_err(|err| device_error("couldn't reconnect", &err))?;
+                info!("device reconnected");
+                self.transport = Some(transport);
+            }
+        }
+        let transport = self
+            .transport
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "no device"))?;
+
+        match block_on(transport.exchange(&command)) {
+            Ok(answer) => {
+                let mut raw = answer.data().to_vec();
+                raw.extend_from_slice(&answer.retcode().to_be_bytes());
+                Ok(raw)
+            },
+            Err(err) => {
+                if !err.kind().is_transient() && self.connect.is_some() {
+                    warn!("dropping the device: {}", err);
+                    self.transport = None;
+                }
+                Err(device_error("exchange failed", &err))
+            },
+        }
+    }
+}
+
+fn device_error<E: TransportError>(
+    context: &str,
+    err: &E,
+) -> io::Error {
+    io::Error::other(format!("{context}: {err} ({})", err.kind()))
+}
+
+/// Whether an `accept` error comes from the listener rather than from a connection or a temporary
+/// shortage of resources
+fn is_fatal_accept_error(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::InvalidInput
+}
@@ -0,0 +1,192 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::{
+    io::{self, ErrorKind},
+    net::TcpStream,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use futures::executor::block_on;
+use ledger_transport::{async_trait, APDUAnswer, APDUCommand, Exchange};
+
+use super::*;
+use crate::protocol::{read_answer, read_command, write_answer, write_command};
+
+/// Device shared by the mock transports, echoing the payloads with 9000
+#[derive(Default)]
+struct MockDevice {
+    sent: Mutex<Vec<Vec<u8>>>,
+    unplugged: Mutex<bool>,
+}
+
+struct MockTransport(Arc<MockDevice>);
+
+#[async_trait]
+impl Exchange for MockTransport {
+    type Error = io::Error;
+    type AnswerType = Vec<u8>;
+
+    async fn exchange<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        if std::mem::take(&mut *self.0.unplugged.lock().unwrap()) {
+            return Err(io::Error::from(ErrorKind::BrokenPipe));
+        }
+        self.0
+            .sent
+            .lock()
+            .unwrap()
+            .push(command.serialize());
+
+        let mut answer = command.data.to_vec();
+        answer.extend_from_slice(&[0x90, 0x00]);
+        Ok(APDUAnswer::from_answer(answer).unwrap())
+    }
+}
+
+/// Serve `device` on a loopback port, in the background
+fn spawn_bridge(
+    device: &Arc<MockDevice>,
+    reconnects: &Arc<AtomicUsize>,
+) -> String {
+    let reconnect = (device.clone(), reconnects.clone());
+    let mut bridge = Bridge::bind("127.0.0.1:0", MockTransport(device.clone()))
+        .unwrap()
+        .with_reconnect(move || {
+            reconnect
+                .1
+                .fetch_add(1, Ordering::SeqCst);
+            Ok(MockTransport(reconnect.0.clone()))
+        });
+    let addr = bridge.local_addr().unwrap().to_string();
+
+    thread::spawn(move || bridge.serve());
+    addr
+}
+
+fn command(data: &[u8]) -> APDUCommand<Vec<u8>> {
+    APDUCommand { cla: 0x55, ins: 0x02, p1: 0x00, p2: 0x00, data: data.to_vec() }
+}
+
+#[test]
+fn protocol_framing() {
+    let mut frame = Vec::new();
+    write_command(&mut frame, &[0xe0, 0x01, 0x00, 0x00, 0x00]).unwrap();
+    assert_eq!(frame, [0, 0, 0, 5, 0xe0, 0x01, 0x00, 0x00, 0x00]);
+    assert_eq!(read_command(&mut frame.as_slice()).unwrap(), Some(vec![0xe0, 0x01, 0x00, 0x00, 0x00]));
+    assert_eq!(read_command(&mut [].as_slice()).unwrap(), None);
+
+    let mut frame = Vec::new();
+    write_answer(&mut frame, &[0x01, 0x02, 0x90, 0x00]).unwrap();
+    assert_eq!(frame, [0, 0, 0, 2, 0x01, 0x02, 0x90, 0x00]);
+    assert_eq!(read_answer(&mut frame.as_slice()).unwrap(), [0x01, 0x02, 0x90, 0x00]);
+
+    let too_long = [0xff, 0xff, 0xff, 0xff];
+    assert_eq!(
+        read_command(&mut too_long.as_slice())
+            .unwrap_err()
+            .kind(),
+        ErrorKind::InvalidData
+    );
+    assert!(write_answer(&mut Vec::new(), &[0x90]).is_err());
+}
+
+#[test]
+fn bridge_forwards_apdus() {
+    let device = Arc::new(MockDevice::default());
+    let addr = spawn_bridge(&device, &Arc::new(AtomicUsize::new(0)));
+
+    let transport = TransportTcp::connect(&addr).unwrap();
+    for payload in [&b""[..], b"hello"] {
+        let answer = block_on(transport.exchange(&command(payload))).unwrap();
+        assert_eq!((answer.data(), answer.retcode()), (payload, 0x9000));
+    }
+
+    assert_eq!(*device.sent.lock().unwrap(), vec![command(b"").serialize(), command(b"hello").serialize()]);
+}
+
+#[test]
+fn bridge_queues_clients() {
+    let device = Arc::new(MockDevice::default());
+    let addr = spawn_bridge(&device, &Arc::new(AtomicUsize::new(0)));
+
+    let first = TransportTcp::connect(&addr).unwrap();
+    block_on(first.exchange(&command(b"first"))).unwrap();
+
+    let mut second = TcpStream::connect(&addr).unwrap();
+    write_command(&mut second, &command(b"second").serialize()).unwrap();
+    second
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    assert!(read_answer(&mut second).is_err(), "served while another client is active");
+
+    drop(first);
+    second.set_read_timeout(None).unwrap();
+    assert_eq!(read_answer(&mut second).unwrap(), b"second\x90\x00");
+}
+
+#[test]
+fn bridge_drops_idle_clients() {
+    let device = Arc::new(MockDevice::default());
+    let mut bridge = Bridge::bind("127.0.0.1:0", MockTransport(device.clone()))
+        .unwrap()
+        .with_idle_timeout(Some(Duration::from_millis(100)));
+    let addr = bridge.local_addr().unwrap().to_string();
+    thread::spawn(move || bridge.serve());
+
+    let idle = TransportTcp::connect(&addr).unwrap();
+    let second = TransportTcp::connect(&addr).unwrap();
+    let answer = block_on(second.exchange(&command(b"second"))).unwrap();
+    assert_eq!(answer.data(), b"second");
+
+    assert!(block_on(idle.exchange(&command(b"late"))).is_err());
+}
+
+#[test]
+fn bridge_rejects_malformed_commands() {
+    let addr = spawn_bridge(&Arc::new(MockDevice::default()), &Arc::new(AtomicUsize::new(0)));
+
+    let mut client = TcpStream::connect(&addr).unwrap();
+    write_command(&mut client, &[0x55, 0x02, 0x00, 0x00, 0x05, 0xab]).unwrap();
+    assert!(read_answer(&mut client).is_err());
+}
+
+#[test]
+fn bridge_reconnects_device() {
+    let device = Arc::new(MockDevice::default());
+    let reconnects = Arc::new(AtomicUsize::new(0));
+    let addr = spawn_bridge(&device, &reconnects);
+
+    *device.unplugged.lock().unwrap() = true;
+    let client = TransportTcp::connect(&addr).unwrap();
+    assert!(block_on(client.exchange(&command(b"lost"))).is_err());
+    assert_eq!(reconnects.load(Ordering::SeqCst), 0);
+
+    let client = TransportTcp::connect(&addr).unwrap();
+    let answer = block_on(client.exchange(&command(b"back"))).unwrap();
+    assert_eq!(answer.data(), b"back");
+    assert_eq!(reconnects.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,63 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! `ledger-broker` shares the Ledger devices connected over HID between local processes
+
+use std::{path::PathBuf, process::ExitCode};
+
+use ledger_broker::{default_socket_path, Broker, HidProvider};
+
+const USAGE: &str = "usage: ledger-broker [--socket <path>]";
+
+fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let mut socket = default_socket_path();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--socket", Some(path)) => socket = PathBuf::from(path),
+            ("-h" | "--help", _) => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            },
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::from(2);
+            },
+        }
+    }
+
+    let provider = match HidProvider::new() {
+        Ok(provider) => provider,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let result = Broker::bind(&socket, provider).and_then(|broker| {
+        log::info!("serving {} devices on {}", broker.devices().len(), broker.path().display());
+        broker.serve()
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        },
+    }
+}
@@ -0,0 +1,231 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Clients of a [Broker](crate::Broker)
+
+use std::{io::BufReader, ops::Deref, os::unix::net::UnixStream, path::Path, sync::Mutex};
+
+use ledger_transport::{async_trait, APDUAnswer, APDUCommand, Exchange, SessionGuard, SessionLock};
+
+use crate::{
+    protocol::{read_message, write_message, Device, DeviceEvent, Request, Response},
+    BrokerError,
+};
+
+/// Session with a broker, see [Request]
+pub struct BrokerClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl BrokerClient {
+    /// Open a session with the broker listening at `path`
+    pub fn connect(path: impl AsRef<Path>) -> Result<Self, BrokerError> {
+        let writer = UnixStream::connect(path)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Self { reader, writer })
+    }
+
+    /// Send a request and wait for its response
+    pub fn request(
+        &mut self,
+        request: &Request,
+    ) -> Result<Response, BrokerError> {
+        write_message(&mut self.writer, request)?;
+        match read_message(&mut self.reader)? {
+            Some(Response::Error { error }) => Err(error),
+            Some(response) => Ok(response),
+            None => Err(BrokerError::Protocol("broker closed the connection".to_string())),
+        }
+    }
+
+    /// Devices held by the broker
+    pub fn devices(&mut self) -> Result<Vec<Device>, BrokerError> {
+        match self.request(&Request::List)? {
+            Response::Devices { devices } => Ok(devices),
+            response => Err(unexpected(&response)),
+        }
+    }
+
+    /// Receive the device events, the session can't send requests anymore
+    ///
+    /// Returns the devices connected when subscribing, and the changes since
+    pub fn subscribe(mut self) -> Result<(Vec<Device>, Events), BrokerError> {
+        match self.request(&Request::Subscribe)? {
+            Response::Devices { devices } => Ok((devices, Events { reader: self.reader })),
+            response => Err(unexpected(&response)),
+        }
+    }
+}
+
+fn unexpected(response: &Response) -> BrokerError {
+    BrokerError::Protocol(format!("unexpected response {response:?}"))
+}
+
+/// Device plugged and unplugged, see [BrokerClient::subscribe]
+///
+/// Ends when the broker goes away
+pub struct Events {
+    reader: BufReader<UnixStream>,
+}
+
+impl Iterator for Events {
+    type Item = Result<DeviceEvent, BrokerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        read_message(&mut self.reader).transpose()
+    }
+}
+
+/// Transport to a device held by a broker, with its own session
+///
+/// [Exchange::lock_session] leases the device, so neither other clients nor other tasks sharing
+/// the transport reach it until the session is dropped
+///
+/// ```ignore
+/// let transport = TransportBroker::connect(default_socket_path(), None)?;
+/// let session = transport.lock_session().await?;
+/// let answer = session.exchange(&command).await?;
+/// ```
+pub struct TransportBroker {
+    client: Mutex<BrokerClient>,
+    device: String,
+    session: SessionLock,
+}
+
+impl TransportBroker {
+    /// Use the device `device` of the broker listening at `path`, or the first one if `None`
+    pub fn connect(
+        path: impl AsRef<Path>,
+        device: Option<&str>,
+    ) -> Result<Self, BrokerError> {
+        let mut client = BrokerClient::connect(path)?;
+        let device = match device {
+            Some(device) => device.to_string(),
+            None => {
+                client
+                    .devices()?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| BrokerError::UnknownDevice(String::new()))?
+                    .id
+            },
+        };
+        Ok(Self { client: Mutex::new(client), device, session: SessionLock::new() })
+    }
+
+    /// Id of the device
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// Take the exclusive use of the device until the returned [Lease] is dropped
+    ///
+    /// Waits for the leases of other clients to be released. Other tasks sharing the transport
+    /// still reach the device, use [Exchange::lock_session] to keep them out too
+    pub fn lease(&self) -> Result<Lease<'_>, BrokerError> {
+        self.request(&Request::Lease { device: self.device.clone() })?;
+        Ok(Lease { transport: self })
+    }
+
+    fn exchange_unlocked<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Vec<u8>>, BrokerError>
+    where
+        I: Deref<Target = [u8]>,
+    {
+        let request = Request::Exchange { device: self.device.clone(), apdu: hex::encode(command.serialize()) };
+        match self.request(&request)? {
+            Response::Answer { apdu } => {
+                let answer = hex::decode(&apdu).map_err(|err| BrokerError::Protocol(err.to_string()))?;
+                APDUAnswer::from_answer(answer).map_err(|err| BrokerError::Protocol(err.to_string()))
+            },
+            response => Err(unexpected(&response)),
+        }
+    }
+
+    fn request(
+        &self,
+        request: &Request,
+    ) -> Result<Response, BrokerError> {
+        self.client
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .request(request)
+    }
+}
+
+/// Exclusive use of a device, see [TransportBroker::lease]
+pub struct Lease<'a> {
+    transport: &'a TransportBroker,
+}
+
+impl Lease<'_> {
+    /// Release the lease, reporting errors
+    pub fn release(self) -> Result<(), BrokerError> {
+        let result = self
+            .transport
+            .request(&Request::Release { device: self.transport.device.clone() });
+        std::mem::forget(self);
+        result.map(drop)
+    }
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        let _ = self
+            .transport
+            .request(&Request::Release { device: self.transport.device.clone() });
+    }
+}
+
+#[async_trait]
+impl Exchange for TransportBroker {
+    type Error = BrokerError;
+    type AnswerType = Vec<u8>;
+
+    async fn exchange<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        let _session = self.session.lock().await;
+        self.exchange_unlocked(command)
+    }
+
+    async fn exchange_in_session<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        self.exchange_unlocked(command)
+    }
+
+    fn session_lock(&self) -> Option<&SessionLock> {
+        Some(&self.session)
+    }
+
+    async fn session_guard(&self) -> Result<Option<SessionGuard<'_>>, Self::Error> {
+        let local = self.session.lock().await;
+        let lease = self.lease()?;
+        // the lease is released before the local lock
+        Ok(Some(SessionGuard::new((lease, local))))
+    }
+}
@@ -0,0 +1,76 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::io;
+
+use ledger_transport::{TransportError, TransportErrorKind};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Error of the broker or of a device behind it
+#[derive(Clone, Debug, Eq, Error, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "error", content = "details")]
+pub enum BrokerError {
+    /// No device with this id is connected
+    #[error("unknown device `{0}`")]
+    UnknownDevice(String),
+    /// The device was unplugged since it was leased, it must be leased again
+    #[error("lease of device `{0}` was lost, it was unplugged")]
+    LeaseLost(String),
+    /// The device failed to exchange the command
+    #[error("device error | {message}")]
+    Device {
+        /// Category of the device error
+        #[serde(with = "crate::protocol::error_kind")]
+        kind: TransportErrorKind,
+        /// Device error
+        message: String,
+    },
+    /// The connection to the broker failed
+    #[error("broker connection | {message}")]
+    Io {
+        /// Category of the i/o error
+        #[serde(with = "crate::protocol::error_kind")]
+        kind: TransportErrorKind,
+        /// I/O error
+        message: String,
+    },
+    /// A message could not be understood
+    #[error("protocol error | {0}")]
+    Protocol(String),
+}
+
+impl From<io::Error> for BrokerError {
+    fn from(err: io::Error) -> Self {
+        BrokerError::Io { kind: err.kind().into(), message: err.to_string() }
+    }
+}
+
+impl From<serde_json::Error> for BrokerError {
+    fn from(err: serde_json::Error) -> Self {
+        BrokerError::Protocol(err.to_string())
+    }
+}
+
+impl TransportError for BrokerError {
+    fn kind(&self) -> TransportErrorKind {
+        match self {
+            Self::UnknownDevice(_) => TransportErrorKind::DeviceNotFound,
+            Self::LeaseLost(_) => TransportErrorKind::Disconnected,
+            Self::Device { kind, .. } | Self::Io { kind, .. } => *kind,
+            Self::Protocol(_) => TransportErrorKind::Io,
+        }
+    }
+}
@@ -0,0 +1,63 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! [DeviceProvider] for the Ledger devices connected over HID
+
+use ledger_transport_hid::{hidapi::HidApi, LedgerHIDError, TransportNativeHID};
+
+use crate::{protocol::Device, DeviceProvider};
+
+/// Finds the Ledger devices with hidapi, identified by their HID path
+pub struct HidProvider {
+    api: HidApi,
+}
+
+impl HidProvider {
+    /// Provider using a new [HidApi]
+    pub fn new() -> Result<Self, LedgerHIDError> {
+        Ok(Self { api: HidApi::new()? })
+    }
+}
+
+impl DeviceProvider for HidProvider {
+    type Transport = TransportNativeHID;
+    type Error = LedgerHIDError;
+
+    fn scan(&mut self) -> Vec<Device> {
+        if let Err(err) = self.api.refresh_devices() {
+            log::warn!("couldn't refresh the HID devices: {}", err);
+        }
+        TransportNativeHID::list_ledgers(&self.api)
+            .map(|info| Device {
+                id: info
+                    .path()
+                    .to_string_lossy()
+                    .into_owned(),
+                model: Some(TransportNativeHID::model(info).to_string()),
+                serial: info.serial_number().map(str::to_string),
+            })
+            .collect()
+    }
+
+    fn open(
+        &mut self,
+        device: &Device,
+    ) -> Result<TransportNativeHID, LedgerHIDError> {
+        let info = TransportNativeHID::list_ledgers(&self.api)
+            .find(|info| info.path().to_string_lossy() == device.id)
+            .ok_or(LedgerHIDError::DeviceNotFound)?;
+        TransportNativeHID::open_device(&self.api, info)
+    }
+}
@@ -0,0 +1,43 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Broker sharing Ledger devices between local processes over a Unix socket
+//!
+//! Opening the same device from two processes locks it (see the hidapi issue linked in
+//! `TransportNativeHID::open_device`). The [Broker] daemon holds the only handle to each device and
+//! serves any number of clients, with exclusive leases for multi-APDU flows and hot-plug
+//! notifications. [TransportBroker] is the matching [Exchange](ledger_transport::Exchange).
+
+#![deny(warnings, unused_qualifications, missing_docs)]
+
+#[cfg(unix)]
+pub mod client;
+mod errors;
+#[cfg(all(unix, feature = "hid"))]
+pub mod hid;
+pub mod protocol;
+#[cfg(unix)]
+pub mod server;
+#[cfg(all(test, unix))]
+mod tests;
+
+#[cfg(unix)]
+pub use client::{BrokerClient, Events, Lease, TransportBroker};
+pub use errors::BrokerError;
+#[cfg(all(unix, feature = "hid"))]
+pub use hid::HidProvider;
+pub use protocol::{default_socket_path, Device, DeviceEvent, MAX_MESSAGE_LEN};
+#[cfg(unix)]
+pub use server::{Broker, DeviceProvider};
@@ -0,0 +1,206 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Messages exchanged with the broker, one JSON object per line
+//!
+//! Clients send [Request]s and get one [Response] each, in order. After [Request::Subscribe] the
+//! connection only carries [DeviceEvent]s.
+
+use std::{
+    io::{self, BufRead, Read, Write},
+    path::PathBuf,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::BrokerError;
+
+/// Longest message accepted, newline included
+pub const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+/// Socket name, in `$XDG_RUNTIME_DIR` or the temporary directory
+const SOCKET_NAME: &str = "ledger-broker.sock";
+
+/// Socket the broker listens on by default
+pub fn default_socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(SOCKET_NAME)
+}
+
+/// Device held by the broker
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Device {
+    /// Identifier of the device for the broker, e.g. its HID path
+    pub id: String,
+    /// Device model, if known
+    pub model: Option<String>,
+    /// Serial number, if known
+    pub serial: Option<String>,
+}
+
+/// Client request
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "op")]
+pub enum Request {
+    /// List the devices
+    List,
+    /// Exchange a command with a device, waiting for other clients' lease to be released
+    Exchange {
+        /// Device id
+        device: String,
+        /// Serialized command, in hex
+        apdu: String,
+    },
+    /// Take the exclusive use of a device, waiting for other clients' lease to be released
+    Lease {
+        /// Device id
+        device: String,
+    },
+    /// Release a lease
+    Release {
+        /// Device id
+        device: String,
+    },
+    /// Receive the [DeviceEvent]s on this connection, instead of responses
+    Subscribe,
+}
+
+/// Broker response
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Response {
+    /// Connected devices, for [Request::List] and [Request::Subscribe]
+    Devices {
+        /// Devices
+        devices: Vec<Device>,
+    },
+    /// Answer of [Request::Exchange]
+    Answer {
+        /// Answer, status word included, in hex
+        apdu: String,
+    },
+    /// [Request::Lease] or [Request::Release] done
+    Done,
+    /// The request failed
+    Error {
+        /// Error
+        error: BrokerError,
+    },
+}
+
+/// Device plugged or unplugged, sent to subscribed clients
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", tag = "event")]
+pub enum DeviceEvent {
+    /// A device was connected
+    Added {
+        /// Device
+        device: Device,
+    },
+    /// A device was disconnected
+    Removed {
+        /// Device id
+        id: String,
+    },
+}
+
+/// Write a message on its own line
+pub fn write_message<W: Write, T: Serialize>(
+    writer: &mut W,
+    message: &T,
+) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line)?;
+    writer.flush()
+}
+
+/// Read a message, `None` if the peer closed the connection
+///
+/// A line longer than [MAX_MESSAGE_LEN] is skipped and fails with [BrokerError::Protocol]
+pub fn read_message<R: BufRead, T: DeserializeOwned>(reader: &mut R) -> Result<Option<T>, BrokerError> {
+    let mut line = Vec::new();
+    if reader
+        .by_ref()
+        .take(MAX_MESSAGE_LEN as u64)
+        .read_until(b'\n', &mut line)?
+        == 0
+    {
+        return Ok(None);
+    }
+    if line.len() == MAX_MESSAGE_LEN && !line.ends_with(b"\n") {
+        skip_line(reader)?;
+        return Err(BrokerError::Protocol(format!("message longer than {MAX_MESSAGE_LEN} bytes")));
+    }
+    Ok(Some(serde_json::from_slice(&line)?))
+}
+
+/// Discard the rest of the current line without buffering it
+fn skip_line<R: BufRead>(reader: &mut R) -> io::Result<()> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        match buf
+            .iter()
+            .position(|&byte| byte == b'\n')
+        {
+            Some(end) => {
+                reader.consume(end + 1);
+                return Ok(());
+            },
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            },
+        }
+    }
+}
+
+/// [TransportErrorKind](ledger_transport::TransportErrorKind) on the wire
+pub(crate) mod error_kind {
+    use ledger_transport::TransportErrorKind;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        kind: &TransportErrorKind,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match kind {
+            TransportErrorKind::Disconnected => "disconnected",
+            TransportErrorKind::Timeout => "timeout",
+            TransportErrorKind::Busy => "busy",
+            TransportErrorKind::DeviceNotFound => "deviceNotFound",
+            TransportErrorKind::Denied => "denied",
+            _ => "io",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TransportErrorKind, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "disconnected" => Ok(TransportErrorKind::Disconnected),
+            "timeout" => Ok(TransportErrorKind::Timeout),
+            "busy" => Ok(TransportErrorKind::Busy),
+            "deviceNotFound" => Ok(TransportErrorKind::DeviceNotFound),
+            "denied" => Ok(TransportErrorKind::Denied),
+            "io" => Ok(TransportErrorKind::Io),
+            kind => Err(de::Error::custom(format!("unknown error kind `{kind}`"))),
+        }
+    }
+}
@@ -0,0 +1,478 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Broker daemon holding the devices and serving the clients
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufReader, ErrorKind, Read},
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread,
+    time::Duration,
+};
+
+use futures::executor::block_on;
+use ledger_transport::{APDUCommand, Exchange, TransportError};
+use log::{debug, info, warn};
+
+use crate::{
+    protocol::{read_message, write_message, Device, DeviceEvent, Request, Response},
+    BrokerError,
+};
+
+/// Default delay between two scans for plugged and unplugged devices
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Delay before accepting again after a failed accept, e.g. when running out of file descriptors
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Delay between two checks that a subscribed client is still connected
+const SUBSCRIBER_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Finds and opens the devices served by a [Broker]
+pub trait DeviceProvider: Send + 'static {
+    /// Transport to an open device
+    type Transport: Exchange<Error = Self::Error> + Send + Sync + 'static;
+    /// Error opening or using a device
+    type Error: TransportError;
+
+    /// Devices currently connected
+    fn scan(&mut self) -> Vec<Device>;
+
+    /// Open a device returned by [DeviceProvider::scan]
+    fn open(
+        &mut self,
+        device: &Device,
+    ) -> Result<Self::Transport, Self::Error>;
+}
+
+/// Lease of a device
+#[derive(Default)]
+struct LeaseState {
+    /// Client holding the lease
+    owner: Option<u64>,
+    /// The device was unplugged
+    removed: bool,
+}
+
+/// A device held by the broker
+struct Slot<T> {
+    /// Tells apart the slots of a device unplugged and plugged again
+    generation: u64,
+    device: Device,
+    transport: T,
+    lease: Mutex<LeaseState>,
+    released: Condvar,
+}
+
+impl<T> Slot<T> {
+    /// Wait until `client` may use the device, other clients' leases being released
+    fn acquire(
+        &self,
+        client: u64,
+    ) -> Result<MutexGuard<'_, LeaseState>, BrokerError> {
+        let mut lease = lock(&self.lease);
+        while lease
+            .owner
+            .is_some_and(|owner| owner != client)
+            && !lease.removed
+        {
+            lease = self
+                .released
+                .wait(lease)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+
+        if lease.removed {
+            return Err(BrokerError::UnknownDevice(self.device.id.clone()));
+        }
+        Ok(lease)
+    }
+
+    fn release(
+        &self,
+        client: u64,
+    ) {
+        let mut lease = lock(&self.lease);
+        if lease.owner == Some(client) {
+            lease.owner = None;
+            self.released.notify_all();
+        }
+    }
+}
+
+/// State shared by the client threads and the scanner
+struct State<P: DeviceProvider> {
+    provider: Mutex<P>,
+    devices: Mutex<BTreeMap<String, Arc<Slot<P::Transport>>>>,
+    subscribers: Mutex<Vec<(u64, Sender<DeviceEvent>)>>,
+    next_client: AtomicU64,
+    next_generation: AtomicU64,
+}
+
+/// Leases held by a client: device id and generation of the leased slot
+type Leases = BTreeMap<String, u64>;
+
+impl<P: DeviceProvider> State<P> {
+    /// Open the new devices, drop the unplugged ones and notify the subscribers
+    fn scan(&self) {
+        let found = lock(&self.provider).scan();
+        let mut devices = lock(&self.devices);
+        let mut events = Vec::new();
+
+        let removed: Vec<_> = devices
+            .keys()
+            .filter(|id| {
+                !found
+                    .iter()
+                    .any(|device| &device.id == *id)
+            })
+            .cloned()
+            .collect();
+        for id in removed {
+            if let Some(slot) = devices.remove(&id) {
+                info!("device {} removed", id);
+                lock(&slot.lease).removed = true;
+                slot.released.notify_all();
+                events.push(DeviceEvent::Removed { id });
+            }
+        }
+
+        for device in found {
+            if devices.contains_key(&device.id) {
+                continue;
+            }
+            match lock(&self.provider).open(&device) {
+                Ok(transport) => {
+                    info!("device {} added", device.id);
+                    let slot = Slot {
+                        generation: self
+                            .next_generation
+                            .fetch_add(1, Ordering::Relaxed),
+                        device: device.clone(),
+                        transport,
+                        lease: Default::default(),
+                        released: Condvar::new(),
+                    };
+                    devices.insert(device.id.clone(), Arc::new(slot));
+                    events.push(DeviceEvent::Added { device });
+                },
+                Err(err) => warn!("couldn't open device {}: {}", device.id, err),
+            }
+        }
+
+        // still holding the devices, so subscribers get each change once
+        let mut subscribers = lock(&self.subscribers);
+        for event in events {
+            subscribers.retain(|(_, subscriber)| subscriber.send(event.clone()).is_ok());
+        }
+    }
+
+    fn devices(&self) -> Vec<Device> {
+        lock(&self.devices)
+            .values()
+            .map(|slot| slot.device.clone())
+            .collect()
+    }
+
+    fn slot(
+        &self,
+        id: &str,
+    ) -> Result<Arc<Slot<P::Transport>>, BrokerError> {
+        lock(&self.devices)
+            .get(id)
+            .cloned()
+            .ok_or_else(|| BrokerError::UnknownDevice(id.to_string()))
+    }
+
+    fn handle(
+        &self,
+        client: u64,
+        leases: &mut Leases,
+        request: Request,
+    ) -> Result<Response, BrokerError> {
+        match request {
+            Request::List => Ok(Response::Devices { devices: self.devices() }),
+            Request::Exchange { device, apdu } => {
+                let apdu = hex::decode(&apdu).map_err(|err| BrokerError::Protocol(err.to_string()))?;
+                let command = APDUCommand::from_bytes(&apdu).map_err(|err| BrokerError::Protocol(err.to_string()))?;
+
+                let slot = self.slot(&device)?;
+                // the device was unplugged since it was leased, other clients may have used it meanwhile
+                if leases
+                    .get(&device)
+                    .is_some_and(|&generation| generation != slot.generation)
+                {
+                    return Err(BrokerError::LeaseLost(device));
+                }
+                // held during the exchange, so exchanges of different clients don't interleave
+                let _lease = slot.acquire(client)?;
+                let answer = block_on(slot.transport.exchange(&command))
+                    .map_err(|err| BrokerError::Device { kind: err.kind(), message: err.to_string() })?;
+
+                let mut raw = answer.data().to_vec();
+                raw.extend_from_slice(&answer.retcode().to_be_bytes());
+                Ok(Response::Answer { apdu: hex::encode(raw) })
+            },
+            Request::Lease { device } => {
+                let slot = self.slot(&device)?;
+                slot.acquire(client)?.owner = Some(client);
+                leases.insert(device.clone(), slot.generation);
+                debug!("client {} leased {}", client, device);
+                Ok(Response::Done)
+            },
+            Request::Release { device } => {
+                let leased = leases.remove(&device);
+                match self.slot(&device) {
+                    Ok(slot) if leased.is_none_or(|generation| generation == slot.generation) => slot.release(client),
+                    // a lease lost with its device has nothing left to release
+                    Ok(_) => {},
+                    Err(_) if leased.is_some() => {},
+                    Err(err) => return Err(err),
+                }
+                debug!("client {} released {}", client, device);
+                Ok(Response::Done)
+            },
+            Request::Subscribe => unreachable!("handled by the client loop"),
+        }
+    }
+
+    /// Release the leases of a client that went away
+    fn disconnect(
+        &self,
+        client: u64,
+    ) {
+        let slots: Vec<_> = lock(&self.devices)
+            .values()
+            .cloned()
+            .collect();
+        for slot in slots {
+            slot.release(client);
+        }
+    }
+
+    /// Serve the requests of a client until it disconnects
+    fn serve_client(
+        &self,
+        stream: UnixStream,
+        client: u64,
+    ) -> Result<(), BrokerError> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        let mut leases = Leases::new();
+
+        while let Some(request) = read_message::<_, Request>(&mut reader).transpose() {
+            let response = match request {
+                Ok(Request::Subscribe) => return self.forward_events(writer, client),
+                Ok(request) => self.handle(client, &mut leases, request),
+                Err(err) => Err(err),
+            };
+            let response = response.unwrap_or_else(|error| Response::Error { error });
+            write_message(&mut writer, &response)?;
+        }
+        Ok(())
+    }
+
+    /// Send the current devices, then every change, until the client goes away
+    fn forward_events(
+        &self,
+        mut stream: UnixStream,
+        client: u64,
+    ) -> Result<(), BrokerError> {
+        // subscribed while holding the devices, so no change is missed nor sent twice
+        let (devices, events) = {
+            let devices = lock(&self.devices);
+            let (sender, events) = mpsc::channel();
+            lock(&self.subscribers).push((client, sender));
+            (
+                devices
+                    .values()
+                    .map(|slot| slot.device.clone())
+                    .collect(),
+                events,
+            )
+        };
+
+        let result = send_events(&mut stream, devices, &events);
+        lock(&self.subscribers).retain(|(subscriber, _)| *subscriber != client);
+        result
+    }
+}
+
+/// Daemon holding the only handle to each device and sharing them between local clients
+///
+/// Every connection to the socket is a client session. Clients exchange commands with any device,
+/// and can lease a device for multi-APDU flows: the commands of other clients then wait until the
+/// lease is released, or the session closed. Devices are scanned for periodically, plugged and
+/// unplugged ones are notified to the subscribed clients.
+///
+/// ```ignore
+/// Broker::bind(default_socket_path(), HidProvider::new()?)?.serve()?;
+/// ```
+pub struct Broker<P: DeviceProvider> {
+    listener: UnixListener,
+    path: PathBuf,
+    state: Arc<State<P>>,
+    scan_interval: Duration,
+}
+
+impl<P: DeviceProvider> Broker<P> {
+    /// Listen on the socket at `path` and open the devices found by `provider`
+    ///
+    /// A socket left by a broker that is no longer running is replaced, any other file at `path` is
+    /// kept and makes binding fail. The socket is only accessible to the user running the broker
+    pub fn bind(
+        path: impl AsRef<Path>,
+        provider: P,
+    ) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let stale = fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_socket())
+            && UnixStream::connect(&path).is_err();
+        if stale {
+            fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+
+        let state = Arc::new(State {
+            provider: Mutex::new(provider),
+            devices: Mutex::new(BTreeMap::new()),
+            subscribers: Mutex::new(Vec::new()),
+            next_client: AtomicU64::new(0),
+            next_generation: AtomicU64::new(0),
+        });
+        state.scan();
+
+        Ok(Self { listener, path, state, scan_interval: SCAN_INTERVAL })
+    }
+
+    /// Set the delay between two scans for plugged and unplugged devices
+    pub fn with_scan_interval(
+        mut self,
+        interval: Duration,
+    ) -> Self {
+        self.scan_interval = interval;
+        self
+    }
+
+    /// Socket the broker listens on
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Devices currently held
+    pub fn devices(&self) -> Vec<Device> {
+        self.state.devices()
+    }
+
+    /// Scan for devices in the background and serve the clients, each in its own thread
+    ///
+    /// Failures to accept a connection are logged and retried, it only returns if the listener
+    /// itself is unusable
+    pub fn serve(&self) -> io::Result<()> {
+        let state = self.state.clone();
+        let interval = self.scan_interval;
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            state.scan();
+        });
+
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) if is_fatal_accept_error(&err) => return Err(err),
+                Err(err) => {
+                    warn!("couldn't accept a client: {}", err);
+                    thread::sleep(ACCEPT_RETRY_DELAY);
+                    continue;
+                },
+            };
+            let client = self
+                .state
+                .next_client
+                .fetch_add(1, Ordering::Relaxed);
+            let state = self.state.clone();
+
+            thread::spawn(move || {
+                debug!("client {} connected", client);
+                if let Err(err) = state.serve_client(stream, client) {
+                    debug!("client {} dropped: {}", client, err);
+                }
+                state.disconnect(client);
+                debug!("client {} disconnected", client);
+            });
+        }
+    }
+}
+
+impl<P: DeviceProvider> Drop for Broker<P> {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Send `devices`, then the events, until the client disconnects
+///
+/// Subscribed clients send nothing, the connection is checked every [SUBSCRIBER_PROBE_INTERVAL]
+/// so a client going away is noticed without waiting for an event
+fn send_events(
+    stream: &mut UnixStream,
+    devices: Vec<Device>,
+    events: &Receiver<DeviceEvent>,
+) -> Result<(), BrokerError> {
+    write_message(stream, &Response::Devices { devices })?;
+    loop {
+        match events.recv_timeout(SUBSCRIBER_PROBE_INTERVAL) {
+            Ok(event) => write_message(stream, &event)?,
+            Err(RecvTimeoutError::Timeout) if is_connected(stream)? => {},
+            Err(_) => return Ok(()),
+        }
+    }
+}
+
+/// Whether the peer is still connected, discarding anything it sent
+fn is_connected(stream: &mut UnixStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let read = stream.read(&mut [0; 64]);
+    stream.set_nonblocking(false)?;
+    match read {
+        Ok(0) => Ok(false),
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(true),
+        Err(err) => Err(err),
+    }
+}
+
+/// Whether an `accept` error comes from the listener rather than from a connection or a temporary
+/// shortage of resources
+fn is_fatal_accept_error(err: &io::Error) -> bool {
+    err.kind() == ErrorKind::InvalidInput
+}
@@ -0,0 +1,301 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::{
+    fs,
+    io::{self, BufReader, Write},
+    ops::Deref,
+    os::unix::{fs::PermissionsExt, net::UnixStream},
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use futures::executor::block_on;
+use ledger_transport::{async_trait, APDUAnswer, APDUCommand, Exchange};
+
+use super::*;
+
+/// Devices plugged in the mock provider
+type Plugged = Arc<Mutex<Vec<Device>>>;
+
+struct MockProvider(Plugged);
+
+/// Answers the device id followed by the payload, with 9000
+struct MockTransport(String);
+
+#[async_trait]
+impl Exchange for MockTransport {
+    type Error = io::Error;
+    type AnswerType = Vec<u8>;
+
+    async fn exchange<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        let mut answer = self.0.as_bytes().to_vec();
+        answer.extend_from_slice(&command.data);
+        answer.extend_from_slice(&[0x90, 0x00]);
+        Ok(APDUAnswer::from_answer(answer).unwrap())
+    }
+}
+
+impl DeviceProvider for MockProvider {
+    type Transport = MockTransport;
+    type Error = io::Error;
+
+    fn scan(&mut self) -> Vec<Device> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn open(
+        &mut self,
+        device: &Device,
+    ) -> Result<MockTransport, io::Error> {
+        Ok(MockTransport(device.id.clone()))
+    }
+}
+
+fn device(id: &str) -> Device {
+    Device { id: id.to_string(), model: Some("Nano X".to_string()), serial: None }
+}
+
+/// Serve the plugged devices on a new socket, in the background
+fn spawn_broker(
+    name: &str,
+    plugged: &Plugged,
+) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ledger-broker-{}-{}.sock", name, std::process::id()));
+    let broker = Broker::bind(&path, MockProvider(plugged.clone()))
+        .unwrap()
+        .with_scan_interval(Duration::from_millis(20));
+
+    thread::spawn(move || broker.serve());
+    path
+}
+
+fn command(data: &[u8]) -> APDUCommand<Vec<u8>> {
+    APDUCommand { cla: 0x55, ins: 0x02, p1: 0x00, p2: 0x00, data: data.to_vec() }
+}
+
+#[test]
+fn broker_serves_clients() {
+    let plugged = Arc::new(Mutex::new(vec![device("a"), device("b")]));
+    let path = spawn_broker("serve", &plugged);
+
+    let mut client = BrokerClient::connect(&path).unwrap();
+    assert_eq!(client.devices().unwrap(), vec![device("a"), device("b")]);
+
+    let first = TransportBroker::connect(&path, None).unwrap();
+    let second = TransportBroker::connect(&path, Some("b")).unwrap();
+    assert_eq!(first.device(), "a");
+
+    let answer = block_on(first.exchange(&command(b"1"))).unwrap();
+    assert_eq!((answer.data(), answer.retcode()), (&b"a1"[..], 0x9000));
+    let answer = block_on(second.exchange(&command(b"2"))).unwrap();
+    assert_eq!(answer.data(), b"b2");
+
+    let unknown = TransportBroker::connect(&path, Some("c")).unwrap();
+    assert_eq!(block_on(unknown.exchange(&command(b""))).unwrap_err(), BrokerError::UnknownDevice("c".to_string()));
+}
+
+#[test]
+fn broker_leases() {
+    let plugged = Arc::new(Mutex::new(vec![device("a")]));
+    let path = spawn_broker("lease", &plugged);
+
+    let owner = TransportBroker::connect(&path, None).unwrap();
+    let lease = owner.lease().unwrap();
+
+    // another client's command waits for the lease
+    let (done, finished) = mpsc::channel();
+    let other = TransportBroker::connect(&path, None).unwrap();
+    thread::spawn(move || {
+        let answer = block_on(other.exchange(&command(b"other"))).unwrap();
+        done.send(answer.data().to_vec())
+            .unwrap();
+    });
+    assert!(finished
+        .recv_timeout(Duration::from_millis(200))
+        .is_err());
+
+    let answer = block_on(owner.exchange(&command(b"owner"))).unwrap();
+    assert_eq!(answer.data(), b"aowner");
+
+    lease.release().unwrap();
+    assert_eq!(
+        finished
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap(),
+        b"aother"
+    );
+
+    // a session going away releases its leases
+    let session = TransportBroker::connect(&path, None).unwrap();
+    let lease = session.lease().unwrap();
+    std::mem::forget(lease);
+    drop(session);
+    let answer = block_on(owner.exchange(&command(b""))).unwrap();
+    assert_eq!(answer.data(), b"a");
+}
+
+#[test]
+fn broker_hotplug() {
+    let plugged = Arc::new(Mutex::new(vec![device("a")]));
+    let path = spawn_broker("hotplug", &plugged);
+
+    let (devices, mut events) = BrokerClient::connect(&path)
+        .unwrap()
+        .subscribe()
+        .unwrap();
+    assert_eq!(devices, vec![device("a")]);
+    let transport = TransportBroker::connect(&path, Some("a")).unwrap();
+
+    plugged
+        .lock()
+        .unwrap()
+        .push(device("b"));
+    assert_eq!(events.next().unwrap().unwrap(), DeviceEvent::Added { device: device("b") });
+
+    plugged.lock().unwrap().remove(0);
+    assert_eq!(events.next().unwrap().unwrap(), DeviceEvent::Removed { id: "a".to_string() });
+    assert_eq!(block_on(transport.exchange(&command(b""))).unwrap_err(), BrokerError::UnknownDevice("a".to_string()));
+
+    let error = serde_json::to_string(&protocol::Response::Error {
+        error: BrokerError::Device { kind: ledger_transport::TransportErrorKind::Timeout, message: "slow".to_string() },
+    })
+    .unwrap();
+    assert_eq!(error, r#"{"type":"error","error":{"error":"device","details":{"kind":"timeout","message":"slow"}}}"#);
+}
+
+#[test]
+fn broker_bind() {
+    let path = std::env::temp_dir().join(format!("ledger-broker-bind-{}.sock", std::process::id()));
+    fs::write(&path, "not a socket").unwrap();
+    let plugged = Plugged::default();
+
+    // only stale sockets are replaced
+    assert!(Broker::bind(&path, MockProvider(plugged.clone())).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "not a socket");
+    fs::remove_file(&path).unwrap();
+
+    let broker = Broker::bind(&path, MockProvider(plugged)).unwrap();
+    let mode = fs::metadata(&path)
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o600);
+    drop(broker);
+}
+
+#[test]
+fn broker_sessions_lease() {
+    let plugged = Arc::new(Mutex::new(vec![device("a")]));
+    let path = spawn_broker("session", &plugged);
+
+    let owner = TransportBroker::connect(&path, None).unwrap();
+    let session = block_on(owner.lock_session()).unwrap();
+
+    let (done, finished) = mpsc::channel();
+    let other = TransportBroker::connect(&path, None).unwrap();
+    thread::spawn(move || {
+        let answer = block_on(other.exchange(&command(b"other"))).unwrap();
+        done.send(answer.data().to_vec())
+            .unwrap();
+    });
+    assert!(finished
+        .recv_timeout(Duration::from_millis(200))
+        .is_err());
+
+    let answer = block_on(session.exchange(&command(b"owner"))).unwrap();
+    assert_eq!(answer.data(), b"aowner");
+
+    drop(session);
+    assert_eq!(
+        finished
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap(),
+        b"aother"
+    );
+}
+
+#[test]
+fn broker_rejects_long_messages() {
+    let plugged = Arc::new(Mutex::new(vec![device("a")]));
+    let path = spawn_broker("long", &plugged);
+
+    let mut writer = UnixStream::connect(&path).unwrap();
+    let mut reader = BufReader::new(writer.try_clone().unwrap());
+    let mut message = vec![b'x'; MAX_MESSAGE_LEN * 2];
+    message.push(b'\n');
+    writer.write_all(&message).unwrap();
+    protocol::write_message(&mut writer, &protocol::Request::List).unwrap();
+
+    let response: protocol::Response = protocol::read_message(&mut reader)
+        .unwrap()
+        .unwrap();
+    assert!(matches!(response, protocol::Response::Error { error: BrokerError::Protocol(_) }));
+
+    // the connection is still usable
+    let response: protocol::Response = protocol::read_message(&mut reader)
+        .unwrap()
+        .unwrap();
+    assert_eq!(response, protocol::Response::Devices { devices: vec![device("a")] });
+}
+
+#[test]
+fn broker_replug_loses_lease() {
+    let plugged = Arc::new(Mutex::new(vec![device("a")]));
+    let path = spawn_broker("replug", &plugged);
+
+    let (_, mut events) = BrokerClient::connect(&path)
+        .unwrap()
+        .subscribe()
+        .unwrap();
+    let owner = TransportBroker::connect(&path, None).unwrap();
+    let lease = owner.lease().unwrap();
+
+    plugged.lock().unwrap().clear();
+    assert_eq!(events.next().unwrap().unwrap(), DeviceEvent::Removed { id: "a".to_string() });
+    plugged
+        .lock()
+        .unwrap()
+        .push(device("a"));
+    assert_eq!(events.next().unwrap().unwrap(), DeviceEvent::Added { device: device("a") });
+
+    // the plugged device is free for others, the previous holder must lease it again
+    let other = TransportBroker::connect(&path, None).unwrap();
+    assert_eq!(
+        block_on(other.exchange(&command(b"")))
+            .unwrap()
+            .data(),
+        b"a"
+    );
+    assert_eq!(block_on(owner.exchange(&command(b""))).unwrap_err(), BrokerError::LeaseLost("a".to_string()));
+    assert_eq!(block_on(owner.exchange(&command(b""))).unwrap_err(), BrokerError::LeaseLost("a".to_string()));
+
+    lease.release().unwrap();
+    let _lease = owner.lease().unwrap();
+    assert_eq!(
+        block_on(owner.exchange(&command(b"1")))
+            .unwrap()
+            .data(),
+        b"a1"
+    );
+}
@@ -0,0 +1,308 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Command line parsing
+
+use std::path::PathBuf;
+
+use crate::errors::CliError;
+
+/// Usage printed by `ledger help`
+pub const USAGE: &str = "\
+usage: ledger [--json] [--capture <pcap>] <command>
+
+commands:
+    devices                     list the connected ledger devices
+    device-info                 show the device info, from the dashboard
+    app-info                    show the name and version of the open app
+    version --cla <cla>         show the version of the open app
+    apdu <hex>                  send a raw APDU
+    send-chunks --cla <cla> --ins <ins> --file <path> [--init <hex>] [--chunk-size <n>]
+                                send a file in chunks
+    open-app <name>             open an app from the dashboard
+    quit-app                    go back to the dashboard
+    dissect <command> [<answer>] [--schema <path>]
+                                decode a command and its answer, given in hex, without a device
+    import-pcap <pcap>          print the APDUs of a usbmon capture as a replayable transcript
+    verify-audit <log> [--head <hash>]
+                                check the hash chain of an audit log, and that it reaches a known head
+
+options:
+    --json                      print JSON instead of text
+    --capture <pcap>            write the HID frames exchanged with the device to a pcap file
+    help                        show this message";
+
+/// Parsed command line
+#[derive(Debug, PartialEq, Eq)]
+pub struct Args {
+    /// Print JSON instead of text
+    pub json: bool,
+    /// pcap file to write the HID frames to
+    pub capture: Option<PathBuf>,
+    /// Command to run
+    pub command: Command,
+}
+
+/// Command to run
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// List the connected devices
+    Devices,
+    /// Device info, from the dashboard
+    DeviceInfo,
+    /// Info of the open app
+    AppInfo,
+    /// Version of the open app
+    Version {
+        /// App's APDU CLA
+        cla: u8,
+    },
+    /// Raw APDU exchange
+    Apdu {
+        /// Serialized APDU command
+        apdu: Vec<u8>,
+    },
+    /// Upload a file in chunks
+    SendChunks {
+        /// App's APDU CLA
+        cla: u8,
+        /// Instruction receiving the chunks
+        ins: u8,
+        /// Payload of the init chunk
+        init: Vec<u8>,
+        /// Payload size of each chunk
+        chunk_size: Option<usize>,
+        /// File to send
+        file: PathBuf,
+    },
+    /// Open an app from the dashboard
+    OpenApp {
+        /// App name
+        name: String,
+    },
+    /// Go back to the dashboard
+    QuitApp,
+    /// Decode a command and its answer
+    Dissect {
+        /// Serialized APDU command
+        command: Vec<u8>,
+        /// Answer, status word included
+        answer: Option<Vec<u8>>,
+        /// App schema
+        schema: Option<PathBuf>,
+    },
+    /// Print the APDUs of a capture
+    ImportPcap {
+        /// pcap or pcapng capture
+        file: PathBuf,
+    },
+    /// Check an audit log
+    VerifyAudit {
+        /// Audit log
+        file: PathBuf,
+        /// Hash the log must reach
+        head: Option<String>,
+    },
+    /// Show the usage
+    Help,
+}
+
+impl Args {
+    /// Parse the command line arguments, without the program name
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, CliError> {
+        let mut args = args.into_iter();
+        let mut json = false;
+        let mut capture = None;
+
+        let command = loop {
+            match args.next().as_deref() {
+                Some("--json") => json = true,
+                Some("--capture") => match args.next() {
+                    Some(path) => capture = Some(PathBuf::from(path)),
+                    None => return Err(CliError::Usage("missing value for `--capture`".to_string())),
+                },
+                Some(command) => break command.to_string(),
+                None => return Ok(Args { json, capture, command: Command::Help }),
+            }
+        };
+        let mut rest = Rest { args: args.collect() };
+
+        let command = match command.as_str() {
+            "devices" => Command::Devices,
+            "device-info" => Command::DeviceInfo,
+            "app-info" => Command::AppInfo,
+            "version" => Command::Version { cla: parse_byte(&rest.required("--cla")?)? },
+            "apdu" => Command::Apdu { apdu: parse_hex(&rest.positional("apdu")?)? },
+            "send-chunks" => Command::SendChunks {
+                cla: parse_byte(&rest.required("--cla")?)?,
+                ins: parse_byte(&rest.required("--ins")?)?,
+                init: match rest.option("--init")? {
+                    Some(init) => parse_hex(&init)?,
+                    None => Vec::new(),
+                },
+                chunk_size: match rest.option("--chunk-size")? {
+                    Some(size) => Some(
+                        size.parse()
+                            .map_err(|_| CliError::Usage(format!("invalid chunk size `{size}`")))?,
+                    ),
+                    None => None,
+                },
+                file: rest.required("--file")?.into(),
+            },
+            "open-app" => Command::OpenApp { name: rest.positional("app name")? },
+            "quit-app" => Command::QuitApp,
+            "import-pcap" => Command::ImportPcap { file: rest.positional("capture file")?.into() },
+            "verify-audit" => {
+                Command::VerifyAudit { head: rest.option("--head")?, file: rest.positional("audit log")?.into() }
+            },
+            "dissect" => {
+                let schema = rest
+                    .option("--schema")?
+                    .map(PathBuf::from);
+                let command = parse_hex(&rest.positional("command")?)?;
+                let answer = match rest.positional("answer") {
+                    Ok(answer) => Some(parse_hex(&answer)?),
+                    Err(_) => None,
+                };
+                Command::Dissect { command, answer, schema }
+            },
+            "help" | "--help" | "-h" => Command::Help,
+            command => return Err(CliError::Usage(format!("unknown command `{command}`"))),
+        };
+
+        if let Some(arg) = rest.args.first() {
+            return Err(CliError::Usage(format!("unexpected argument `{arg}`")));
+        }
+
+        Ok(Args { json, capture, command })
+    }
+}
+
+/// Arguments left after the command name
+struct Rest {
+    args: Vec<String>,
+}
+
+impl Rest {
+    fn option(
+        &mut self,
+        name: &str,
+    ) -> Result<Option<String>, CliError> {
+        let Some(at) = self
+            .args
+            .iter()
+            .position(|arg| arg == name)
+        else {
+            return Ok(None);
+        };
+
+        if at + 1 >= self.args.len() {
+            return Err(CliError::Usage(format!("missing value for `{name}`")));
+        }
+        let value = self.args.remove(at + 1);
+        self.args.remove(at);
+        Ok(Some(value))
+    }
+
+    fn required(
+        &mut self,
+        name: &str,
+    ) -> Result<String, CliError> {
+        self.option(name)?
+            .ok_or_else(|| CliError::Usage(format!("missing `{name}`")))
+    }
+
+    fn positional(
+        &mut self,
+        name: &str,
+    ) -> Result<String, CliError> {
+        match self
+            .args
+            .iter()
+            .position(|arg| !arg.starts_with("--"))
+        {
+            Some(at) => Ok(self.args.remove(at)),
+            None => Err(CliError::Usage(format!("missing {name}"))),
+        }
+    }
+}
+
+/// Parse a byte written in hex, with or without `0x`
+fn parse_byte(value: &str) -> Result<u8, CliError> {
+    u8::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|_| CliError::Usage(format!("invalid byte `{value}`")))
+}
+
+/// Parse hex bytes, with or without `0x`
+fn parse_hex(value: &str) -> Result<Vec<u8>, CliError> {
+    hex::decode(value.trim_start_matches("0x")).map_err(|_| CliError::Usage(format!("invalid hex `{value}`")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args, CliError> {
+        Args::parse(
+            args.split_whitespace()
+                .map(String::from),
+        )
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse("").unwrap(), Args { json: false, capture: None, command: Command::Help });
+        assert_eq!(parse("--json --capture out.pcap version --cla 0x55").unwrap(), Args {
+            json: true,
+            capture: Some("out.pcap".into()),
+            command: Command::Version { cla: 0x55 }
+        });
+        assert_eq!(
+            parse("import-pcap in.pcapng")
+                .unwrap()
+                .command,
+            Command::ImportPcap { file: "in.pcapng".into() }
+        );
+        assert_eq!(
+            parse("verify-audit audit.jsonl --head 00ff")
+                .unwrap()
+                .command,
+            Command::VerifyAudit { file: "audit.jsonl".into(), head: Some("00ff".to_string()) }
+        );
+        assert_eq!(parse("apdu e0010000").unwrap().command, Command::Apdu { apdu: vec![0xe0, 0x01, 0x00, 0x00] });
+        assert_eq!(
+            parse("open-app Cosmos")
+                .unwrap()
+                .command,
+            Command::OpenApp { name: "Cosmos".to_string() }
+        );
+        assert_eq!(
+            parse("send-chunks --file tx.bin --ins 02 --cla 55 --chunk-size 100")
+                .unwrap()
+                .command,
+            Command::SendChunks { cla: 0x55, ins: 0x02, init: vec![], chunk_size: Some(100), file: "tx.bin".into() }
+        );
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert!(matches!(parse("version"), Err(CliError::Usage(_))));
+        assert!(matches!(parse("--capture"), Err(CliError::Usage(_))));
+        assert!(matches!(parse("version --cla"), Err(CliError::Usage(_))));
+        assert!(matches!(parse("apdu xyz"), Err(CliError::Usage(_))));
+        assert!(matches!(parse("quit-app now"), Err(CliError::Usage(_))));
+        assert!(matches!(parse("reboot"), Err(CliError::Usage(_))));
+    }
+}
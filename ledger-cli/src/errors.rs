@@ -0,0 +1,82 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use ledger_transport::APDUErrorCode;
+use ledger_transport_hid::{CaptureError, LedgerHIDError};
+use ledger_zondax_generic::LedgerAppError;
+use thiserror::Error;
+
+/// Error of a CLI command
+#[derive(Error, Debug)]
+pub enum CliError {
+    /// Invalid command line
+    #[error("{0}")]
+    Usage(String),
+    /// No ledger device connected
+    #[error("no ledger device found")]
+    NoDevice,
+    /// HID transport error
+    #[error(transparent)]
+    Hid(#[from] LedgerHIDError),
+    /// App or device error
+    #[error("{}", describe(.0))]
+    App(#[from] LedgerAppError<LedgerHIDError>),
+    /// i/o error
+    #[error("i/o error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Invalid capture
+    #[error(transparent)]
+    Capture(#[from] CaptureError),
+    /// The audit log failed verification
+    #[error(transparent)]
+    Audit(#[from] ledger_audit::VerifyError),
+    /// Invalid app schema
+    #[error(transparent)]
+    Schema(#[from] ledger_apdu_dissect::SchemaError),
+    /// JSON output error
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl CliError {
+    /// Status word returned by the device, if any
+    pub fn status_word(&self) -> Option<u16> {
+        match self {
+            CliError::App(err) => err.status_word(),
+            _ => None,
+        }
+    }
+}
+
+/// Describe a status word, e.g. `0x6985 (Conditions not satisfied)`
+pub fn describe_status_word(code: u16) -> String {
+    match APDUErrorCode::try_from(code) {
+        Ok(APDUErrorCode::NoError) => format!("0x{code:04x} (OK)"),
+        Ok(err) => format!("0x{code:04x} ({})", err.description()),
+        Err(_) => format!("0x{code:04x}"),
+    }
+}
+
+fn describe(err: &LedgerAppError<LedgerHIDError>) -> String {
+    match err {
+        LedgerAppError::AppSpecific(code, _) | LedgerAppError::Unknown(code) => {
+            format!("device returned {}", describe_status_word(*code))
+        },
+        LedgerAppError::ChunkRejected { index, retcode, .. } => {
+            format!("chunk {index} rejected, device returned {}", describe_status_word(*retcode))
+        },
+        err => err.to_string(),
+    }
+}
@@ -0,0 +1,268 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! `ledger` command line tool
+
+#![deny(unused_import_braces, unused_qualifications)]
+
+mod args;
+mod errors;
+
+use std::{fs::File, io::BufReader, ops::Deref, process::ExitCode};
+
+use futures::executor::block_on;
+use ledger_apdu_dissect::{Dissector, Schema};
+use ledger_transport::{APDUAnswer, APDUCommand};
+use ledger_transport_hid::{
+    capture::PcapWriter, hidapi::HidApi, import::Transcript, LedgerHIDError, TransportNativeHID,
+};
+use ledger_zondax_generic::{AppConfig, AppSession};
+use serde::Serialize;
+
+use crate::{
+    args::{Args, Command, USAGE},
+    errors::{describe_status_word, CliError},
+};
+
+/// CLA of the dashboard commands, only used to build the session
+const CLA_DASHBOARD: u8 = 0xe0;
+
+/// A connected ledger device
+#[derive(Serialize)]
+struct Device {
+    path: String,
+    model: String,
+    #[serde(rename(serialize = "productId"))]
+    product_id: u16,
+    #[serde(rename(serialize = "serialNumber"))]
+    serial_number: Option<String>,
+    product: Option<String>,
+}
+
+/// Answer of a raw exchange
+#[derive(Serialize)]
+struct Answer {
+    data: String,
+    #[serde(rename(serialize = "statusWord"))]
+    status_word: u16,
+    status: String,
+}
+
+impl<B: Deref<Target = [u8]>> From<&APDUAnswer<B>> for Answer {
+    fn from(answer: &APDUAnswer<B>) -> Self {
+        Answer {
+            data: hex::encode(answer.data()),
+            status_word: answer.retcode(),
+            status: describe_status_word(answer.retcode()),
+        }
+    }
+}
+
+/// Output of `dissect`
+#[derive(Serialize)]
+struct Dissected {
+    command: ledger_apdu_dissect::CommandDissection,
+    answer: Option<ledger_apdu_dissect::AnswerDissection>,
+}
+
+/// Exchange of `import-pcap`
+#[derive(Serialize)]
+struct Exchange {
+    command: String,
+    answer: Option<String>,
+}
+
+/// Error printed with `--json`
+#[derive(Serialize)]
+struct JsonError {
+    error: String,
+    #[serde(rename(serialize = "statusWord"))]
+    status_word: Option<u16>,
+}
+
+fn connect(api: &HidApi) -> Result<TransportNativeHID, CliError> {
+    match TransportNativeHID::new(api) {
+        Err(LedgerHIDError::DeviceNotFound) => Err(CliError::NoDevice),
+        transport => Ok(transport?),
+    }
+}
+
+fn print<T: Serialize>(
+    json: bool,
+    value: &T,
+    text: impl FnOnce(&T) -> String,
+) -> Result<(), CliError> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    } else {
+        println!("{}", text(value));
+    }
+    Ok(())
+}
+
+fn run(args: Args) -> Result<(), CliError> {
+    let json = args.json;
+    if args.command == Command::Help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
+    if let Command::Dissect { command, answer, schema } = &args.command {
+        let mut dissector = Dissector::new();
+        if let Some(schema) = schema {
+            dissector = dissector.with_schema(Schema::from_json(&std::fs::read_to_string(schema)?)?);
+        }
+
+        let dissected = Dissected {
+            command: dissector.command(command),
+            answer: answer
+                .as_ref()
+                .map(|answer| dissector.answer(command, answer)),
+        };
+        return print(json, &dissected, |dissected| match &dissected.answer {
+            Some(answer) => format!("=> {}\n<= {}", dissected.command, answer),
+            None => format!("=> {}", dissected.command),
+        });
+    }
+
+    if let Command::ImportPcap { file } = &args.command {
+        let transcript = Transcript::from_capture(&std::fs::read(file)?)?;
+        let exchanges: Vec<_> = transcript
+            .exchanges
+            .iter()
+            .map(|exchange| Exchange {
+                command: hex::encode(&exchange.command),
+                answer: exchange
+                    .answer
+                    .as_ref()
+                    .map(hex::encode),
+            })
+            .collect();
+        return print(json, &exchanges, |_| transcript.to_string());
+    }
+
+    if let Command::VerifyAudit { file, head } = &args.command {
+        let log = BufReader::new(File::open(file)?);
+        let head = match head {
+            Some(expected) => ledger_audit::verify_head(log, expected)?,
+            None => ledger_audit::verify(log)?,
+        };
+        return print(json, &head, |head| format!("{} records, head {}", head.records, head.hash));
+    }
+
+    let api = HidApi::new().map_err(LedgerHIDError::from)?;
+    if args.command == Command::Devices {
+        let devices: Vec<_> = TransportNativeHID::list_ledgers(&api)
+            .map(|dev| Device {
+                path: dev
+                    .path()
+                    .to_string_lossy()
+                    .into_owned(),
+                model: TransportNativeHID::model(dev).to_string(),
+                product_id: dev.product_id(),
+                serial_number: dev.serial_number().map(String::from),
+                product: dev.product_string().map(String::from),
+            })
+            .collect();
+
+        return print(json, &devices, |devices| {
+            devices
+                .iter()
+                .map(|dev| format!("{}\t{}", dev.model, dev.path))
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+    }
+
+    let transport = connect(&api)?;
+    if let Some(capture) = &args.capture {
+        transport.set_capture(PcapWriter::new(std::io::BufWriter::new(File::create(capture)?))?);
+    }
+    let dashboard = AppSession::new(&transport, AppConfig::new(CLA_DASHBOARD));
+
+    match args.command {
+        Command::DeviceInfo => {
+            let info = block_on(dashboard.get_device_info())?;
+            print(json, &info, |info| {
+                format!(
+                    "target id:   {}\nse version:  {}\nmcu version: {}\nflags:       {:?}",
+                    hex::encode(info.target_id),
+                    info.se_version,
+                    info.mcu_version,
+                    info.flags
+                )
+            })
+        },
+        Command::AppInfo => {
+            let info = block_on(dashboard.get_app_info())?;
+            print(json, &info, |info| format!("{} {}", info.app_name, info.app_version))
+        },
+        Command::Version { cla } => {
+            let version = block_on(AppSession::new(&transport, AppConfig::new(cla)).get_version())?;
+            print(json, &version, |version| version.to_string())
+        },
+        Command::Apdu { apdu } => {
+            let command = APDUCommand::from_bytes(&apdu).map_err(|err| CliError::Usage(err.to_string()))?;
+            let answer = transport.exchange(&command)?;
+            print(json, &Answer::from(&answer), |answer| format!("{}\n{}", answer.data, answer.status))
+        },
+        Command::SendChunks { cla, ins, init, chunk_size, file } => {
+            let message = std::fs::read(file)?;
+            let mut config = AppConfig::new(cla);
+            if let Some(chunk_size) = chunk_size {
+                config = config.with_chunk_size(chunk_size);
+            }
+            let init = APDUCommand { cla, ins, p1: 0x00, p2: 0x00, data: init };
+
+            let answer = block_on(AppSession::new(&transport, config).send_chunks(init, &message))?;
+            print(json, &Answer::from(&answer), |answer| format!("{}\n{}", answer.data, answer.status))
+        },
+        Command::OpenApp { name } => Ok(block_on(dashboard.open_app(&name))?),
+        Command::QuitApp => Ok(block_on(dashboard.quit_app())?),
+        Command::Devices
+        | Command::Help
+        | Command::Dissect { .. }
+        | Command::ImportPcap { .. }
+        | Command::VerifyAudit { .. } => {
+            unreachable!("handled without a device")
+        },
+    }
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        },
+    };
+    let json = args.json;
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            if json {
+                let error = JsonError { error: err.to_string(), status_word: err.status_word() };
+                eprintln!("{}", serde_json::to_string_pretty(&error).unwrap_or_default());
+            } else {
+                eprintln!("error: {err}");
+            }
+            ExitCode::FAILURE
+        },
+    }
+}
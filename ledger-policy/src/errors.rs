@@ -0,0 +1,90 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use ledger_transport::{TransportError, TransportErrorKind};
+use serde::Serialize;
+use thiserror::Error;
+
+/// Why a command was refused by a [Policy](crate::Policy)
+#[derive(Clone, Debug, Eq, Error, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "violation")]
+pub enum PolicyViolation {
+    /// No rule allows the CLA
+    #[error("CLA 0x{cla:02x} is not allowed")]
+    Cla {
+        /// Class of the command
+        cla: u8,
+    },
+    /// No rule allows the INS for this CLA
+    #[error("INS 0x{ins:02x} is not allowed for CLA 0x{cla:02x}")]
+    Ins {
+        /// Class of the command
+        cla: u8,
+        /// Instruction of the command
+        ins: u8,
+    },
+    /// No rule allows the parameters for this CLA and INS
+    #[error("P1 0x{p1:02x} P2 0x{p2:02x} are not allowed for CLA 0x{cla:02x} INS 0x{ins:02x}")]
+    Params {
+        /// Class of the command
+        cla: u8,
+        /// Instruction of the command
+        ins: u8,
+        /// First parameter
+        p1: u8,
+        /// Second parameter
+        p2: u8,
+    },
+    /// The payload is larger than allowed
+    #[error("payload of {len} bytes is larger than the {max} allowed")]
+    PayloadTooLarge {
+        /// Payload length
+        len: usize,
+        /// Largest payload allowed
+        max: usize,
+    },
+}
+
+/// Error of a [PolicyTransport](crate::PolicyTransport) exchange
+#[derive(Clone, Debug, Error)]
+pub enum PolicyError<E> {
+    /// The command was refused, it didn't reach the transport
+    #[error("denied by policy | {0}")]
+    Denied(PolicyViolation),
+    /// The wrapped transport failed
+    #[error("Transport | {0}")]
+    Transport(E),
+}
+
+impl<E: TransportError> TransportError for PolicyError<E> {
+    fn kind(&self) -> TransportErrorKind {
+        match self {
+            Self::Denied(_) => TransportErrorKind::Denied,
+            Self::Transport(err) => err.kind(),
+        }
+    }
+}
+
+/// Invalid policy
+#[derive(Debug, Error)]
+pub enum PolicyParseError {
+    /// The JSON policy is invalid
+    #[error("invalid policy: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The TOML policy is invalid
+    #[cfg(feature = "toml")]
+    #[error("invalid policy: {0}")]
+    Toml(#[from] toml::de::Error),
+}
@@ -0,0 +1,33 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Allowlist policy for the APDUs sent to Ledger devices
+//!
+//! [PolicyTransport] wraps any [Exchange](ledger_transport::Exchange) and refuses the commands not
+//! allowed by a [Policy] with [PolicyError::Denied] before they reach the device. Policies are built
+//! in code or loaded from JSON, or from TOML with `Policy::from_toml` behind the `toml` feature, and
+//! denials can be recorded in a [ledger_audit::AuditLog].
+
+#![deny(warnings, unused_qualifications, missing_docs)]
+
+mod errors;
+pub mod policy;
+#[cfg(test)]
+mod tests;
+pub mod transport;
+
+pub use errors::{PolicyError, PolicyParseError, PolicyViolation};
+pub use policy::{Policy, Rule};
+pub use transport::{AuditDenials, DenialSink, PolicyTransport};
@@ -0,0 +1,232 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Allowlist of the commands that may reach the device
+
+use std::ops::Deref;
+
+use ledger_apdu::num::{de_bytes, de_u8};
+use ledger_transport::APDUCommand;
+use serde::{Deserialize, Serialize};
+
+use crate::{PolicyParseError, PolicyViolation};
+
+/// Commands matching a rule are allowed
+///
+/// Empty `ins`, `p1` or `p2` lists allow any value
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Rule {
+    /// Name of the rule, for humans
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Class allowed
+    #[serde(deserialize_with = "de_u8")]
+    pub cla: u8,
+    /// Instructions allowed
+    #[serde(default, deserialize_with = "de_bytes")]
+    pub ins: Vec<u8>,
+    /// First parameters allowed
+    #[serde(default, deserialize_with = "de_bytes")]
+    pub p1: Vec<u8>,
+    /// Second parameters allowed
+    #[serde(default, deserialize_with = "de_bytes")]
+    pub p2: Vec<u8>,
+    /// Largest payload allowed
+    #[serde(default)]
+    pub max_payload: Option<usize>,
+}
+
+impl Rule {
+    /// Allow any command with `cla`
+    pub fn new(cla: u8) -> Self {
+        Self { name: None, cla, ins: Vec::new(), p1: Vec::new(), p2: Vec::new(), max_payload: None }
+    }
+
+    /// Name the rule
+    pub fn with_name(
+        mut self,
+        name: impl Into<String>,
+    ) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Only allow these instructions
+    pub fn with_ins(
+        mut self,
+        ins: &[u8],
+    ) -> Self {
+        self.ins = ins.to_vec();
+        self
+    }
+
+    /// Only allow these first parameters
+    pub fn with_p1(
+        mut self,
+        p1: &[u8],
+    ) -> Self {
+        self.p1 = p1.to_vec();
+        self
+    }
+
+    /// Only allow these second parameters
+    pub fn with_p2(
+        mut self,
+        p2: &[u8],
+    ) -> Self {
+        self.p2 = p2.to_vec();
+        self
+    }
+
+    /// Only allow payloads up to `max` bytes
+    pub fn with_max_payload(
+        mut self,
+        max: usize,
+    ) -> Self {
+        self.max_payload = Some(max);
+        self
+    }
+
+    fn allows_ins(
+        &self,
+        ins: u8,
+    ) -> bool {
+        self.ins.is_empty() || self.ins.contains(&ins)
+    }
+
+    fn allows_params(
+        &self,
+        p1: u8,
+        p2: u8,
+    ) -> bool {
+        (self.p1.is_empty() || self.p1.contains(&p1)) && (self.p2.is_empty() || self.p2.contains(&p2))
+    }
+}
+
+/// Allowlist of commands: a command is allowed if it's within `max_payload` and a rule matches it
+///
+/// ```
+/// use ledger_policy::{Policy, Rule};
+///
+/// let policy = Policy::new()
+///     .with_max_payload(255)
+///     .allow(Rule::new(0x55).with_ins(&[0x04, 0x02]));
+///
+/// let json = Policy::from_json(r#"{ "maxPayload": 255, "rules": [{ "cla": "0x55", "ins": ["0x04", 2] }] }"#);
+/// assert_eq!(json.unwrap(), policy);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Policy {
+    /// Largest payload allowed for any command
+    #[serde(default)]
+    pub max_payload: Option<usize>,
+    /// Allowed commands
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// Policy denying everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow the commands matching `rule`
+    pub fn allow(
+        mut self,
+        rule: Rule,
+    ) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Only allow payloads up to `max` bytes
+    pub fn with_max_payload(
+        mut self,
+        max: usize,
+    ) -> Self {
+        self.max_payload = Some(max);
+        self
+    }
+
+    /// Load a policy from JSON, numbers can be given as `"0x.."` strings
+    pub fn from_json(json: &str) -> Result<Self, PolicyParseError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Load a policy from TOML, with the same fields as [Policy::from_json]
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, PolicyParseError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Check `command` against the policy
+    ///
+    /// The violation reported is the one of the rules matching the command the furthest
+    pub fn check<I: Deref<Target = [u8]>>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<(), PolicyViolation> {
+        let APDUCommand { cla, ins, p1, p2, .. } = *command;
+        let len = command.data.len();
+
+        if let Some(max) = self
+            .max_payload
+            .filter(|&max| len > max)
+        {
+            return Err(PolicyViolation::PayloadTooLarge { len, max });
+        }
+
+        let rules: Vec<_> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.cla == cla)
+            .collect();
+        if rules.is_empty() {
+            return Err(PolicyViolation::Cla { cla });
+        }
+
+        let rules: Vec<_> = rules
+            .into_iter()
+            .filter(|rule| rule.allows_ins(ins))
+            .collect();
+        if rules.is_empty() {
+            return Err(PolicyViolation::Ins { cla, ins });
+        }
+
+        let rules: Vec<_> = rules
+            .into_iter()
+            .filter(|rule| rule.allows_params(p1, p2))
+            .collect();
+        if rules.is_empty() {
+            return Err(PolicyViolation::Params { cla, ins, p1, p2 });
+        }
+
+        if rules.iter().any(|rule| {
+            rule.max_payload
+                .is_none_or(|max| len <= max)
+        }) {
+            return Ok(());
+        }
+        let max = rules
+            .iter()
+            .filter_map(|rule| rule.max_payload)
+            .max()
+            .unwrap_or_default();
+        Err(PolicyViolation::PayloadTooLarge { len, max })
+    }
+}
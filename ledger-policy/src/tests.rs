@@ -0,0 +1,193 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::sync::Mutex;
+
+use futures::executor::block_on;
+use ledger_audit::{verify, AuditLog, AuditRecord, Event, Payload, Redaction};
+use ledger_transport::{mock::MockTransport, APDUCommand, Exchange, TransportError, TransportErrorKind};
+
+use super::*;
+
+/// Records the denials
+#[derive(Default)]
+struct Denials(Mutex<Vec<(u8, PolicyViolation)>>);
+
+impl DenialSink for &'static Denials {
+    fn denied(
+        &self,
+        command: &APDUCommand<&[u8]>,
+        violation: &PolicyViolation,
+    ) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((command.ins, violation.clone()));
+    }
+}
+
+fn command(
+    cla: u8,
+    ins: u8,
+    p1: u8,
+    len: usize,
+) -> APDUCommand<Vec<u8>> {
+    APDUCommand { cla, ins, p1, p2: 0, data: vec![0; len] }
+}
+
+fn policy() -> Policy {
+    Policy::new()
+        .with_max_payload(255)
+        .allow(
+            Rule::new(0x55)
+                .with_ins(&[0x04])
+                .with_p1(&[0x00, 0x01]),
+        )
+        .allow(
+            Rule::new(0x55)
+                .with_ins(&[0x02])
+                .with_max_payload(200),
+        )
+        .allow(Rule::new(0xb0).with_ins(&[0x01]))
+}
+
+#[test]
+fn policy_check() {
+    let policy = policy();
+
+    assert_eq!(policy.check(&command(0x55, 0x04, 0x01, 20)), Ok(()));
+    assert_eq!(policy.check(&command(0x55, 0x02, 0x02, 200)), Ok(()));
+    assert_eq!(policy.check(&command(0xb0, 0x01, 0x00, 0)), Ok(()));
+
+    assert_eq!(policy.check(&command(0xe0, 0xd8, 0x00, 6)), Err(PolicyViolation::Cla { cla: 0xe0 }));
+    assert_eq!(policy.check(&command(0x55, 0x00, 0x00, 0)), Err(PolicyViolation::Ins { cla: 0x55, ins: 0x00 }));
+    assert_eq!(
+        policy.check(&command(0x55, 0x04, 0x02, 0)),
+        Err(PolicyViolation::Params { cla: 0x55, ins: 0x04, p1: 0x02, p2: 0x00 })
+    );
+    assert_eq!(
+        policy.check(&command(0x55, 0x02, 0x00, 201)),
+        Err(PolicyViolation::PayloadTooLarge { len: 201, max: 200 })
+    );
+    assert_eq!(
+        policy.check(&command(0x55, 0x04, 0x00, 256)),
+        Err(PolicyViolation::PayloadTooLarge { len: 256, max: 255 })
+    );
+    assert_eq!(Policy::new().check(&command(0x55, 0x04, 0x00, 0)), Err(PolicyViolation::Cla { cla: 0x55 }));
+}
+
+#[test]
+fn policy_from_json() {
+    let json = r#"{
+        "maxPayload": 255,
+        "rules": [
+            { "name": "get address", "cla": "0x55", "ins": ["0x04"], "p1": [0, 1] },
+            { "name": "sign", "cla": 85, "ins": [2], "maxPayload": 200 },
+            { "cla": "0xb0", "ins": ["0x01"] }
+        ]
+    }"#;
+    let parsed = Policy::from_json(json).unwrap();
+    assert_eq!(parsed.rules[0].name.as_deref(), Some("get address"));
+
+    let unnamed: Vec<_> = parsed
+        .rules
+        .into_iter()
+        .map(|rule| Rule { name: None, ..rule })
+        .collect();
+    assert_eq!(Policy { rules: unnamed, ..policy() }, policy());
+
+    assert!(Policy::from_json(r#"{ "rules": [{ "cla": "0x155" }] }"#).is_err());
+    assert!(Policy::from_json(r#"{ "rules": [{ "cla": 85, "instructions": [2] }] }"#).is_err());
+}
+
+#[test]
+#[cfg(feature = "toml")]
+fn policy_from_toml() {
+    let toml = r#"
+        maxPayload = 255
+
+        [[rules]]
+        cla = "0x55"
+        ins = ["0x04"]
+        p1 = [0, 1]
+
+        [[rules]]
+        cla = 85
+        ins = [2]
+        maxPayload = 200
+
+        [[rules]]
+        cla = "0xb0"
+        ins = ["0x01"]
+    "#;
+    assert_eq!(Policy::from_toml(toml).unwrap(), policy());
+    assert!(matches!(Policy::from_toml("[[rules]]\ncla = 256"), Err(PolicyParseError::Toml(_))));
+}
+
+#[test]
+fn policy_transport_denies() {
+    static DENIALS: Denials = Denials(Mutex::new(Vec::new()));
+
+    let transport = PolicyTransport::new(MockTransport::ok(1), policy()).with_denial_sink(&DENIALS);
+
+    block_on(async {
+        transport
+            .exchange(&command(0x55, 0x04, 0x00, 20))
+            .await
+            .unwrap();
+        let err = transport
+            .exchange(&command(0x55, 0x03, 0x00, 20))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PolicyError::Denied(PolicyViolation::Ins { ins: 0x03, .. })));
+        assert_eq!(err.kind(), TransportErrorKind::Denied);
+    });
+
+    assert_eq!(transport.into_inner().sent().len(), 1);
+    assert_eq!(*DENIALS.0.lock().unwrap(), vec![(0x03, PolicyViolation::Ins { cla: 0x55, ins: 0x03 })]);
+}
+
+#[test]
+fn policy_audit_log() {
+    let path = std::env::temp_dir().join(format!("ledger-policy-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let transport = PolicyTransport::new(MockTransport::ok(1), policy())
+        .with_audit_log(AuditLog::open(&path).unwrap(), Redaction::Only(vec![(0xe0, 0xd8)]));
+    block_on(async {
+        transport
+            .exchange(&command(0x55, 0x04, 0x00, 2))
+            .await
+            .unwrap();
+        transport
+            .exchange(&command(0xe0, 0xd8, 0x00, 2))
+            .await
+            .unwrap_err();
+    });
+    drop(transport);
+
+    let log = std::fs::read(&path).unwrap();
+    assert_eq!(verify(log.as_slice()).unwrap().records, 1);
+    let record: AuditRecord = serde_json::from_slice(&log).unwrap();
+    assert_eq!(record.event, Event::Denied {
+        cla: 0xe0,
+        ins: 0xd8,
+        p1: 0,
+        p2: 0,
+        payload: Payload::new(&[0, 0], true),
+        reason: "CLA 0xe0 is not allowed".to_string(),
+    });
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,210 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! [Exchange] wrapper enforcing a [Policy]
+
+use std::{io::Write, ops::Deref, sync::Mutex};
+
+use ledger_audit::{AuditLog, Event, Payload, Redaction};
+use ledger_transport::{async_trait, APDUAnswer, APDUCommand, Exchange, SessionGuard, SessionLock};
+use log::warn;
+
+use crate::{Policy, PolicyError, PolicyViolation};
+
+/// Told about every command refused by a [PolicyTransport]
+pub trait DenialSink: Send + Sync {
+    /// `command` was refused because of `violation`
+    fn denied(
+        &self,
+        command: &APDUCommand<&[u8]>,
+        violation: &PolicyViolation,
+    );
+}
+
+/// Records denials in a tamper-evident [AuditLog], as [Event::Denied]
+pub struct AuditDenials<W> {
+    log: Mutex<AuditLog<W>>,
+    redaction: Redaction,
+}
+
+impl<W> AuditDenials<W> {
+    /// Record denials in `log`, with the payloads selected by `redaction` redacted
+    pub fn new(
+        log: AuditLog<W>,
+        redaction: Redaction,
+    ) -> Self {
+        Self { log: Mutex::new(log), redaction }
+    }
+}
+
+impl<W: Write + Send> DenialSink for AuditDenials<W> {
+    fn denied(
+        &self,
+        command: &APDUCommand<&[u8]>,
+        violation: &PolicyViolation,
+    ) {
+        let redact = self
+            .redaction
+            .applies(command.cla, command.ins);
+        let event = Event::Denied {
+            cla: command.cla,
+            ins: command.ins,
+            p1: command.p1,
+            p2: command.p2,
+            payload: Payload::new(command.data, redact),
+            reason: violation.to_string(),
+        };
+
+        let mut log = self
+            .log
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(err) = log.append(None, None, event) {
+            warn!("couldn't record denial in the audit log: {}", err);
+        }
+    }
+}
+
+/// Transport refusing the commands not allowed by a [Policy] before they reach the wrapped transport
+///
+/// Denials are logged with [log::warn] and passed to the [DenialSink]s, e.g. an [AuditLog]
+///
+/// ```ignore
+/// let policy = Policy::from_json(&std::fs::read_to_string("policy.json")?)?;
+/// let transport = PolicyTransport::new(TransportNativeHID::new(&api)?, policy)
+///     .with_audit_log(AuditLog::open("/var/log/ledger-denials.jsonl")?, Redaction::All);
+/// ```
+pub struct PolicyTransport<E> {
+    inner: E,
+    policy: Policy,
+    sinks: Vec<Box<dyn DenialSink>>,
+}
+
+impl<E> PolicyTransport<E> {
+    /// Enforce `policy` on the commands sent to `inner`
+    pub fn new(
+        inner: E,
+        policy: Policy,
+    ) -> Self {
+        Self { inner, policy, sinks: Vec::new() }
+    }
+
+    /// Record denials in `log`, see [AuditDenials]
+    pub fn with_audit_log<W: Write + Send + 'static>(
+        self,
+        log: AuditLog<W>,
+        redaction: Redaction,
+    ) -> Self {
+        self.with_denial_sink(AuditDenials::new(log, redaction))
+    }
+
+    /// Pass denials to `sink`
+    pub fn with_denial_sink(
+        mut self,
+        sink: impl DenialSink + 'static,
+    ) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// The enforced policy
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Stop enforcing the policy, returning the wrapped transport
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    fn check<I, T>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<(), PolicyError<T>>
+    where
+        I: Deref<Target = [u8]>,
+    {
+        self.policy
+            .check(command)
+            .map_err(|violation| {
+                warn!("denied {}: {}", hex_header(command), violation);
+                let command = APDUCommand {
+                    cla: command.cla,
+                    ins: command.ins,
+                    p1: command.p1,
+                    p2: command.p2,
+                    data: &*command.data,
+                };
+                for sink in &self.sinks {
+                    sink.denied(&command, &violation);
+                }
+                PolicyError::Denied(violation)
+            })
+    }
+}
+
+/// CLA, INS, P1 and P2, for the logs
+fn hex_header<I>(command: &APDUCommand<I>) -> String {
+    format!("{:02x}{:02x}{:02x}{:02x}", command.cla, command.ins, command.p1, command.p2)
+}
+
+#[async_trait]
+impl<E> Exchange for PolicyTransport<E>
+where
+    E: Exchange + Send + Sync,
+    E::Error: Send,
+{
+    type Error = PolicyError<E::Error>;
+    type AnswerType = E::AnswerType;
+
+    async fn exchange<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        self.check(command)?;
+        self.inner
+            .exchange(command)
+            .await
+            .map_err(PolicyError::Transport)
+    }
+
+    async fn exchange_in_session<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        self.check(command)?;
+        self.inner
+            .exchange_in_session(command)
+            .await
+            .map_err(PolicyError::Transport)
+    }
+
+    fn session_lock(&self) -> Option<&SessionLock> {
+        self.inner.session_lock()
+    }
+
+    async fn session_guard(&self) -> Result<Option<SessionGuard<'_>>, Self::Error> {
+        self.inner
+            .session_guard()
+            .await
+            .map_err(PolicyError::Transport)
+    }
+}
@@ -0,0 +1,147 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Capture of the HID frames exchanged with a device
+//!
+//! [PcapWriter] writes them as a pcap file with Linux usbmon headers, which Wireshark can open
+
+use std::{
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Direction of a HID frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Host to device
+    Out,
+    /// Device to host
+    In,
+}
+
+/// Receives every HID frame sent and received by a [TransportNativeHID](crate::TransportNativeHID)
+///
+/// Frames are 64 bytes, without the report ID prepended on writes
+pub trait FrameSink: Send {
+    /// Record a frame
+    fn frame(
+        &mut self,
+        direction: Direction,
+        frame: &[u8],
+    ) -> io::Result<()>;
+}
+
+/// pcap magic, microsecond timestamps
+pub(crate) const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+/// pcap magic, nanosecond timestamps
+pub(crate) const PCAP_MAGIC_NS: u32 = 0xa1b2_3c4d;
+/// Linux usbmon, 48 bytes header
+pub(crate) const LINKTYPE_USB_LINUX: u32 = 189;
+/// Linux usbmon, 64 bytes header
+pub(crate) const LINKTYPE_USB_LINUX_MMAPPED: u32 = 220;
+/// usbmon transfer type of interrupt transfers
+pub(crate) const URB_INTERRUPT: u8 = 1;
+/// Interrupt IN endpoint of Ledger devices
+const ENDPOINT_IN: u8 = 0x81;
+/// Interrupt OUT endpoint of Ledger devices
+const ENDPOINT_OUT: u8 = 0x02;
+const SNAPLEN: u32 = 0xffff;
+
+/// Writes frames as a pcap capture with [LINKTYPE_USB_LINUX_MMAPPED] headers
+pub struct PcapWriter<W> {
+    writer: W,
+    bus: u16,
+    device: u8,
+    next_id: u64,
+}
+
+impl<W: Write + Send> PcapWriter<W> {
+    /// Start a capture, writing the pcap header
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&PCAP_MAGIC.to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&4u16.to_le_bytes())?;
+        // timezone offset and timestamp accuracy
+        writer.write_all(&[0; 8])?;
+        writer.write_all(&SNAPLEN.to_le_bytes())?;
+        writer.write_all(&LINKTYPE_USB_LINUX_MMAPPED.to_le_bytes())?;
+
+        Ok(Self { writer, bus: 1, device: 1, next_id: 1 })
+    }
+
+    /// USB bus and device numbers to write in the usbmon headers, 1 and 1 by default
+    pub fn with_address(
+        mut self,
+        bus: u16,
+        device: u8,
+    ) -> Self {
+        self.bus = bus;
+        self.device = device;
+        self
+    }
+
+    /// Flush and return the underlying writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write + Send> FrameSink for PcapWriter<W> {
+    fn frame(
+        &mut self,
+        direction: Direction,
+        frame: &[u8],
+    ) -> io::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let (secs, usecs) = (now.as_secs(), now.subsec_micros());
+        // OUT data travels with the submission, IN data with the completion
+        let (event, endpoint) = match direction {
+            Direction::Out => (b'S', ENDPOINT_OUT),
+            Direction::In => (b'C', ENDPOINT_IN),
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut header = Vec::with_capacity(64);
+        header.extend_from_slice(&id.to_le_bytes());
+        header.extend_from_slice(&[event, URB_INTERRUPT, endpoint, self.device]);
+        header.extend_from_slice(&self.bus.to_le_bytes());
+        // no setup packet, data present
+        header.extend_from_slice(&[b'-', 0]);
+        header.extend_from_slice(&(secs as i64).to_le_bytes());
+        header.extend_from_slice(&(usecs as i32).to_le_bytes());
+        // status
+        header.extend_from_slice(&0i32.to_le_bytes());
+        header.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        // setup, interval, start frame, transfer flags and iso descriptors count
+        header.extend_from_slice(&[0; 24]);
+
+        let len = (header.len() + frame.len()) as u32;
+        self.writer
+            .write_all(&(secs as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&usecs.to_le_bytes())?;
+        self.writer
+            .write_all(&len.to_le_bytes())?;
+        self.writer
+            .write_all(&len.to_le_bytes())?;
+        self.writer.write_all(&header)?;
+        self.writer.write_all(frame)
+    }
+}
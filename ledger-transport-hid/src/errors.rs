@@ -13,23 +13,159 @@
 *  See the License for the specific language governing permissions and
 *  limitations under the License.
 ********************************************************************************/
+use ledger_transport::{TransportError, TransportErrorKind};
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
 pub enum LedgerHIDError {
     /// Device not found error
     #[error("Ledger device not found")]
     DeviceNotFound,
-    /// Communication error
-    #[error("Ledger device: communication error `{0}`")]
-    Comm(&'static str),
+    /// The device didn't answer in time
+    #[error("Ledger device: read timed out")]
+    Timeout,
+    /// Only part of a packet was written
+    #[error("Ledger device: USB write error, sent {sent} of {expected} bytes")]
+    IncompleteWrite {
+        /// Packet size
+        expected: usize,
+        /// Bytes written
+        sent: usize,
+    },
+    /// A packet was too short to hold its header
+    #[error("Ledger device: incomplete header, read {got} of {expected} bytes")]
+    IncompleteHeader {
+        /// Header size
+        expected: usize,
+        /// Bytes read
+        got: usize,
+    },
+    /// A packet was received on another channel
+    #[error("Ledger device: invalid channel 0x{got:04X}, expected 0x{expected:04X}")]
+    InvalidChannel {
+        /// Channel of the exchange
+        expected: u16,
+        /// Channel of the packet
+        got: u16,
+    },
+    /// A packet had an unexpected tag
+    #[error("Ledger device: invalid tag 0x{got:02X}, expected 0x{expected:02X}")]
+    InvalidTag {
+        /// APDU tag
+        expected: u8,
+        /// Tag of the packet
+        got: u8,
+    },
+    /// A packet was received out of sequence
+    #[error("Ledger device: invalid sequence index {got}, expected {expected}")]
+    InvalidSequence {
+        /// Next sequence index
+        expected: u16,
+        /// Sequence index of the packet
+        got: u16,
+    },
+    /// The answer is too short to hold a status word
+    #[error("Ledger device: response was too short, {0} bytes")]
+    ResponseTooShort(usize),
     /// i/o error
-    #[error("Ledger device: i/o error")]
-    Io(#[from] std::io::Error),
+    #[error("Ledger device: i/o error `{message}`")]
+    Io {
+        /// Category of the error
+        kind: TransportErrorKind,
+        /// Description of the underlying error
+        message: String,
+    },
     /// HID error
-    #[error("Ledger device: Io error")]
-    Hid(#[from] hidapi::HidError),
+    #[error("Ledger device: HID error `{message}`")]
+    Hid {
+        /// Category of the error
+        kind: TransportErrorKind,
+        /// Description of the underlying error
+        message: String,
+    },
     /// UT8F error
     #[error("Ledger device: UTF8 error")]
     UTF8(#[from] std::str::Utf8Error),
 }
+
+/// Category of an i/o error, from its errno when [std::io::ErrorKind] doesn't tell
+///
+/// An unplugged hidraw device fails with ENODEV, which the standard library doesn't categorize
+fn io_error_kind(err: &std::io::Error) -> TransportErrorKind {
+    #[cfg(unix)]
+    match err.raw_os_error() {
+        Some(libc::ENODEV | libc::ENXIO | libc::ESHUTDOWN) => return TransportErrorKind::Disconnected,
+        Some(libc::EBUSY) => return TransportErrorKind::Busy,
+        _ => {},
+    }
+    TransportError::kind(err)
+}
+
+impl From<std::io::Error> for LedgerHIDError {
+    fn from(err: std::io::Error) -> Self {
+        LedgerHIDError::Io { kind: io_error_kind(&err), message: err.to_string() }
+    }
+}
+
+impl From<hidapi::HidError> for LedgerHIDError {
+    fn from(err: hidapi::HidError) -> Self {
+        let kind = match &err {
+            hidapi::HidError::IoError { error } => io_error_kind(error),
+            hidapi::HidError::IncompleteSendError { sent, all } => {
+                TransportErrorKind::Framing { expected: *all, got: *sent }
+            },
+            // hidapi reports errno strings, e.g. when the device is unplugged mid-exchange
+            hidapi::HidError::HidApiError { message }
+                if message.contains("No such device") || message.contains("disconnected") =>
+            {
+                TransportErrorKind::Disconnected
+            },
+            hidapi::HidError::HidApiError { message } if message.contains("busy") => TransportErrorKind::Busy,
+            _ => TransportErrorKind::Io,
+        };
+
+        LedgerHIDError::Hid { kind, message: err.to_string() }
+    }
+}
+
+impl TransportError for LedgerHIDError {
+    fn kind(&self) -> TransportErrorKind {
+        match self {
+            LedgerHIDError::DeviceNotFound => TransportErrorKind::DeviceNotFound,
+            LedgerHIDError::Timeout => TransportErrorKind::Timeout,
+            LedgerHIDError::IncompleteWrite { expected, sent: got }
+            | LedgerHIDError::IncompleteHeader { expected, got } => {
+                TransportErrorKind::Framing { expected: *expected, got: *got }
+            },
+            LedgerHIDError::InvalidChannel { expected, got } | LedgerHIDError::InvalidSequence { expected, got } => {
+                TransportErrorKind::Framing { expected: *expected as usize, got: *got as usize }
+            },
+            LedgerHIDError::InvalidTag { expected, got } => {
+                TransportErrorKind::Framing { expected: *expected as usize, got: *got as usize }
+            },
+            LedgerHIDError::ResponseTooShort(got) => TransportErrorKind::Framing { expected: 2, got: *got },
+            LedgerHIDError::Io { kind, .. } | LedgerHIDError::Hid { kind, .. } => *kind,
+            LedgerHIDError::UTF8(_) => TransportErrorKind::Io,
+        }
+    }
+}
+
+/// Error reading a capture, see [import](crate::import)
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub enum CaptureError {
+    /// Neither a pcap nor a pcapng capture
+    #[error("not a pcap or pcapng capture")]
+    NotACapture,
+    /// The capture ends in the middle of a record
+    #[error("truncated capture")]
+    Truncated,
+    /// The capture doesn't hold usbmon packets
+    #[error("unsupported link type {0}, expected usbmon (189 or 220)")]
+    UnsupportedLinkType(u32),
+    /// A packet refers to an interface that wasn't described
+    #[error("packet on undescribed interface {0}")]
+    UnknownInterface(usize),
+    /// A packet timestamp doesn't fit a [Duration](std::time::Duration), e.g. a negative one
+    #[error("packet timestamp out of range")]
+    InvalidTimestamp,
+}
@@ -0,0 +1,370 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Import of pcap and pcapng captures with Linux usbmon headers
+//!
+//! Captures can come from [PcapWriter](crate::capture::PcapWriter) or from `usbmon` and Wireshark directly.
+//! [Transcript] reassembles the Ledger HID frames into commands and answers
+
+use std::{collections::HashMap, fmt, time::Duration};
+
+use crate::{
+    capture::{Direction, LINKTYPE_USB_LINUX, LINKTYPE_USB_LINUX_MMAPPED, PCAP_MAGIC, PCAP_MAGIC_NS, URB_INTERRUPT},
+    errors::CaptureError,
+};
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const LEDGER_TAG_APDU: u8 = 0x05;
+
+/// A USB frame read from a capture
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedFrame {
+    /// Time of the frame, since the unix epoch
+    pub timestamp: Duration,
+    /// USB bus number
+    pub bus: u16,
+    /// USB device number
+    pub device: u8,
+    /// Direction of the transfer
+    pub direction: Direction,
+    /// Frame data
+    pub data: Vec<u8>,
+}
+
+/// Read the interrupt transfers carrying data in a pcap or pcapng capture
+///
+/// Packets of other link types than usbmon are skipped
+pub fn read_frames(capture: &[u8]) -> Result<Vec<CapturedFrame>, CaptureError> {
+    let magic = capture
+        .get(.. 4)
+        .ok_or(CaptureError::Truncated)?;
+    if u32::from_le_bytes(magic.try_into().unwrap_or_default()) == PCAPNG_SECTION_HEADER {
+        return read_pcapng(capture);
+    }
+    read_pcap(capture)
+}
+
+/// Fields read with the byte order of the capture
+#[derive(Clone, Copy)]
+struct Bytes<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Bytes<'a> {
+    fn slice(
+        &self,
+        at: usize,
+        len: usize,
+    ) -> Result<&'a [u8], CaptureError> {
+        self.data
+            .get(at .. at + len)
+            .ok_or(CaptureError::Truncated)
+    }
+
+    fn array<const N: usize>(
+        &self,
+        at: usize,
+    ) -> Result<[u8; N], CaptureError> {
+        Ok(self
+            .slice(at, N)?
+            .try_into()
+            .unwrap_or([0; N]))
+    }
+
+    fn u16(
+        &self,
+        at: usize,
+    ) -> Result<u16, CaptureError> {
+        let bytes = self.array(at)?;
+        Ok(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(
+        &self,
+        at: usize,
+    ) -> Result<u32, CaptureError> {
+        let bytes = self.array(at)?;
+        Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn u64(
+        &self,
+        at: usize,
+    ) -> Result<u64, CaptureError> {
+        let bytes = self.array(at)?;
+        Ok(if self.big_endian { u64::from_be_bytes(bytes) } else { u64::from_le_bytes(bytes) })
+    }
+}
+
+fn read_pcap(capture: &[u8]) -> Result<Vec<CapturedFrame>, CaptureError> {
+    let le = Bytes { data: capture, big_endian: false };
+    // timestamps are taken from the usbmon headers, the record timestamp resolution doesn't matter
+    let big_endian = match le.u32(0)? {
+        PCAP_MAGIC | PCAP_MAGIC_NS => false,
+        magic if magic.swap_bytes() == PCAP_MAGIC || magic.swap_bytes() == PCAP_MAGIC_NS => true,
+        _ => return Err(CaptureError::NotACapture),
+    };
+    let file = Bytes { data: capture, big_endian };
+    let link_type = file.u32(20)?;
+    if link_type != LINKTYPE_USB_LINUX && link_type != LINKTYPE_USB_LINUX_MMAPPED {
+        return Err(CaptureError::UnsupportedLinkType(link_type));
+    }
+
+    let mut frames = Vec::new();
+    let mut at = 24;
+    while at < capture.len() {
+        let len = file.u32(at + 8)? as usize;
+        let packet = file.slice(at + 16, len)?;
+        at += 16 + len;
+
+        if let Some(frame) = usbmon_frame(Bytes { data: packet, big_endian }, link_type)? {
+            frames.push(frame);
+        }
+    }
+
+    Ok(frames)
+}
+
+fn read_pcapng(capture: &[u8]) -> Result<Vec<CapturedFrame>, CaptureError> {
+    let mut frames = Vec::new();
+    let mut big_endian = false;
+    let mut link_types = Vec::new();
+    let mut at = 0;
+
+    while at < capture.len() {
+        let le = Bytes { data: capture, big_endian: false };
+        if le.u32(at)? == PCAPNG_SECTION_HEADER {
+            big_endian = match le.u32(at + 8)? {
+                PCAPNG_BYTE_ORDER_MAGIC => false,
+                magic if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+                _ => return Err(CaptureError::NotACapture),
+            };
+            link_types.clear();
+        }
+
+        let file = Bytes { data: capture, big_endian };
+        let block_type = file.u32(at)?;
+        let block_len = file.u32(at + 4)? as usize;
+        if block_len < 12 {
+            return Err(CaptureError::Truncated);
+        }
+        let block = Bytes { data: file.slice(at + 8, block_len - 12)?, big_endian };
+        at += block_len;
+
+        let (interface, packet) = match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION => {
+                link_types.push(block.u16(0)? as u32);
+                continue;
+            },
+            PCAPNG_ENHANCED_PACKET => (block.u32(0)? as usize, block.slice(20, block.u32(12)? as usize)?),
+            PCAPNG_SIMPLE_PACKET => {
+                let len = (block.u32(0)? as usize).min(block.data.len() - 4);
+                (0, block.slice(4, len)?)
+            },
+            _ => continue,
+        };
+
+        let Some(&link_type) = link_types.get(interface) else {
+            return Err(CaptureError::UnknownInterface(interface));
+        };
+        if let Some(frame) = usbmon_frame(Bytes { data: packet, big_endian }, link_type)? {
+            frames.push(frame);
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Frame of a usbmon packet, if it's an interrupt transfer carrying data
+fn usbmon_frame(
+    packet: Bytes<'_>,
+    link_type: u32,
+) -> Result<Option<CapturedFrame>, CaptureError> {
+    let header_len = match link_type {
+        LINKTYPE_USB_LINUX => 48,
+        LINKTYPE_USB_LINUX_MMAPPED => 64,
+        _ => return Ok(None),
+    };
+
+    let [event, transfer_type, endpoint, device] = packet.array(8)?;
+    let bus = packet.u16(12)?;
+    let secs = packet.u64(16)?;
+    let usecs = packet.u32(24)?;
+    let captured = packet.u32(36)? as usize;
+
+    let direction = if endpoint & 0x80 != 0 { Direction::In } else { Direction::Out };
+    // OUT data travels with the submission, IN data with the completion
+    let carries_data = matches!((event, direction), (b'S', Direction::Out) | (b'C', Direction::In));
+    if transfer_type != URB_INTERRUPT || !carries_data || captured == 0 {
+        return Ok(None);
+    }
+
+    let available = packet
+        .data
+        .len()
+        .saturating_sub(header_len);
+    let data = packet
+        .slice(header_len, captured.min(available))?
+        .to_vec();
+
+    Ok(Some(CapturedFrame {
+        timestamp: Duration::from_secs(secs)
+            .checked_add(Duration::from_micros(usecs as u64))
+            .ok_or(CaptureError::InvalidTimestamp)?,
+        bus,
+        device,
+        direction,
+        data,
+    }))
+}
+
+/// A command and its answer, both without the HID framing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedExchange {
+    /// Serialized APDU command
+    pub command: Vec<u8>,
+    /// Answer, status word included, `None` if the capture ends before it
+    pub answer: Option<Vec<u8>>,
+}
+
+/// Commands and answers reassembled from HID frames
+///
+/// Displays as a `=>` / `<=` transcript that `ledger-apdu-script` can replay
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transcript {
+    /// Exchanges, in capture order
+    pub exchanges: Vec<CapturedExchange>,
+}
+
+/// APDU being reassembled from the frames of one direction
+#[derive(Default)]
+struct Partial {
+    expected: usize,
+    next_sequence: u16,
+    data: Vec<u8>,
+}
+
+impl Partial {
+    /// Add a frame, returning the APDU once complete
+    fn push(
+        &mut self,
+        frame: &[u8],
+    ) -> Option<Vec<u8>> {
+        let sequence = u16::from_be_bytes([*frame.get(3)?, *frame.get(4)?]);
+        let payload = if sequence == 0 {
+            self.expected = u16::from_be_bytes([*frame.get(5)?, *frame.get(6)?]) as usize;
+            self.data.clear();
+            frame.get(7 ..)?
+        } else if sequence == self.next_sequence {
+            frame.get(5 ..)?
+        } else {
+            // lost frame, wait for the next APDU
+            self.next_sequence = 0;
+            return None;
+        };
+        self.next_sequence = sequence + 1;
+
+        let missing = self.expected - self.data.len();
+        self.data
+            .extend_from_slice(&payload[.. missing.min(payload.len())]);
+        if self.data.len() < self.expected {
+            return None;
+        }
+
+        self.next_sequence = 0;
+        Some(std::mem::take(&mut self.data))
+    }
+}
+
+/// Exchanges in progress on one HID channel of a device
+#[derive(Default)]
+struct Stream {
+    out: Partial,
+    incoming: Partial,
+    /// Index of the last command of the channel in the transcript, while it has no answer
+    pending: Option<usize>,
+}
+
+impl Transcript {
+    /// Reassemble the Ledger APDUs carried by `frames`, other HID traffic is skipped
+    ///
+    /// Frames are grouped by bus, device and HID channel, so the exchanges of several devices can be interleaved
+    pub fn from_frames<'a>(frames: impl IntoIterator<Item = &'a CapturedFrame>) -> Self {
+        let mut transcript = Transcript::default();
+        let mut streams: HashMap<(u16, u8, u16), Stream> = HashMap::new();
+
+        for frame in frames {
+            // writes may keep the report ID
+            let data = match &frame.data[..] {
+                [0, rest @ ..] if rest.len() == 64 => rest,
+                data => data,
+            };
+            let [channel_hi, channel_lo, LEDGER_TAG_APDU, ..] = *data else {
+                continue;
+            };
+            let stream = streams
+                .entry((frame.bus, frame.device, u16::from_be_bytes([channel_hi, channel_lo])))
+                .or_default();
+
+            match frame.direction {
+                Direction::Out => {
+                    if let Some(command) = stream.out.push(data) {
+                        stream.pending = Some(transcript.exchanges.len());
+                        transcript
+                            .exchanges
+                            .push(CapturedExchange { command, answer: None });
+                    }
+                },
+                Direction::In => {
+                    let Some(answer) = stream.incoming.push(data) else {
+                        continue;
+                    };
+                    match stream.pending.take() {
+                        Some(index) => transcript.exchanges[index].answer = Some(answer),
+                        None => log::warn!("answer without a command: {}", hex::encode(answer)),
+                    }
+                },
+            }
+        }
+
+        transcript
+    }
+
+    /// Read a pcap or pcapng capture and reassemble its APDUs
+    pub fn from_capture(capture: &[u8]) -> Result<Self, CaptureError> {
+        Ok(Self::from_frames(&read_frames(capture)?))
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        for exchange in &self.exchanges {
+            writeln!(f, "=> {}", hex::encode(&exchange.command))?;
+            match &exchange.answer {
+                Some(answer) => writeln!(f, "<= {}", hex::encode(answer))?,
+                None => writeln!(f, "# no answer captured")?,
+            }
+        }
+        Ok(())
+    }
+}
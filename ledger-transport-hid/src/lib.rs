@@ -13,15 +13,20 @@
 *  See the License for the specific language governing permissions and
 *  limitations under the License.
 ********************************************************************************/
+pub mod capture;
 mod errors;
+pub mod import;
+mod model;
 use std::{io::Cursor, ops::Deref, sync::Mutex};
 
 use byteorder::{BigEndian, ReadBytesExt};
-pub use errors::LedgerHIDError;
+use capture::{Direction, FrameSink};
+pub use errors::{CaptureError, LedgerHIDError};
 pub use hidapi;
 use hidapi::{DeviceInfo, HidApi, HidDevice};
-use ledger_transport::{async_trait, APDUAnswer, APDUCommand, Exchange};
-use log::info;
+use ledger_transport::{async_trait, APDUAnswer, APDUCommand, Exchange, SessionLock};
+use log::{info, warn};
+pub use model::LedgerModel;
 
 const LEDGER_VID: u16 = 0x2c97;
 const LEDGER_USAGE_PAGE: u16 = 0xFFA0;
@@ -34,6 +39,8 @@ const LEDGER_TIMEOUT: i32 = 10_000_000;
 
 pub struct TransportNativeHID {
     device: Mutex<HidDevice>,
+    session: SessionLock,
+    capture: Mutex<Option<Box<dyn FrameSink>>>,
 }
 
 impl TransportNativeHID {
@@ -41,6 +48,11 @@ impl TransportNativeHID {
         dev.vendor_id() == LEDGER_VID && dev.usage_page() == LEDGER_USAGE_PAGE
     }
 
+    /// Model of a ledger device
+    pub fn model(dev: &DeviceInfo) -> LedgerModel {
+        LedgerModel::from_product_id(dev.product_id())
+    }
+
     /// Get a list of ledger devices available
     pub fn list_ledgers(api: &HidApi) -> impl Iterator<Item = &DeviceInfo> {
         api.device_list()
@@ -74,12 +86,54 @@ impl TransportNativeHID {
         let device = device.open_device(api)?;
         let _ = device.set_blocking_mode(true);
 
-        let ledger = TransportNativeHID { device: Mutex::new(device) };
+        let ledger =
+            TransportNativeHID { device: Mutex::new(device), session: SessionLock::new(), capture: Mutex::new(None) };
 
         Ok(ledger)
     }
 
+    /// Record every HID frame sent and received from now on, e.g. with a [PcapWriter](capture::PcapWriter)
+    ///
+    /// Replaces the previous sink, if any
+    pub fn set_capture(
+        &self,
+        sink: impl FrameSink + 'static,
+    ) {
+        *self
+            .capture
+            .lock()
+            .expect("HID capture poisoned") = Some(Box::new(sink));
+    }
+
+    /// Stop recording frames, returning the sink
+    pub fn take_capture(&self) -> Option<Box<dyn FrameSink>> {
+        self.capture
+            .lock()
+            .expect("HID capture poisoned")
+            .take()
+    }
+
+    /// Record a frame with the capture sink, if any
+    ///
+    /// The sink is only locked for the frame, so it can be replaced while waiting for an answer
+    fn record(
+        &self,
+        direction: Direction,
+        frame: &[u8],
+    ) {
+        let mut capture = self
+            .capture
+            .lock()
+            .expect("HID capture poisoned");
+        if let Some(sink) = capture.as_mut() {
+            if let Err(err) = sink.frame(direction, frame) {
+                warn!("could not capture HID frame: {}", err);
+            }
+        }
+    }
+
     fn write_apdu(
+        &self,
         device: &HidDevice,
         channel: u16,
         apdu_command: &[u8],
@@ -106,22 +160,24 @@ impl TransportNativeHID {
             buffer[6 .. 6 + chunk.len()].copy_from_slice(chunk);
 
             info!("[{:3}] << {:}", buffer.len(), hex::encode(&buffer));
+            self.record(Direction::Out, &buffer[1 ..]);
 
             let result = device.write(&buffer);
 
             match result {
                 Ok(size) => {
                     if size < buffer.len() {
-                        return Err(LedgerHIDError::Comm("USB write error. Could not send whole message"));
+                        return Err(LedgerHIDError::IncompleteWrite { expected: buffer.len(), sent: size });
                     }
                 },
-                Err(x) => return Err(LedgerHIDError::Hid(x)),
+                Err(x) => return Err(x.into()),
             }
         }
         Ok(1)
     }
 
     fn read_apdu(
+        &self,
         device: &HidDevice,
         channel: u16,
         apdu_answer: &mut Vec<u8>,
@@ -132,9 +188,16 @@ impl TransportNativeHID {
 
         loop {
             let res = device.read_timeout(&mut buffer, LEDGER_TIMEOUT)?;
+            if res > 0 {
+                self.record(Direction::In, &buffer[.. res]);
+            }
 
-            if (sequence_idx == 0 && res < 7) || res < 5 {
-                return Err(LedgerHIDError::Comm("Read error. Incomplete header"));
+            if res == 0 {
+                return Err(LedgerHIDError::Timeout);
+            }
+            let header_len = if sequence_idx == 0 { 7 } else { 5 };
+            if res < header_len {
+                return Err(LedgerHIDError::IncompleteHeader { expected: header_len, got: res });
             }
 
             let mut rdr = Cursor::new(&buffer);
@@ -144,14 +207,14 @@ impl TransportNativeHID {
             let rcv_seq_idx = rdr.read_u16::<BigEndian>()?;
 
             if rcv_channel != channel {
-                return Err(LedgerHIDError::Comm("Invalid channel"));
+                return Err(LedgerHIDError::InvalidChannel { expected: channel, got: rcv_channel });
             }
             if rcv_tag != 0x05u8 {
-                return Err(LedgerHIDError::Comm("Invalid tag"));
+                return Err(LedgerHIDError::InvalidTag { expected: 0x05, got: rcv_tag });
             }
 
             if rcv_seq_idx != sequence_idx {
-                return Err(LedgerHIDError::Comm("Invalid sequence idx"));
+                return Err(LedgerHIDError::InvalidSequence { expected: sequence_idx, got: rcv_seq_idx });
             }
 
             if rcv_seq_idx == 0 {
@@ -176,6 +239,11 @@ impl TransportNativeHID {
         }
     }
 
+    /// Exchange a single APDU with the device
+    ///
+    /// # Note
+    /// This doesn't wait for a [Session](ledger_transport::Session) opened on this transport to be released,
+    /// use [Exchange::exchange] when sharing the transport between tasks
     pub fn exchange<I: Deref<Target = [u8]>>(
         &self,
         command: &APDUCommand<I>,
@@ -185,12 +253,13 @@ impl TransportNativeHID {
             .lock()
             .expect("HID device poisoned");
 
-        Self::write_apdu(&device, LEDGER_CHANNEL, &command.serialize())?;
+        self.write_apdu(&device, LEDGER_CHANNEL, &command.serialize())?;
 
         let mut answer: Vec<u8> = Vec::with_capacity(256);
-        Self::read_apdu(&device, LEDGER_CHANNEL, &mut answer)?;
+        self.read_apdu(&device, LEDGER_CHANNEL, &mut answer)?;
 
-        APDUAnswer::from_answer(answer).map_err(|_| LedgerHIDError::Comm("response was too short"))
+        let len = answer.len();
+        APDUAnswer::from_answer(answer).map_err(|_| LedgerHIDError::ResponseTooShort(len))
     }
 }
 
@@ -206,8 +275,179 @@ impl Exchange for TransportNativeHID {
     where
         I: Deref<Target = [u8]> + Send + Sync,
     {
+        let _session = self.session.lock().await;
         self.exchange(command)
     }
+
+    async fn exchange_in_session<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        self.exchange(command)
+    }
+
+    fn session_lock(&self) -> Option<&SessionLock> {
+        Some(&self.session)
+    }
+}
+
+#[cfg(test)]
+mod capture_tests {
+    use crate::{
+        capture::{Direction, FrameSink, PcapWriter},
+        import::{read_frames, CapturedExchange, CapturedFrame, Transcript},
+        CaptureError,
+    };
+
+    /// Split an APDU into 64 bytes HID frames
+    fn frames(apdu: &[u8]) -> Vec<Vec<u8>> {
+        let data = [&(apdu.len() as u16).to_be_bytes()[..], apdu].concat();
+        data.chunks(59)
+            .enumerate()
+            .map(|(seq, chunk)| {
+                let mut frame = vec![0x01, 0x01, 0x05];
+                frame.extend_from_slice(&(seq as u16).to_be_bytes());
+                frame.extend_from_slice(chunk);
+                frame.resize(64, 0);
+                frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pcap_round_trip() {
+        let command = [&[0x55, 0x02, 0x00, 0x00, 100][..], &[0xab; 100]].concat();
+        let answer = [0x01, 0x02, 0x90, 0x00];
+
+        let mut writer = PcapWriter::new(Vec::new()).expect("header written");
+        for frame in frames(&command) {
+            writer
+                .frame(Direction::Out, &frame)
+                .unwrap();
+        }
+        for frame in frames(&answer) {
+            writer
+                .frame(Direction::In, &frame)
+                .unwrap();
+        }
+        let capture = writer.into_inner().unwrap();
+
+        let frames = read_frames(&capture).expect("valid capture");
+        assert_eq!(frames.len(), 3);
+        assert_eq!((frames[0].bus, frames[0].device, frames[2].direction), (1, 1, Direction::In));
+
+        let transcript = Transcript::from_capture(&capture).expect("valid capture");
+        assert_eq!(transcript.exchanges, vec![CapturedExchange {
+            command: command.clone(),
+            answer: Some(answer.to_vec())
+        }]);
+        assert_eq!(transcript.to_string(), format!("=> {}\n<= 01029000\n", hex::encode(&command)));
+    }
+
+    /// usbmon packet with the 48 bytes header
+    fn usbmon(
+        event: u8,
+        endpoint: u8,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut packet = vec![0; 48];
+        packet[8 .. 12].copy_from_slice(&[event, 1, endpoint, 7]);
+        packet[12 .. 14].copy_from_slice(&3u16.to_le_bytes());
+        packet[36 .. 40].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    fn block(
+        block_type: u32,
+        body: &[u8],
+    ) -> Vec<u8> {
+        let mut body = body.to_vec();
+        body.resize(body.len().div_ceil(4) * 4, 0);
+        let len = (body.len() + 12) as u32;
+        [&block_type.to_le_bytes()[..], &len.to_le_bytes(), &body, &len.to_le_bytes()].concat()
+    }
+
+    fn enhanced_packet(packet: &[u8]) -> Vec<u8> {
+        let len = (packet.len() as u32).to_le_bytes();
+        let body = [&[0u8; 12][..], &len, &len, packet].concat();
+        block(6, &body)
+    }
+
+    #[test]
+    fn pcapng_usbmon_import() {
+        let command = [0xe0, 0x01, 0x00, 0x00, 0x00];
+        let answer = [0x69, 0x85];
+
+        let mut capture = block(0x0a0d_0d0a, &[&0x1a2b_3c4du32.to_le_bytes()[..], &[1, 0, 0, 0], &[0xff; 8]].concat());
+        capture.extend(block(1, &[189, 0, 0, 0, 0xff, 0xff, 0, 0]));
+        // submission of the IN transfer, without data
+        capture.extend(enhanced_packet(&usbmon(b'S', 0x81, &[])));
+        // another HID device on the bus
+        capture.extend(enhanced_packet(&usbmon(b'C', 0x83, &[0x42; 8])));
+        capture.extend(enhanced_packet(&usbmon(b'S', 0x02, &frames(&command)[0])));
+        capture.extend(enhanced_packet(&usbmon(b'C', 0x02, &[])));
+        capture.extend(enhanced_packet(&usbmon(b'C', 0x81, &frames(&answer)[0])));
+        // a command without answer at the end of the capture
+        capture.extend(enhanced_packet(&usbmon(b'S', 0x02, &frames(&command)[0])));
+
+        let frames = read_frames(&capture).expect("valid capture");
+        assert_eq!(frames.len(), 4);
+        assert_eq!((frames[0].bus, frames[0].device), (3, 7));
+
+        let transcript = Transcript::from_frames(&frames);
+        assert_eq!(transcript.to_string(), "=> e001000000\n<= 6985\n=> e001000000\n# no answer captured\n");
+    }
+
+    #[test]
+    fn interleaved_devices() {
+        let frame = |device, direction, data: &[u8]| CapturedFrame {
+            timestamp: Default::default(),
+            bus: 1,
+            device,
+            direction,
+            data: data.to_vec(),
+        };
+        let command = [&[0x55, 0x02, 0x00, 0x00, 100][..], &[0xab; 100]].concat();
+        let long = frames(&command);
+        let short = frames(&[0xe0, 0x01, 0x00, 0x00, 0x00]);
+
+        let transcript = Transcript::from_frames(&[
+            frame(1, Direction::Out, &long[0]),
+            frame(2, Direction::Out, &short[0]),
+            frame(1, Direction::Out, &long[1]),
+            frame(2, Direction::In, &frames(&[0x69, 0x85])[0]),
+            frame(1, Direction::In, &frames(&[0x90, 0x00])[0]),
+        ]);
+        assert_eq!(transcript.exchanges, vec![
+            CapturedExchange { command: vec![0xe0, 0x01, 0x00, 0x00, 0x00], answer: Some(vec![0x69, 0x85]) },
+            CapturedExchange { command, answer: Some(vec![0x90, 0x00]) },
+        ]);
+    }
+
+    #[test]
+    fn invalid_captures() {
+        assert_eq!(read_frames(&[1, 2, 3, 4, 5, 6]), Err(CaptureError::NotACapture));
+        assert_eq!(read_frames(&[0xd4, 0xc3]), Err(CaptureError::Truncated));
+
+        let mut capture = PcapWriter::new(Vec::new())
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        capture[20] = 1;
+        assert_eq!(read_frames(&capture), Err(CaptureError::UnsupportedLinkType(1)));
+
+        // a negative usbmon timestamp, with the largest microseconds
+        let mut packet = usbmon(b'S', 0x02, &[0; 64]);
+        packet[16 .. 28].copy_from_slice(&[0xff; 12]);
+        let mut capture = block(0x0a0d_0d0a, &[&0x1a2b_3c4du32.to_le_bytes()[..], &[1, 0, 0, 0], &[0xff; 8]].concat());
+        capture.extend(block(1, &[189, 0, 0, 0, 0xff, 0xff, 0, 0]));
+        capture.extend(enhanced_packet(&packet));
+        assert_eq!(read_frames(&capture), Err(CaptureError::InvalidTimestamp));
+    }
 }
 
 #[cfg(test)]
@@ -283,6 +523,34 @@ mod integration_tests {
         assert_eq!(serialized_command, expected)
     }
 
+    #[test]
+    fn model_from_product_id() {
+        use crate::LedgerModel;
+
+        assert_eq!(LedgerModel::from_product_id(0x1011), LedgerModel::NanoS);
+        assert_eq!(LedgerModel::from_product_id(0x4015), LedgerModel::NanoX);
+        assert_eq!(LedgerModel::from_product_id(0x0005), LedgerModel::NanoSPlus);
+        assert_eq!(LedgerModel::from_product_id(0x6011), LedgerModel::Stax);
+        assert_eq!(LedgerModel::from_product_id(0x9001), LedgerModel::Unknown(0x9001));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn io_error_kinds() {
+        use ledger_transport::{TransportError, TransportErrorKind};
+
+        use crate::LedgerHIDError;
+
+        let kind = |errno| LedgerHIDError::from(std::io::Error::from_raw_os_error(errno)).kind();
+        assert_eq!(kind(libc::ENODEV), TransportErrorKind::Disconnected);
+        assert_eq!(kind(libc::EBUSY), TransportErrorKind::Busy);
+        assert_eq!(kind(libc::ETIMEDOUT), TransportErrorKind::Timeout);
+        assert_eq!(kind(libc::EINVAL), TransportErrorKind::Io);
+
+        let err = hidapi::HidError::IoError { error: std::io::Error::from_raw_os_error(libc::ENODEV) };
+        assert_eq!(LedgerHIDError::from(err).kind(), TransportErrorKind::Disconnected);
+    }
+
     #[test]
     #[serial]
     fn exchange() {
@@ -0,0 +1,67 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::fmt;
+
+/// Ledger device model
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LedgerModel {
+    /// Nano S
+    NanoS,
+    /// Nano X
+    NanoX,
+    /// Nano S Plus
+    NanoSPlus,
+    /// Stax
+    Stax,
+    /// Flex
+    Flex,
+    /// Unknown model, with its USB product id
+    Unknown(u16),
+}
+
+impl LedgerModel {
+    /// Model of a device from its USB product id
+    ///
+    /// Recent firmwares put the model in the high byte, bootloaders and older firmwares report it alone
+    pub fn from_product_id(product_id: u16) -> Self {
+        let model = if product_id > 0xff { product_id >> 12 } else { product_id };
+
+        match model {
+            0x1 => LedgerModel::NanoS,
+            0x4 => LedgerModel::NanoX,
+            0x5 => LedgerModel::NanoSPlus,
+            0x6 => LedgerModel::Stax,
+            0x7 => LedgerModel::Flex,
+            _ => LedgerModel::Unknown(product_id),
+        }
+    }
+}
+
+impl fmt::Display for LedgerModel {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            LedgerModel::NanoS => write!(f, "Nano S"),
+            LedgerModel::NanoX => write!(f, "Nano X"),
+            LedgerModel::NanoSPlus => write!(f, "Nano S Plus"),
+            LedgerModel::Stax => write!(f, "Stax"),
+            LedgerModel::Flex => write!(f, "Flex"),
+            LedgerModel::Unknown(product_id) => write!(f, "unknown (0x{product_id:04x})"),
+        }
+    }
+}
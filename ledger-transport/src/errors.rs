@@ -0,0 +1,101 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::{convert::Infallible, fmt, io};
+
+/// Transport independent category of a transport error
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TransportErrorKind {
+    /// The device went away, e.g. it was unplugged or the connection was closed
+    Disconnected,
+    /// The device didn't answer in time
+    Timeout,
+    /// The device or the transport is in use
+    Busy,
+    /// A frame didn't have the expected shape
+    Framing {
+        /// Expected value, e.g. a length or a sequence index
+        expected: usize,
+        /// Value received
+        got: usize,
+    },
+    /// Any other i/o failure
+    Io,
+    /// No device to connect to
+    DeviceNotFound,
+    /// The command was refused before reaching the device, e.g. by a policy
+    Denied,
+}
+
+impl TransportErrorKind {
+    /// Whether retrying the same exchange later may succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Timeout | Self::Busy)
+    }
+}
+
+impl fmt::Display for TransportErrorKind {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::Disconnected => write!(f, "device disconnected"),
+            Self::Timeout => write!(f, "timed out"),
+            Self::Busy => write!(f, "device busy"),
+            Self::Framing { expected, got } => write!(f, "framing error, expected {expected}, got {got}"),
+            Self::Io => write!(f, "i/o error"),
+            Self::DeviceNotFound => write!(f, "device not found"),
+            Self::Denied => write!(f, "command denied"),
+        }
+    }
+}
+
+impl From<io::ErrorKind> for TransportErrorKind {
+    fn from(kind: io::ErrorKind) -> Self {
+        match kind {
+            io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => Self::DeviceNotFound,
+            io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::UnexpectedEof => Self::Disconnected,
+            io::ErrorKind::TimedOut => Self::Timeout,
+            io::ErrorKind::WouldBlock | io::ErrorKind::AddrInUse | io::ErrorKind::ResourceBusy => Self::Busy,
+            _ => Self::Io,
+        }
+    }
+}
+
+/// Error of a transport, exposing its [TransportErrorKind]
+///
+/// Lets callers handle failures the same way whichever transport is used
+pub trait TransportError: std::error::Error {
+    /// Category of the error
+    fn kind(&self) -> TransportErrorKind;
+}
+
+impl TransportError for io::Error {
+    fn kind(&self) -> TransportErrorKind {
+        io::Error::kind(self).into()
+    }
+}
+
+impl TransportError for Infallible {
+    fn kind(&self) -> TransportErrorKind {
+        match *self {}
+    }
+}
@@ -22,22 +22,181 @@
 use std::ops::Deref;
 
 pub use async_trait::async_trait;
-pub use ledger_apdu::{APDUAnswer, APDUCommand, APDUErrorCode};
+pub use ledger_apdu::{APDUAnswer, APDUCommand, APDUCommandError, APDUErrorCode};
+
+mod errors;
+pub use errors::{TransportError, TransportErrorKind};
+
+#[cfg(feature = "mock")]
+pub mod mock;
 
 /// Use to talk to the ledger device
 #[async_trait]
 pub trait Exchange {
     /// Error defined by Transport used
-    type Error;
+    ///
+    /// Its [TransportErrorKind] lets callers handle failures whichever transport is used
+    type Error: TransportError;
 
     /// The concrete type containing the APDUAnswer
     type AnswerType: Deref<Target = [u8]> + Send;
 
     /// Send a command with the given transport and retrieve an answer or a transport error
+    ///
+    /// Transports with a [SessionLock] wait for any other [Session] to be released first
     async fn exchange<I>(
         &self,
         command: &APDUCommand<I>,
     ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
     where
         I: Deref<Target = [u8]> + Send + Sync;
+
+    /// Send a command on behalf of a [Session], which already holds the [SessionLock]
+    ///
+    /// Transports returning a lock from [Exchange::session_lock] must override this so it doesn't wait for the lock
+    async fn exchange_in_session<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        self.exchange(command).await
+    }
+
+    /// Lock used to give a [Session] exclusive use of the transport
+    ///
+    /// Transports that can be shared between tasks should return one, the default is `None`
+    fn session_lock(&self) -> Option<&SessionLock> {
+        None
+    }
+
+    /// Acquire what a [Session] holds while it has exclusive use of the transport
+    ///
+    /// The default takes the [Exchange::session_lock], if any. Transports sharing the device
+    /// by other means, e.g. a lease from a daemon, override it and return their own guard
+    async fn session_guard(&self) -> Result<Option<SessionGuard<'_>>, Self::Error> {
+        Ok(match self.session_lock() {
+            Some(lock) => Some(lock.lock().await),
+            None => None,
+        })
+    }
+
+    /// Acquire exclusive use of the transport, until the returned [Session] is dropped
+    ///
+    /// Use it for flows made of multiple APDUs, so they don't get interleaved with
+    /// the exchanges of other tasks sharing the transport
+    async fn lock_session(&self) -> Result<Session<'_, Self>, Self::Error>
+    where
+        Self: Sized + Sync,
+    {
+        let guard = self.session_guard().await?;
+
+        Ok(Session { transport: self, _guard: guard })
+    }
+}
+
+#[async_trait]
+impl<T> Exchange for &T
+where
+    T: Exchange + Sync + ?Sized,
+{
+    type Error = T::Error;
+    type AnswerType = T::AnswerType;
+
+    async fn exchange<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        (**self).exchange(command).await
+    }
+
+    async fn exchange_in_session<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        (**self)
+            .exchange_in_session(command)
+            .await
+    }
+
+    fn session_lock(&self) -> Option<&SessionLock> {
+        (**self).session_lock()
+    }
+
+    async fn session_guard(&self) -> Result<Option<SessionGuard<'_>>, Self::Error> {
+        (**self).session_guard().await
+    }
+}
+
+/// Lock serializing the [Session]s of a transport
+#[derive(Debug, Default)]
+pub struct SessionLock(async_lock::Mutex<()>);
+
+impl SessionLock {
+    /// Create a new, unlocked, session lock
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for the current session, if any, to be released and take the lock
+    ///
+    /// Transports hold it for the duration of every [Exchange::exchange]
+    pub async fn lock(&self) -> SessionGuard<'_> {
+        SessionGuard::new(self.0.lock().await)
+    }
+}
+
+/// Held while an exchange or a session uses the transport, released when dropped
+pub struct SessionGuard<'a> {
+    _guard: Box<dyn Send + Sync + 'a>,
+}
+
+impl<'a> SessionGuard<'a> {
+    /// Wrap whatever keeps the transport exclusive, e.g. a lock guard or a lease releasing itself on drop
+    pub fn new<G: Send + Sync + 'a>(guard: G) -> Self {
+        Self { _guard: Box::new(guard) }
+    }
+}
+
+/// Exclusive handle on a transport, obtained with [Exchange::lock_session]
+///
+/// Other exchanges on the transport wait until the session is dropped
+pub struct Session<'a, T: ?Sized> {
+    transport: &'a T,
+    _guard: Option<SessionGuard<'a>>,
+}
+
+impl<T: ?Sized> Session<'_, T> {
+    /// The transport this session was opened on
+    pub fn transport(&self) -> &T {
+        self.transport
+    }
+}
+
+#[async_trait]
+impl<T> Exchange for Session<'_, T>
+where
+    T: Exchange + Sync + ?Sized,
+{
+    type Error = T::Error;
+    type AnswerType = T::AnswerType;
+
+    async fn exchange<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        self.transport
+            .exchange_in_session(command)
+            .await
+    }
 }
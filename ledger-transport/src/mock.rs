@@ -0,0 +1,156 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! In-memory transport for tests, enabled by the `mock` feature
+
+use std::{
+    fmt,
+    future::Future,
+    ops::Deref,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+};
+
+use crate::{async_trait, APDUAnswer, APDUCommand, Exchange, SessionLock, TransportError, TransportErrorKind};
+
+/// Error of [MockTransport], returned when no answer is left or a queued answer is invalid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MockError;
+
+impl fmt::Display for MockError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "mock transport error")
+    }
+}
+
+impl std::error::Error for MockError {}
+
+impl TransportError for MockError {
+    fn kind(&self) -> TransportErrorKind {
+        TransportErrorKind::Disconnected
+    }
+}
+
+/// Replies with the queued answers and records every command sent
+///
+/// Every exchange yields once to the executor, so concurrent flows can interleave
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    sent: Mutex<Vec<Vec<u8>>>,
+    answers: Mutex<Vec<Vec<u8>>>,
+    session: SessionLock,
+}
+
+impl MockTransport {
+    /// Reply with `answers` in order, each made of the answer data and the status word
+    pub fn new(answers: &[&[u8]]) -> Self {
+        let answers = answers
+            .iter()
+            .rev()
+            .map(|a| a.to_vec())
+            .collect();
+        Self { sent: Mutex::new(Vec::new()), answers: Mutex::new(answers), session: SessionLock::new() }
+    }
+
+    /// Answer the first `count` commands with `0x9000`
+    pub fn ok(count: usize) -> Self {
+        Self::new(&vec![&[0x90u8, 0x00][..]; count])
+    }
+
+    /// Serialized commands sent so far
+    pub fn sent(&self) -> Vec<Vec<u8>> {
+        self.sent
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    async fn exchange_unlocked<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Vec<u8>>, MockError>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        self.sent
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(command.serialize());
+        YieldNow(false).await;
+
+        let answer = self
+            .answers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .pop()
+            .ok_or(MockError)?;
+        APDUAnswer::from_answer(answer).map_err(|_| MockError)
+    }
+}
+
+/// Pending on its first poll only
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[async_trait]
+impl Exchange for MockTransport {
+    type Error = MockError;
+    type AnswerType = Vec<u8>;
+
+    async fn exchange<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        let _session = self.session.lock().await;
+        self.exchange_unlocked(command).await
+    }
+
+    async fn exchange_in_session<I>(
+        &self,
+        command: &APDUCommand<I>,
+    ) -> Result<APDUAnswer<Self::AnswerType>, Self::Error>
+    where
+        I: Deref<Target = [u8]> + Send + Sync,
+    {
+        self.exchange_unlocked(command).await
+    }
+
+    fn session_lock(&self) -> Option<&SessionLock> {
+        Some(&self.session)
+    }
+}
@@ -0,0 +1,467 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Derive macros for typed Ledger app commands
+//!
+//! ```ignore
+//! #[derive(LedgerCommand)]
+//! #[apdu(ins = 0x01, response = GetAddressResponse, method = get_address)]
+//! struct GetAddress {
+//!     #[apdu(p1)]
+//!     show: bool,
+//!     #[apdu(le)]
+//!     path: [u32; 5],
+//! }
+//!
+//! #[derive(LedgerResponse)]
+//! struct GetAddressResponse {
+//!     pubkey: [u8; 33],
+//!     address: String,
+//! }
+//! ```
+//!
+//! Container attributes of `LedgerCommand`:
+//! * `ins = <expr>` - instruction of the command (required)
+//! * `p1 = <expr>`, `p2 = <expr>` - constant P1/P2, `0` by default. Chunked commands can't set the ones their app's
+//!   chunking scheme overwrites, e.g. P1 with the Zondax scheme, `send_command` fails otherwise
+//! * `response = <type>` - typed answer, `()` by default
+//! * `method = <ident>` - generates a `<Name>Ext` trait, implemented for every `AppExt`, with an async method of that name
+//!
+//! Container attributes of `LedgerResponse`:
+//! * `allow_trailing` - don't fail when bytes are left after the last field
+//!
+//! Field attributes:
+//! * `p1`, `p2` - take P1/P2 from this field instead of the payload (commands only)
+//! * `chunked` - stream this field with `send_chunks`, the other fields being the `Init` payload (commands only)
+//! * `le` - encode integers as little endian, big endian being the default
+//! * `len = u8` or `len = u16` - prefix the field with its length
+//! * `skip` - leave the field out of the encoding (decoded as `Default::default()`)
+
+#![deny(missing_docs)]
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Expr, Fields, Ident, Member, Type};
+
+/// Derive `ledger_zondax_generic::LedgerCommand`
+#[proc_macro_derive(LedgerCommand, attributes(apdu))]
+pub fn derive_ledger_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    command::expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Derive `ledger_zondax_generic::LedgerResponse`
+#[proc_macro_derive(LedgerResponse, attributes(apdu))]
+pub fn derive_ledger_response(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    response::expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum LengthPrefix {
+    U8,
+    U16,
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    p1: bool,
+    p2: bool,
+    chunked: bool,
+    le: bool,
+    skip: bool,
+    len: Option<LengthPrefix>,
+}
+
+struct Field {
+    member: Member,
+    binding: Ident,
+    ty: Type,
+    attrs: FieldAttrs,
+}
+
+impl Field {
+    fn endianness(&self) -> TokenStream2 {
+        if self.attrs.le {
+            quote!(::ledger_zondax_generic::command::Endianness::Little)
+        } else {
+            quote!(::ledger_zondax_generic::command::Endianness::Big)
+        }
+    }
+}
+
+fn parse_fields(fields: &Fields) -> syn::Result<Vec<Field>> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let member = match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(idx.into()),
+            };
+            let binding = match &field.ident {
+                Some(ident) => ident.clone(),
+                None => format_ident!("__field{}", idx),
+            };
+
+            let mut attrs = FieldAttrs::default();
+            for attr in field
+                .attrs
+                .iter()
+                .filter(|attr| attr.path().is_ident("apdu"))
+            {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("p1") {
+                        attrs.p1 = true;
+                    } else if meta.path.is_ident("p2") {
+                        attrs.p2 = true;
+                    } else if meta.path.is_ident("chunked") {
+                        attrs.chunked = true;
+                    } else if meta.path.is_ident("le") {
+                        attrs.le = true;
+                    } else if meta.path.is_ident("skip") {
+                        attrs.skip = true;
+                    } else if meta.path.is_ident("len") {
+                        let ty: Ident = meta.value()?.parse()?;
+                        attrs.len = Some(match ty.to_string().as_str() {
+                            "u8" => LengthPrefix::U8,
+                            "u16" => LengthPrefix::U16,
+                            _ => return Err(Error::new(ty.span(), "length prefix must be `u8` or `u16`")),
+                        });
+                    } else {
+                        return Err(meta.error("unknown field attribute"));
+                    }
+                    Ok(())
+                })?;
+            }
+
+            Ok(Field { member, binding, ty: field.ty.clone(), attrs })
+        })
+        .collect()
+}
+
+fn struct_fields(input: &DeriveInput) -> syn::Result<&Fields> {
+    match &input.data {
+        Data::Struct(data) => Ok(&data.fields),
+        _ => Err(Error::new(Span::call_site(), "only structs are supported")),
+    }
+}
+
+mod command {
+    use super::*;
+
+    #[derive(Default)]
+    struct ContainerAttrs {
+        ins: Option<Expr>,
+        p1: Option<Expr>,
+        p2: Option<Expr>,
+        response: Option<Type>,
+        method: Option<Ident>,
+    }
+
+    fn parse_container(input: &DeriveInput) -> syn::Result<ContainerAttrs> {
+        let mut attrs = ContainerAttrs::default();
+
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("apdu"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("ins") {
+                    attrs.ins = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("p1") {
+                    attrs.p1 = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("p2") {
+                    attrs.p2 = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("response") {
+                    attrs.response = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("method") {
+                    attrs.method = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("unknown container attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(attrs)
+    }
+
+    fn header(
+        name: &str,
+        constant: Option<Expr>,
+        field: Option<&Field>,
+    ) -> syn::Result<TokenStream2> {
+        match (constant, field) {
+            (Some(_), Some(field)) => {
+                Err(Error::new(field.ty.span(), format!("{name} is set both as a constant and from a field")))
+            },
+            (Some(expr), None) => Ok(quote!(#expr)),
+            (None, Some(field)) => {
+                let member = &field.member;
+                Ok(quote!(::core::convert::From::from(self.#member)))
+            },
+            (None, None) => Ok(quote!(0)),
+        }
+    }
+
+    pub fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+        let container = parse_container(&input)?;
+        let fields = parse_fields(struct_fields(&input)?)?;
+
+        let name = &input.ident;
+        let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+        let ins = container
+            .ins
+            .ok_or_else(|| Error::new(Span::call_site(), "missing `#[apdu(ins = ...)]`"))?;
+        let response = container
+            .response
+            .map(|ty| quote!(#ty))
+            .unwrap_or_else(|| quote!(()));
+
+        let p1_field = fields.iter().find(|f| f.attrs.p1);
+        let p2_field = fields.iter().find(|f| f.attrs.p2);
+        let p1 = header("P1", container.p1, p1_field)?;
+        let p2 = header("P2", container.p2, p2_field)?;
+
+        let mut chunked = fields
+            .iter()
+            .filter(|f| f.attrs.chunked);
+        let chunked_data = match (chunked.next(), chunked.next()) {
+            (_, Some(second)) => return Err(Error::new(second.ty.span(), "only one field can be `chunked`")),
+            (Some(field), None) => {
+                let member = &field.member;
+                quote! {
+                    fn chunked_data(&self) -> ::core::option::Option<&[u8]> {
+                        ::core::option::Option::Some(&self.#member[..])
+                    }
+                }
+            },
+            (None, None) => quote!(),
+        };
+
+        let writes = fields
+            .iter()
+            .filter(|f| !(f.attrs.p1 || f.attrs.p2 || f.attrs.chunked || f.attrs.skip))
+            .map(|field| {
+                let member = &field.member;
+                let endianness = field.endianness();
+                let write = quote! {
+                    ::ledger_zondax_generic::command::ApduSerialize::write_to(&self.#member, &mut field, #endianness);
+                };
+
+                match field.attrs.len {
+                    None => quote! {
+                        ::ledger_zondax_generic::command::ApduSerialize::write_to(&self.#member, &mut out, #endianness);
+                    },
+                    Some(LengthPrefix::U8) => quote! {
+                        {
+                            let mut field = ::std::vec::Vec::new();
+                            #write
+                            let len = u8::try_from(field.len()).map_err(|_| {
+                                ::ledger_zondax_generic::EncodeError::FieldTooLong { len: field.len(), max: u8::MAX as usize }
+                            })?;
+                            out.push(len);
+                            out.extend_from_slice(&field);
+                        }
+                    },
+                    Some(LengthPrefix::U16) => quote! {
+                        {
+                            let mut field = ::std::vec::Vec::new();
+                            #write
+                            let len = u16::try_from(field.len()).map_err(|_| {
+                                ::ledger_zondax_generic::EncodeError::FieldTooLong { len: field.len(), max: u16::MAX as usize }
+                            })?;
+                            ::ledger_zondax_generic::command::ApduSerialize::write_to(&len, &mut out, #endianness);
+                            out.extend_from_slice(&field);
+                        }
+                    },
+                }
+            });
+
+        let mut expanded = quote! {
+            impl #impl_generics ::ledger_zondax_generic::LedgerCommand for #name #ty_generics #where_clause {
+                const INS: u8 = #ins;
+
+                type Response = #response;
+
+                fn p1(&self) -> u8 {
+                    #p1
+                }
+
+                fn p2(&self) -> u8 {
+                    #p2
+                }
+
+                fn payload(&self) -> ::core::result::Result<::std::vec::Vec<u8>, ::ledger_zondax_generic::EncodeError> {
+                    let mut out = ::std::vec::Vec::new();
+                    #(#writes)*
+                    ::core::result::Result::Ok(out)
+                }
+
+                #chunked_data
+            }
+        };
+
+        if let Some(method) = container.method {
+            let vis = &input.vis;
+            let trait_name = format_ident!("{}Ext", name);
+            let doc = format!("Send [{name}] to the app and decode its answer");
+            let trait_doc = format!("Sends [{name}], implemented for every `AppExt`");
+            let method_generics = &input.generics.params;
+            let method_where = &input.generics.where_clause;
+
+            expanded.extend(quote! {
+                #[doc = #trait_doc]
+                #[::ledger_zondax_generic::__private::async_trait]
+                #vis trait #trait_name<E>: ::ledger_zondax_generic::AppExt<E>
+                where
+                    E: ::ledger_zondax_generic::__private::Exchange + Send + Sync,
+                    E::Error: ::std::error::Error,
+                {
+                    #[doc = #doc]
+                    async fn #method<#method_generics>(
+                        transport: &E,
+                        command: &#name #ty_generics,
+                    ) -> ::core::result::Result<#response, ::ledger_zondax_generic::LedgerAppError<E::Error>>
+                    #method_where
+                    {
+                        <Self as ::ledger_zondax_generic::AppExt<E>>::send_command(transport, command).await
+                    }
+                }
+
+                impl<T, E> #trait_name<E> for T
+                where
+                    T: ::ledger_zondax_generic::AppExt<E>,
+                    E: ::ledger_zondax_generic::__private::Exchange + Send + Sync,
+                    E::Error: ::std::error::Error,
+                {
+                }
+            });
+        }
+
+        Ok(expanded)
+    }
+}
+
+mod response {
+    use super::*;
+
+    fn allow_trailing(input: &DeriveInput) -> syn::Result<bool> {
+        let mut allow = false;
+
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("apdu"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("allow_trailing") {
+                    allow = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown container attribute"))
+                }
+            })?;
+        }
+
+        Ok(allow)
+    }
+
+    pub fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+        let allow_trailing = allow_trailing(&input)?;
+        let struct_fields = struct_fields(&input)?;
+        let fields = parse_fields(struct_fields)?;
+
+        if let Some(field) = fields
+            .iter()
+            .find(|f| f.attrs.p1 || f.attrs.p2 || f.attrs.chunked)
+        {
+            return Err(Error::new(field.ty.span(), "`p1`, `p2` and `chunked` are only valid on commands"));
+        }
+
+        let name = &input.ident;
+        let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+        let reads = fields.iter().map(|field| {
+            let binding = &field.binding;
+            let ty = &field.ty;
+            let endianness = field.endianness();
+
+            if field.attrs.skip {
+                return quote!(let #binding: #ty = ::core::default::Default::default(););
+            }
+
+            let take = match field.attrs.len {
+                None => {
+                    return quote! {
+                        let #binding = <#ty as ::ledger_zondax_generic::command::ApduDeserialize>::read_from(&mut data, #endianness)?;
+                    };
+                },
+                Some(LengthPrefix::U8) => quote!(::ledger_zondax_generic::command::take_u8_prefixed(&mut data)?),
+                Some(LengthPrefix::U16) => {
+                    quote!(::ledger_zondax_generic::command::take_u16_prefixed(&mut data, #endianness)?)
+                },
+            };
+
+            quote! {
+                let #binding = {
+                    let mut field = #take;
+                    let value = <#ty as ::ledger_zondax_generic::command::ApduDeserialize>::read_from(&mut field, #endianness)?;
+                    if !field.is_empty() {
+                        return ::core::result::Result::Err(::ledger_zondax_generic::DecodeError::TrailingBytes(field.len()));
+                    }
+                    value
+                };
+            }
+        });
+
+        let bindings = fields.iter().map(|f| &f.binding);
+        let construct = match struct_fields {
+            Fields::Named(_) => quote!(Self { #(#bindings),* }),
+            Fields::Unnamed(_) => quote!(Self ( #(#bindings),* )),
+            Fields::Unit => quote!(Self),
+        };
+
+        let trailing = if allow_trailing {
+            quote!()
+        } else {
+            quote! {
+                if !data.is_empty() {
+                    return ::core::result::Result::Err(::ledger_zondax_generic::DecodeError::TrailingBytes(data.len()));
+                }
+            }
+        };
+
+        Ok(quote! {
+            impl #impl_generics ::ledger_zondax_generic::LedgerResponse for #name #ty_generics #where_clause {
+                fn from_response(data: &[u8]) -> ::core::result::Result<Self, ::ledger_zondax_generic::DecodeError> {
+                    #[allow(unused_mut)]
+                    let mut data = data;
+                    #(#reads)*
+                    #trailing
+                    ::core::result::Result::Ok(#construct)
+                }
+            }
+        })
+    }
+}
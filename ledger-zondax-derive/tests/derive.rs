@@ -0,0 +1,179 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use ledger_transport::mock::MockTransport;
+use ledger_zondax_derive::{LedgerCommand, LedgerResponse};
+use ledger_zondax_generic::{
+    App, AppExt, Chunking, DecodeError, EncodeError, LedgerAppError, LedgerCommand as _, LedgerResponse as _,
+};
+
+struct Dummy;
+
+impl App for Dummy {
+    const CLA: u8 = 0x55;
+}
+
+/// App with the chunk type in P2
+struct P2App;
+
+impl App for P2App {
+    const CLA: u8 = 0x66;
+    const CHUNKING: Chunking = Chunking::P2;
+}
+
+#[derive(LedgerResponse, Debug, PartialEq)]
+struct AddressResponse {
+    pubkey: [u8; 4],
+    #[apdu(len = u8)]
+    address: String,
+}
+
+#[derive(LedgerCommand)]
+#[apdu(ins = 0x04, p2 = 0x01, response = AddressResponse, method = get_address)]
+struct GetAddress {
+    #[apdu(p1)]
+    show: bool,
+    #[apdu(le)]
+    path: [u32; 2],
+}
+
+#[derive(LedgerCommand)]
+#[apdu(ins = 0x02, response = Vec<u8>, method = sign)]
+struct Sign<'a> {
+    #[apdu(len = u16)]
+    path: Vec<u8>,
+    #[apdu(chunked)]
+    message: &'a [u8],
+}
+
+#[derive(LedgerCommand)]
+#[apdu(ins = 0x03, p1 = 0x01, response = Vec<u8>)]
+struct SignWithParams<'a> {
+    #[apdu(p2)]
+    p2: u8,
+    #[apdu(chunked)]
+    message: &'a [u8],
+}
+
+#[derive(LedgerResponse, Debug, PartialEq)]
+struct Tuple(u16, #[apdu(le)] u16, #[apdu(skip)] u8);
+
+#[test]
+fn command_payload() {
+    let command = GetAddress { show: true, path: [0x8000002c, 1] };
+
+    assert_eq!(GetAddress::INS, 0x04);
+    assert_eq!(command.p1(), 1);
+    assert_eq!(command.p2(), 1);
+    assert_eq!(command.payload(), Ok(vec![0x2c, 0, 0, 0x80, 1, 0, 0, 0]));
+    assert_eq!(command.chunked_data(), None);
+}
+
+#[test]
+fn chunked_command_payload() {
+    let message = [0xAA; 3];
+    let command = Sign { path: vec![1, 2], message: &message };
+
+    assert_eq!(command.payload(), Ok(vec![0, 2, 1, 2]));
+    assert_eq!(command.chunked_data(), Some(&message[..]));
+
+    let command = Sign { path: vec![0; 0x10000], message: &message };
+    assert_eq!(command.payload(), Err(EncodeError::FieldTooLong { len: 0x10000, max: 0xffff }));
+}
+
+#[test]
+fn response_decoding() {
+    let decoded = AddressResponse::from_response(&[1, 2, 3, 4, 2, b'h', b'i']).expect("valid response");
+    assert_eq!(decoded, AddressResponse { pubkey: [1, 2, 3, 4], address: "hi".to_string() });
+
+    let decoded = Tuple::from_response(&[0, 1, 1, 0]).expect("valid response");
+    assert_eq!(decoded, Tuple(1, 1, 0));
+}
+
+#[test]
+fn response_decoding_errors() {
+    assert_eq!(
+        AddressResponse::from_response(&[1, 2, 3, 4, 3, b'a']),
+        Err(DecodeError::TooShort { expected: 3, got: 1 })
+    );
+    assert_eq!(Tuple::from_response(&[0, 1, 1, 0, 9]), Err(DecodeError::TrailingBytes(1)));
+}
+
+#[test]
+fn generated_method() {
+    let transport = MockTransport::new(&[&[9, 9, 9, 9, 1, b'a', 0x90, 0x00]]);
+
+    let command = GetAddress { show: false, path: [1, 2] };
+    let response = futures::executor::block_on(Dummy::get_address(&transport, &command)).expect("valid exchange");
+
+    assert_eq!(response, AddressResponse { pubkey: [9; 4], address: "a".to_string() });
+    assert_eq!(transport.sent()[0], vec![0x55, 0x04, 0, 1, 8, 1, 0, 0, 0, 2, 0, 0, 0]);
+}
+
+#[test]
+fn generated_method_chunks() {
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x90, 0x00], &[0xCA, 0xFE, 0x90, 0x00]]);
+
+    let message = vec![0x42; 260];
+    let command = Sign { path: vec![7], message: &message };
+    let response = futures::executor::block_on(<Dummy as AppExt<MockTransport>>::send_command(&transport, &command))
+        .expect("valid exchange");
+
+    assert_eq!(response, vec![0xCA, 0xFE]);
+
+    let sent = transport.sent();
+    assert_eq!(sent.len(), 3);
+    assert_eq!(sent[0], vec![0x55, 0x02, 0x00, 0, 3, 0, 1, 7]);
+    assert_eq!(&sent[1][.. 5], &[0x55, 0x02, 0x01, 0, 250]);
+    assert_eq!(&sent[2][.. 5], &[0x55, 0x02, 0x02, 0, 10]);
+}
+
+#[test]
+fn generated_method_encode_error() {
+    let transport = MockTransport::ok(0);
+
+    let command = Sign { path: vec![0; 0x10000], message: &[] };
+    let err = futures::executor::block_on(<Dummy as AppExt<MockTransport>>::send_command(&transport, &command))
+        .expect_err("payload too long");
+
+    assert_eq!(err, LedgerAppError::InvalidCommand(EncodeError::FieldTooLong { len: 0x10000, max: 0xffff }));
+    assert!(transport.sent().is_empty());
+}
+
+#[test]
+fn generated_method_chunk_params() {
+    let message = [0x42; 3];
+
+    // the Zondax scheme carries the chunk type in P1
+    let transport = MockTransport::ok(0);
+    let command = SignWithParams { p2: 0, message: &message };
+    let err = futures::executor::block_on(<Dummy as AppExt<MockTransport>>::send_command(&transport, &command))
+        .expect_err("P1 is overwritten");
+    assert_eq!(err, LedgerAppError::ChunkParamOverwritten { param: "P1".to_string(), value: 0x01 });
+    assert!(transport.sent().is_empty());
+
+    // the P2 scheme keeps P1, but carries the chunk type in P2
+    let transport = MockTransport::ok(2);
+    let response = futures::executor::block_on(<P2App as AppExt<MockTransport>>::send_command(&transport, &command))
+        .expect("valid exchange");
+    assert!(response.is_empty());
+    assert_eq!(&transport.sent()[0][.. 4], &[0x66, 0x03, 0x01, 0x00]);
+
+    let transport = MockTransport::ok(0);
+    let command = SignWithParams { p2: 0x05, message: &message };
+    let err = futures::executor::block_on(<P2App as AppExt<MockTransport>>::send_command(&transport, &command))
+        .expect_err("P2 is overwritten");
+    assert_eq!(err, LedgerAppError::ChunkParamOverwritten { param: "P2".to_string(), value: 0x05 });
+}
@@ -0,0 +1,142 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Public key and address retrieval, see [AppExt::get_pubkey](crate::AppExt::get_pubkey)
+
+use serde::{Deserialize, Serialize};
+
+use crate::{command, DecodeError, DerivationPath, PathError};
+
+/// How the derivation path is written in the request payload
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PathEncoding {
+    /// Length byte followed by big endian `u32`, see [DerivationPath::serialize_be]
+    LengthPrefixedBe,
+    /// Five little endian `u32`, see [DerivationPath::serialize_zondax]
+    Zondax,
+}
+
+impl PathEncoding {
+    /// Encode `path` for the request payload
+    pub fn encode(
+        self,
+        path: &DerivationPath,
+    ) -> Result<Vec<u8>, PathError> {
+        match self {
+            Self::LengthPrefixedBe => Ok(path.serialize_be()),
+            Self::Zondax => path
+                .serialize_zondax()
+                .map(|path| path.to_vec()),
+        }
+    }
+}
+
+/// Length of a field of the answer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FieldLength {
+    /// Always this many bytes
+    Fixed(usize),
+    /// Prefixed with a length byte
+    Prefixed,
+    /// Everything left in the answer
+    Rest,
+}
+
+impl FieldLength {
+    fn take<'a>(
+        self,
+        data: &mut &'a [u8],
+    ) -> Result<&'a [u8], DecodeError> {
+        match self {
+            Self::Fixed(len) => command::take(data, len),
+            Self::Prefixed => command::take_u8_prefixed(data),
+            Self::Rest => command::take(data, data.len()),
+        }
+    }
+}
+
+/// Shape of an app "get address" instruction
+///
+/// P1 asks for on-screen confirmation, P2 is always 0.
+/// The answer holds the public key followed by the address as text
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AddressLayout {
+    /// Instruction of the command
+    pub ins: u8,
+    /// Encoding of the path in the request
+    pub path: PathEncoding,
+    /// Length of the public key in the answer
+    pub pubkey: FieldLength,
+    /// Length of the address in the answer
+    pub address: FieldLength,
+}
+
+impl AddressLayout {
+    /// Layout of most Zondax apps: compressed secp256k1 key followed by the address
+    pub const ZONDAX_SECP256K1: Self =
+        Self { ins: 0x04, path: PathEncoding::Zondax, pubkey: FieldLength::Fixed(33), address: FieldLength::Rest };
+
+    /// Layout of Zondax ed25519 apps: 32 bytes key followed by the address
+    pub const ZONDAX_ED25519: Self =
+        Self { ins: 0x01, path: PathEncoding::Zondax, pubkey: FieldLength::Fixed(32), address: FieldLength::Rest };
+
+    pub(crate) fn decode(
+        &self,
+        mut data: &[u8],
+    ) -> Result<AddressInfo, DecodeError> {
+        let public_key = self.pubkey.take(&mut data)?;
+        let address = self.address.take(&mut data)?;
+        if !data.is_empty() {
+            return Err(DecodeError::TrailingBytes(data.len()));
+        }
+
+        let address = std::str::from_utf8(address).map_err(|_| DecodeError::Utf8)?;
+        Ok(AddressInfo { public_key: PublicKey(public_key.to_vec()), address: address.to_string() })
+    }
+}
+
+/// Raw public key returned by an app
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PublicKey(pub Vec<u8>);
+
+impl PublicKey {
+    /// Key bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Parse a compressed (33 bytes) or uncompressed (65 bytes) secp256k1 key
+    #[cfg(feature = "secp256k1")]
+    pub fn to_secp256k1(&self) -> Option<k256::PublicKey> {
+        k256::PublicKey::from_sec1_bytes(&self.0).ok()
+    }
+
+    /// Parse a 32 bytes ed25519 key
+    #[cfg(feature = "ed25519")]
+    pub fn to_ed25519(&self) -> Option<ed25519_dalek::VerifyingKey> {
+        let bytes = self.0.as_slice().try_into().ok()?;
+        ed25519_dalek::VerifyingKey::from_bytes(bytes).ok()
+    }
+}
+
+/// Answer of [AppExt::get_pubkey](crate::AppExt::get_pubkey)
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AddressInfo {
+    /// Public key
+    #[serde(rename(serialize = "publicKey"), alias = "publicKey")]
+    pub public_key: PublicKey,
+    /// Address derived from the public key
+    pub address: String,
+}
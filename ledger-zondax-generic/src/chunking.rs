@@ -0,0 +1,494 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Strategies to split a long message in multiple APDUs
+
+use std::ops::ControlFlow;
+
+use ledger_transport::{APDUAnswer, APDUCommand, Exchange};
+
+use crate::{source::MessageSource, ChunkPayloadType, LedgerAppError};
+
+/// Default chunk payload size
+pub const DEFAULT_CHUNK_SIZE: usize = 250;
+
+/// Largest chunk payload, the length of an APDU payload is a single byte
+pub const MAX_CHUNK_SIZE: usize = u8::MAX as usize;
+
+/// CLA, INS, P1 and P2 of the message being chunked
+///
+/// Schemes derive the header of every chunk from it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkHeader {
+    /// APDU Class
+    pub cla: u8,
+    /// APDU Instruction
+    pub ins: u8,
+    /// First parameter of instruction
+    pub p1: u8,
+    /// Second parameter of instruction
+    pub p2: u8,
+}
+
+impl ChunkHeader {
+    /// Check that the header doesn't set a parameter `scheme` overwrites, it would silently be lost
+    ///
+    /// Parameters left at `0` are accepted
+    pub(crate) fn check<S, E>(
+        &self,
+        scheme: &S,
+    ) -> Result<(), LedgerAppError<E>>
+    where
+        S: ChunkingScheme + ?Sized,
+        E: std::error::Error,
+    {
+        for (param, value, overwritten) in [("P1", self.p1, scheme.sets_p1()), ("P2", self.p2, scheme.sets_p2())] {
+            if overwritten && value != 0 {
+                return Err(LedgerAppError::ChunkParamOverwritten { param: param.to_string(), value });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<I> From<&APDUCommand<I>> for ChunkHeader {
+    fn from(command: &APDUCommand<I>) -> Self {
+        Self { cla: command.cla, ins: command.ins, p1: command.p1, p2: command.p2 }
+    }
+}
+
+/// Defines how a long message is split into APDUs
+pub trait ChunkingScheme: Send + Sync {
+    /// Maximum payload size of a single chunk
+    fn chunk_size(&self) -> usize;
+
+    /// Maximum number of chunks in a message (not counting the init APDU), `None` if unbounded
+    fn max_chunks(&self) -> Option<usize>;
+
+    /// Whether the scheme sets P1 of every APDU, overwriting the one of the [ChunkHeader]
+    ///
+    /// The default is `false`
+    fn sets_p1(&self) -> bool {
+        false
+    }
+
+    /// Whether the scheme sets P2 of every APDU, overwriting the one of the [ChunkHeader]
+    ///
+    /// The default is `false`
+    fn sets_p2(&self) -> bool {
+        false
+    }
+
+    /// Build the APDU opening the message with the given payload
+    ///
+    /// Schemes without an init APDU return `None`, the payload is then sent
+    /// as the beginning of the chunked message
+    fn init(
+        &self,
+        header: &ChunkHeader,
+        payload: &[u8],
+    ) -> Option<APDUCommand<Vec<u8>>>;
+
+    /// Build the APDU carrying the chunk at `index` (0-based)
+    fn chunk(
+        &self,
+        header: &ChunkHeader,
+        index: usize,
+        last: bool,
+        data: &[u8],
+    ) -> APDUCommand<Vec<u8>>;
+}
+
+/// Zondax scheme: P1 is [ChunkPayloadType::Init], then [ChunkPayloadType::Add] and
+/// [ChunkPayloadType::Last], P2 is kept for all chunks
+///
+/// At most 255 chunks can be sent
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ZondaxChunking {
+    chunk_size: usize,
+}
+
+impl ZondaxChunking {
+    /// Create the scheme with the given chunk size
+    pub const fn new(chunk_size: usize) -> Self {
+        Self { chunk_size }
+    }
+}
+
+impl Default for ZondaxChunking {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHUNK_SIZE)
+    }
+}
+
+impl ChunkingScheme for ZondaxChunking {
+    fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    fn max_chunks(&self) -> Option<usize> {
+        Some(255)
+    }
+
+    fn sets_p1(&self) -> bool {
+        true
+    }
+
+    fn init(
+        &self,
+        header: &ChunkHeader,
+        payload: &[u8],
+    ) -> Option<APDUCommand<Vec<u8>>> {
+        Some(APDUCommand {
+            cla: header.cla,
+            ins: header.ins,
+            p1: ChunkPayloadType::Init as u8,
+            p2: header.p2,
+            data: payload.to_vec(),
+        })
+    }
+
+    fn chunk(
+        &self,
+        header: &ChunkHeader,
+        _: usize,
+        last: bool,
+        data: &[u8],
+    ) -> APDUCommand<Vec<u8>> {
+        let p1 = if last { ChunkPayloadType::Last } else { ChunkPayloadType::Add };
+
+        APDUCommand { cla: header.cla, ins: header.ins, p1: p1 as u8, p2: header.p2, data: data.to_vec() }
+    }
+}
+
+/// Same as [ZondaxChunking], with the chunk type in P2 and P1 kept for all chunks
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct P2Chunking {
+    chunk_size: usize,
+}
+
+impl P2Chunking {
+    /// Create the scheme with the given chunk size
+    pub const fn new(chunk_size: usize) -> Self {
+        Self { chunk_size }
+    }
+}
+
+impl ChunkingScheme for P2Chunking {
+    fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    fn max_chunks(&self) -> Option<usize> {
+        Some(255)
+    }
+
+    fn sets_p2(&self) -> bool {
+        true
+    }
+
+    fn init(
+        &self,
+        header: &ChunkHeader,
+        payload: &[u8],
+    ) -> Option<APDUCommand<Vec<u8>>> {
+        Some(APDUCommand {
+            cla: header.cla,
+            ins: header.ins,
+            p1: header.p1,
+            p2: ChunkPayloadType::Init as u8,
+            data: payload.to_vec(),
+        })
+    }
+
+    fn chunk(
+        &self,
+        header: &ChunkHeader,
+        _: usize,
+        last: bool,
+        data: &[u8],
+    ) -> APDUCommand<Vec<u8>> {
+        let p2 = if last { ChunkPayloadType::Last } else { ChunkPayloadType::Add };
+
+        APDUCommand { cla: header.cla, ins: header.ins, p1: header.p1, p2: p2 as u8, data: data.to_vec() }
+    }
+}
+
+/// Counter scheme: P1-P2 hold the chunk index as a big endian `u16`, there is no init APDU
+///
+/// The last chunk can optionally be flagged by OR-ing the CLA with `last_cla_flag`.
+/// Up to 65536 chunks can be sent
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CounterChunking {
+    chunk_size: usize,
+    last_cla_flag: u8,
+}
+
+impl CounterChunking {
+    /// Create the scheme with the given chunk size
+    pub const fn new(chunk_size: usize) -> Self {
+        Self { chunk_size, last_cla_flag: 0 }
+    }
+
+    /// Flag the last chunk by OR-ing its CLA with `flag`
+    pub const fn with_last_cla_flag(
+        mut self,
+        flag: u8,
+    ) -> Self {
+        self.last_cla_flag = flag;
+        self
+    }
+}
+
+impl ChunkingScheme for CounterChunking {
+    fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    fn max_chunks(&self) -> Option<usize> {
+        Some(u16::MAX as usize + 1)
+    }
+
+    fn sets_p1(&self) -> bool {
+        true
+    }
+
+    fn sets_p2(&self) -> bool {
+        true
+    }
+
+    fn init(
+        &self,
+        _: &ChunkHeader,
+        _: &[u8],
+    ) -> Option<APDUCommand<Vec<u8>>> {
+        None
+    }
+
+    fn chunk(
+        &self,
+        header: &ChunkHeader,
+        index: usize,
+        last: bool,
+        data: &[u8],
+    ) -> APDUCommand<Vec<u8>> {
+        let [p1, p2] = (index as u16).to_be_bytes();
+        let cla = if last { header.cla | self.last_cla_flag } else { header.cla };
+
+        APDUCommand { cla, ins: header.ins, p1, p2, data: data.to_vec() }
+    }
+}
+
+/// The chunking scheme used by an app, see [App::CHUNKING](crate::App::CHUNKING)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Chunking {
+    /// [ZondaxChunking]
+    #[default]
+    Zondax,
+    /// [P2Chunking]
+    P2,
+    /// [CounterChunking], flagging the last chunk with `last_cla_flag`
+    Counter {
+        /// Flag OR-ed with the CLA of the last chunk, `0` for none
+        last_cla_flag: u8,
+    },
+}
+
+impl Chunking {
+    /// The scheme with the given chunk size
+    pub fn scheme(
+        self,
+        chunk_size: usize,
+    ) -> Box<dyn ChunkingScheme> {
+        match self {
+            Self::Zondax => Box::new(ZondaxChunking::new(chunk_size)),
+            Self::P2 => Box::new(P2Chunking::new(chunk_size)),
+            Self::Counter { last_cla_flag } => {
+                Box::new(CounterChunking::new(chunk_size).with_last_cla_flag(last_cla_flag))
+            },
+        }
+    }
+
+    /// Whether `header` is the one of an init APDU of this scheme
+    pub fn is_init(
+        self,
+        header: &ChunkHeader,
+    ) -> bool {
+        match self {
+            Self::Zondax => header.p1 == ChunkPayloadType::Init as u8,
+            Self::P2 => header.p2 == ChunkPayloadType::Init as u8,
+            Self::Counter { .. } => true,
+        }
+    }
+}
+
+/// Progress of a chunked message upload, reported after each chunk is accepted by the app
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkProgress {
+    /// Index of the accepted chunk (0-based, not counting the init APDU)
+    pub chunk_index: usize,
+    /// Total number of chunks, if known
+    pub total_chunks: Option<usize>,
+    /// Bytes of the message accepted so far
+    pub bytes_sent: usize,
+    /// Total size of the message, if known
+    pub total_bytes: Option<usize>,
+}
+
+/// Report chunk rejections like any other error, as [AppExt::send_chunks] does
+pub(crate) fn flatten_rejection<E: std::error::Error>(err: LedgerAppError<E>) -> LedgerAppError<E> {
+    match err {
+        LedgerAppError::ChunkRejected { error, .. } => *error,
+        err => err,
+    }
+}
+
+/// Splits a [MessageSource] in chunks, knowing in advance which one is the last
+struct Chunker<M> {
+    source: M,
+    chunk_size: usize,
+    pending: Vec<u8>,
+    exhausted: bool,
+}
+
+impl<M: MessageSource> Chunker<M> {
+    /// Next chunk of the message and whether it is the last one
+    async fn next_chunk(&mut self) -> Result<Option<(Vec<u8>, bool)>, std::io::Error> {
+        // read past the chunk boundary to know if more data follows
+        while !self.exhausted && self.pending.len() <= self.chunk_size {
+            match self
+                .source
+                .next_piece(self.chunk_size)
+                .await?
+            {
+                Some(piece) => self.pending.extend_from_slice(&piece),
+                None => self.exhausted = true,
+            }
+        }
+
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+
+        let len = std::cmp::min(self.chunk_size, self.pending.len());
+        let chunk = self.pending.drain(.. len).collect();
+
+        Ok(Some((chunk, self.exhausted && self.pending.is_empty())))
+    }
+}
+
+/// Send the message read from `source` following `scheme`
+///
+/// `check` maps the status word of every answer to an error.
+/// `progress` is called after every chunk and can cancel the upload before the next one.
+/// A chunk rejected by the app is reported as [LedgerAppError::ChunkRejected]
+pub(crate) async fn upload<E, C, S, M, F>(
+    transport: &E,
+    check: C,
+    scheme: &S,
+    header: ChunkHeader,
+    init: &[u8],
+    source: M,
+    mut progress: F,
+) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+where
+    E: Exchange + Send + Sync,
+    E::Error: std::error::Error,
+    C: Fn(&APDUAnswer<E::AnswerType>) -> Result<(), LedgerAppError<E::Error>> + Sync,
+    S: ChunkingScheme + ?Sized,
+    M: MessageSource,
+    F: FnMut(&ChunkProgress) -> ControlFlow<()> + Send,
+{
+    let chunk_size = scheme.chunk_size();
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+        return Err(LedgerAppError::InvalidMessageSize);
+    }
+
+    let init_command = scheme.init(&header, init);
+    let mut total_bytes = source.len_hint();
+    let mut chunker = Chunker { source, chunk_size, pending: Vec::new(), exhausted: false };
+
+    // without an init APDU, its payload is streamed as the start of the message
+    if init_command.is_none() {
+        chunker.pending = init.to_vec();
+        total_bytes = total_bytes.map(|len| len + init.len());
+    }
+
+    let total_chunks = total_bytes.map(|len| len.div_ceil(chunk_size));
+    let max_chunks = scheme.max_chunks();
+    match total_chunks {
+        Some(0) => return Err(LedgerAppError::InvalidEmptyMessage),
+        Some(n) if matches!(max_chunks, Some(max) if n > max) => return Err(LedgerAppError::InvalidMessageSize),
+        _ => (),
+    }
+
+    let read_error = |err: std::io::Error| LedgerAppError::MessageSource(err.to_string());
+    let Some(mut next) = chunker
+        .next_chunk()
+        .await
+        .map_err(read_error)?
+    else {
+        return Err(LedgerAppError::InvalidEmptyMessage);
+    };
+
+    // keep other tasks from interleaving APDUs with the chunks
+    let session = transport.lock_session().await?;
+
+    if let Some(command) = init_command {
+        let response = session.exchange(&command).await?;
+        check(&response)?;
+    }
+
+    let mut chunk_index = 0;
+    let mut bytes_sent = 0;
+    loop {
+        // only reachable when the message length wasn't known in advance
+        if matches!(max_chunks, Some(max) if chunk_index >= max) {
+            return Err(LedgerAppError::InvalidMessageSize);
+        }
+
+        let (chunk, last) = next;
+        let command = scheme.chunk(&header, chunk_index, last, &chunk);
+
+        let response = session.exchange(&command).await?;
+        if let Err(error) = check(&response) {
+            return Err(LedgerAppError::ChunkRejected {
+                index: chunk_index,
+                retcode: response.retcode(),
+                error: Box::new(error),
+            });
+        }
+
+        bytes_sent += chunk.len();
+        let flow = progress(&ChunkProgress { chunk_index, total_chunks, bytes_sent, total_bytes });
+
+        if last {
+            return Ok(response);
+        }
+        if flow.is_break() {
+            return Err(LedgerAppError::Cancelled { chunks_sent: chunk_index + 1 });
+        }
+
+        next = match chunker
+            .next_chunk()
+            .await
+            .map_err(read_error)?
+        {
+            Some(next) => next,
+            None => return Ok(response),
+        };
+        chunk_index += 1;
+    }
+}
@@ -0,0 +1,275 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Typed app commands and answers
+//!
+//! These traits are usually implemented with the `LedgerCommand` and `LedgerResponse`
+//! derives of `ledger-zondax-derive` (see the `derive` feature)
+
+use crate::{DecodeError, EncodeError};
+
+/// Byte order used when encoding or decoding integers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    /// Most significant byte first
+    Big,
+    /// Least significant byte first
+    Little,
+}
+
+/// A value that can be written into an APDU payload
+pub trait ApduSerialize {
+    /// Append the encoded value to `out`
+    fn write_to(
+        &self,
+        out: &mut Vec<u8>,
+        endianness: Endianness,
+    );
+}
+
+/// A value that can be read from an APDU answer payload
+pub trait ApduDeserialize: Sized {
+    /// Read a value from the front of `data`, advancing it past the consumed bytes
+    ///
+    /// Variable length values (bytes, strings) consume everything that is left
+    fn read_from(
+        data: &mut &[u8],
+        endianness: Endianness,
+    ) -> Result<Self, DecodeError>;
+}
+
+/// Take `len` bytes from the front of `data`
+pub fn take<'a>(
+    data: &mut &'a [u8],
+    len: usize,
+) -> Result<&'a [u8], DecodeError> {
+    if data.len() < len {
+        return Err(DecodeError::TooShort { expected: len, got: data.len() });
+    }
+
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+/// Take a length-prefixed slice from the front of `data`, the prefix being a `u8`
+pub fn take_u8_prefixed<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], DecodeError> {
+    let len = u8::read_from(data, Endianness::Big)?;
+    take(data, len as usize)
+}
+
+/// Take a length-prefixed slice from the front of `data`, the prefix being a `u16`
+pub fn take_u16_prefixed<'a>(
+    data: &mut &'a [u8],
+    endianness: Endianness,
+) -> Result<&'a [u8], DecodeError> {
+    let len = u16::read_from(data, endianness)?;
+    take(data, len as usize)
+}
+
+macro_rules! impl_int {
+    ($($ty:ty),*) => {$(
+        impl ApduSerialize for $ty {
+            fn write_to(&self, out: &mut Vec<u8>, endianness: Endianness) {
+                match endianness {
+                    Endianness::Big => out.extend_from_slice(&self.to_be_bytes()),
+                    Endianness::Little => out.extend_from_slice(&self.to_le_bytes()),
+                }
+            }
+        }
+
+        impl ApduDeserialize for $ty {
+            fn read_from(data: &mut &[u8], endianness: Endianness) -> Result<Self, DecodeError> {
+                let mut bytes = [0u8; std::mem::size_of::<$ty>()];
+                bytes.copy_from_slice(take(data, std::mem::size_of::<$ty>())?);
+
+                Ok(match endianness {
+                    Endianness::Big => <$ty>::from_be_bytes(bytes),
+                    Endianness::Little => <$ty>::from_le_bytes(bytes),
+                })
+            }
+        }
+    )*};
+}
+
+impl_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl ApduSerialize for bool {
+    fn write_to(
+        &self,
+        out: &mut Vec<u8>,
+        _: Endianness,
+    ) {
+        out.push(*self as u8)
+    }
+}
+
+impl ApduDeserialize for bool {
+    fn read_from(
+        data: &mut &[u8],
+        endianness: Endianness,
+    ) -> Result<Self, DecodeError> {
+        u8::read_from(data, endianness).map(|b| b != 0)
+    }
+}
+
+impl<T: ApduSerialize, const N: usize> ApduSerialize for [T; N] {
+    fn write_to(
+        &self,
+        out: &mut Vec<u8>,
+        endianness: Endianness,
+    ) {
+        for item in self {
+            item.write_to(out, endianness)
+        }
+    }
+}
+
+impl<T: ApduDeserialize, const N: usize> ApduDeserialize for [T; N] {
+    fn read_from(
+        data: &mut &[u8],
+        endianness: Endianness,
+    ) -> Result<Self, DecodeError> {
+        let items = (0 .. N)
+            .map(|_| T::read_from(data, endianness))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match items.try_into() {
+            Ok(array) => Ok(array),
+            Err(_) => unreachable!("exactly N items were read"),
+        }
+    }
+}
+
+impl ApduSerialize for [u8] {
+    fn write_to(
+        &self,
+        out: &mut Vec<u8>,
+        _: Endianness,
+    ) {
+        out.extend_from_slice(self)
+    }
+}
+
+impl ApduSerialize for Vec<u8> {
+    fn write_to(
+        &self,
+        out: &mut Vec<u8>,
+        _: Endianness,
+    ) {
+        out.extend_from_slice(self)
+    }
+}
+
+impl ApduDeserialize for Vec<u8> {
+    fn read_from(
+        data: &mut &[u8],
+        _: Endianness,
+    ) -> Result<Self, DecodeError> {
+        let all = take(data, data.len())?;
+        Ok(all.to_vec())
+    }
+}
+
+impl ApduSerialize for str {
+    fn write_to(
+        &self,
+        out: &mut Vec<u8>,
+        _: Endianness,
+    ) {
+        out.extend_from_slice(self.as_bytes())
+    }
+}
+
+impl ApduSerialize for String {
+    fn write_to(
+        &self,
+        out: &mut Vec<u8>,
+        _: Endianness,
+    ) {
+        out.extend_from_slice(self.as_bytes())
+    }
+}
+
+impl ApduDeserialize for String {
+    fn read_from(
+        data: &mut &[u8],
+        _: Endianness,
+    ) -> Result<Self, DecodeError> {
+        let all = take(data, data.len())?;
+        std::str::from_utf8(all)
+            .map(ToString::to_string)
+            .map_err(|_| DecodeError::Utf8)
+    }
+}
+
+impl<T: ApduSerialize + ?Sized> ApduSerialize for &T {
+    fn write_to(
+        &self,
+        out: &mut Vec<u8>,
+        endianness: Endianness,
+    ) {
+        (**self).write_to(out, endianness)
+    }
+}
+
+/// A typed APDU request for an app
+///
+/// The CLA is taken from the [App](crate::App) the command is sent to
+pub trait LedgerCommand {
+    /// Instruction of the command
+    const INS: u8;
+
+    /// Typed answer of the command
+    type Response: LedgerResponse;
+
+    /// First parameter of the instruction
+    fn p1(&self) -> u8 {
+        0
+    }
+
+    /// Second parameter of the instruction
+    fn p2(&self) -> u8 {
+        0
+    }
+
+    /// Payload of the command
+    ///
+    /// When [LedgerCommand::chunked_data] is set, this is the payload of the `Init` chunk
+    fn payload(&self) -> Result<Vec<u8>, EncodeError>;
+
+    /// Data to stream with [AppExt::send_chunks](crate::AppExt::send_chunks) after the `Init` chunk
+    fn chunked_data(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+/// A typed APDU answer, decoded from the answer's payload (without the return code)
+pub trait LedgerResponse: Sized {
+    /// Decode the answer payload
+    fn from_response(data: &[u8]) -> Result<Self, DecodeError>;
+}
+
+impl LedgerResponse for () {
+    fn from_response(_: &[u8]) -> Result<Self, DecodeError> {
+        Ok(())
+    }
+}
+
+impl LedgerResponse for Vec<u8> {
+    fn from_response(data: &[u8]) -> Result<Self, DecodeError> {
+        Ok(data.to_vec())
+    }
+}
@@ -0,0 +1,168 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Dashboard orchestration: moving the device to a given app
+
+use std::time::{Duration, Instant};
+
+use futures_timer::Delay;
+use ledger_transport::Exchange;
+use log::debug;
+
+use crate::{App, AppExt, AppInfo, LedgerAppError};
+
+/// Name reported by [AppExt::get_app_info] when the dashboard is active
+pub const DASHBOARD_NAME: &str = "BOLOS";
+
+/// What the device is currently running, see [AppExt::current_context]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CurrentContext {
+    /// The dashboard is active, with the OS info
+    Dashboard(AppInfo),
+    /// An app is open
+    App(AppInfo),
+    /// The device doesn't support the command: an older dashboard, or an app not implementing it
+    Unknown {
+        /// Status word of the answer, CLA or INS not supported
+        status_word: u16,
+    },
+}
+
+impl CurrentContext {
+    /// Whether the dashboard is active
+    pub fn is_dashboard(&self) -> bool {
+        matches!(self, Self::Dashboard(_))
+    }
+
+    /// Name of the open app, `None` in the dashboard or if unknown
+    pub fn app_name(&self) -> Option<&str> {
+        match self {
+            Self::App(info) => Some(&info.app_name),
+            Self::Dashboard(_) | Self::Unknown { .. } => None,
+        }
+    }
+}
+
+/// Delay between two attempts to reach the device while it re-enumerates, see [wait_for_app]
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Any app, used to send the dashboard level commands
+struct Dashboard;
+
+impl App for Dashboard {
+    const CLA: u8 = 0xe0;
+}
+
+/// Bring the device to the app `name` and return a transport connected to it
+///
+/// Quits the current app if another one is open, opens `name` from the dashboard and waits for it,
+/// reconnecting with `connect` every time the device re-enumerates. The app is confirmed with
+/// [AppExt::current_context]. Each connection is locked while it is used, see [Exchange::lock_session].
+///
+/// Fails with [LedgerAppError::Timeout] if the app isn't open after `timeout`, with the last
+/// connection error if the device couldn't be reached at all. The device is polled every
+/// [DEFAULT_POLL_INTERVAL], see [wait_for_app_with_interval] to change it.
+///
+/// ```ignore
+/// let mut api = HidApi::new()?;
+/// let transport = wait_for_app(
+///     || {
+///         api.refresh_devices()?;
+///         TransportNativeHID::new(&api)
+///     },
+///     "Cosmos",
+///     Duration::from_secs(30),
+/// )
+/// .await?;
+/// ```
+pub async fn wait_for_app<E, C>(
+    connect: C,
+    name: &str,
+    timeout: Duration,
+) -> Result<E, LedgerAppError<E::Error>>
+where
+    E: Exchange + Send + Sync,
+    E::Error: std::error::Error,
+    C: FnMut() -> Result<E, E::Error>,
+{
+    wait_for_app_with_interval(connect, name, timeout, DEFAULT_POLL_INTERVAL).await
+}
+
+/// Same as [wait_for_app], polling the device every `poll_interval`
+pub async fn wait_for_app_with_interval<E, C>(
+    mut connect: C,
+    name: &str,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<E, LedgerAppError<E::Error>>
+where
+    E: Exchange + Send + Sync,
+    E::Error: std::error::Error,
+    C: FnMut() -> Result<E, E::Error>,
+{
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let transport = match connect() {
+            Ok(transport) => transport,
+            Err(err) if Instant::now() >= deadline => return Err(LedgerAppError::TransportError(err)),
+            Err(err) => {
+                debug!("device not reachable yet: {}", err);
+                Delay::new(poll_interval).await;
+                continue;
+            },
+        };
+
+        match step(&transport, name).await {
+            Ok(true) => return Ok(transport),
+            // the device is re-enumerating, which can break the exchange midway
+            Ok(false) | Err(LedgerAppError::TransportError(_)) => {},
+            Err(err) => return Err(err),
+        }
+
+        drop(transport);
+        if Instant::now() >= deadline {
+            return Err(LedgerAppError::Timeout);
+        }
+        Delay::new(poll_interval).await;
+    }
+}
+
+/// Move the device one step towards the app `name`, `true` once it is open
+async fn step<E>(
+    transport: &E,
+    name: &str,
+) -> Result<bool, LedgerAppError<E::Error>>
+where
+    E: Exchange + Send + Sync,
+    E::Error: std::error::Error,
+{
+    let session = transport.lock_session().await?;
+
+    match Dashboard::current_context(&session).await? {
+        CurrentContext::App(info) if info.app_name == name => return Ok(true),
+        // most likely an older dashboard, an app would fail to open another one
+        CurrentContext::Dashboard(_) | CurrentContext::Unknown { .. } => {
+            debug!("opening {}", name);
+            Dashboard::open_app(&session, name).await?
+        },
+        CurrentContext::App(info) => {
+            debug!("quitting {}", info.app_name);
+            Dashboard::quit_app(&session).await?
+        },
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,247 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! BIP44 account discovery, see [AppExt::discover_accounts](crate::AppExt::discover_accounts)
+
+use std::{collections::HashMap, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AddressInfo, AddressLayout, DerivationPath, LedgerAppError};
+
+/// An address checked during discovery
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DiscoveredAddress {
+    /// Path of the address
+    pub path: DerivationPath,
+    /// Public key and address returned by the app
+    pub info: AddressInfo,
+}
+
+/// Progress of a discovery, reported after each address is checked
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiscoveryProgress<'a> {
+    /// Path of the address just checked
+    pub path: &'a DerivationPath,
+    /// Whether the address is used
+    pub used: bool,
+    /// Addresses checked so far
+    pub scanned: usize,
+    /// Used addresses found so far
+    pub found: usize,
+}
+
+/// Position of a discovery, can be saved to resume it later
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DiscoveryState {
+    /// Account being scanned
+    pub account: u32,
+    /// Position of the change being scanned in [AccountDiscovery::changes]
+    pub change: usize,
+    /// Next address index to check
+    pub index: u32,
+    /// Consecutive unused addresses found on the current chain
+    pub gap: u32,
+    /// Whether a used address was found in the current account
+    pub account_used: bool,
+    /// Addresses checked so far
+    pub scanned: usize,
+    /// Used addresses found so far
+    pub used: Vec<DiscoveredAddress>,
+    /// Whether the discovery is complete
+    pub finished: bool,
+}
+
+/// BIP44 account discovery
+///
+/// Walks `m/44'/coin_type'/account'/change/index`, moving to the next chain after `gap_limit`
+/// consecutive unused addresses and stopping at the first account without any used address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountDiscovery {
+    /// Coin type of the scanned paths
+    pub coin_type: u32,
+    /// Consecutive unused addresses ending a chain
+    pub gap_limit: u32,
+    /// Changes scanned in every account
+    pub changes: Vec<u32>,
+    /// Accounts scanned at most
+    pub max_accounts: u32,
+    /// Current position
+    pub state: DiscoveryState,
+}
+
+impl AccountDiscovery {
+    /// Scan the external and internal chains of `coin_type` with the given gap limit
+    pub fn new(
+        coin_type: u32,
+        gap_limit: u32,
+    ) -> Self {
+        Self { coin_type, gap_limit, changes: vec![0, 1], max_accounts: u32::MAX, state: DiscoveryState::default() }
+    }
+
+    /// Scan only the given changes, e.g. `&[0]` for apps without change addresses
+    pub fn with_changes(
+        mut self,
+        changes: &[u32],
+    ) -> Self {
+        self.changes = changes.to_vec();
+        self
+    }
+
+    /// Scan at most `max_accounts` accounts
+    pub fn with_max_accounts(
+        mut self,
+        max_accounts: u32,
+    ) -> Self {
+        self.max_accounts = max_accounts;
+        self
+    }
+
+    /// Resume from a previously saved position
+    ///
+    /// A state that doesn't match the discovery, e.g. saved with more changes, fails the discovery with
+    /// [LedgerAppError::InvalidDiscoveryState]
+    pub fn resume(
+        mut self,
+        state: DiscoveryState,
+    ) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Whether the discovery is complete
+    pub fn is_finished(&self) -> bool {
+        self.state.finished
+    }
+
+    /// Used addresses found so far
+    pub fn used(&self) -> &[DiscoveredAddress] {
+        &self.state.used
+    }
+
+    /// Next path to check, `None` once finished. Moves to the next chain or account as needed
+    pub(crate) fn next_path<E: std::error::Error>(&mut self) -> Result<Option<DerivationPath>, LedgerAppError<E>> {
+        let state = &mut self.state;
+        while !state.finished {
+            if state.account >= self.max_accounts || self.changes.is_empty() {
+                state.finished = true;
+                break;
+            }
+
+            let Some(&change) = self.changes.get(state.change) else {
+                return Err(LedgerAppError::InvalidDiscoveryState {
+                    change: state.change,
+                    changes: self.changes.len(),
+                });
+            };
+            if state.gap < self.gap_limit {
+                return DerivationPath::bip44(self.coin_type, state.account, change, state.index)
+                    .map(Some)
+                    .map_err(LedgerAppError::InvalidDerivationPath);
+            }
+
+            state.change += 1;
+            state.index = 0;
+            state.gap = 0;
+            if state.change == self.changes.len() {
+                if !state.account_used {
+                    state.finished = true;
+                    break;
+                }
+                state.account += 1;
+                state.change = 0;
+                state.account_used = false;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Record the result of the address at [AccountDiscovery::next_path]
+    pub(crate) fn record(
+        &mut self,
+        address: DiscoveredAddress,
+        used: bool,
+    ) {
+        let state = &mut self.state;
+        if used {
+            state.gap = 0;
+            state.account_used = true;
+            state.used.push(address);
+        } else {
+            state.gap += 1;
+        }
+        state.index += 1;
+        state.scanned += 1;
+    }
+}
+
+/// Addresses already retrieved from devices, to avoid asking them again on rescans
+///
+/// Entries are keyed by a device identifier chosen by the caller, which must tell apart
+/// devices with different seeds, e.g. a serial number or the first address of the wallet
+#[derive(Debug, Default)]
+pub struct AddressCache {
+    entries: Mutex<HashMap<(String, u8, u8, DerivationPath), AddressInfo>>,
+}
+
+impl AddressCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cached address of the app with class `cla` at `path` on `device`
+    pub fn get(
+        &self,
+        device: &str,
+        cla: u8,
+        layout: &AddressLayout,
+        path: &DerivationPath,
+    ) -> Option<AddressInfo> {
+        self.entries
+            .lock()
+            .expect("address cache poisoned")
+            .get(&(device.to_string(), cla, layout.ins, path.clone()))
+            .cloned()
+    }
+
+    /// Remember the address of the app with class `cla` at `path` on `device`
+    pub fn insert(
+        &self,
+        device: &str,
+        cla: u8,
+        layout: &AddressLayout,
+        path: DerivationPath,
+        info: AddressInfo,
+    ) {
+        self.entries
+            .lock()
+            .expect("address cache poisoned")
+            .insert((device.to_string(), cla, layout.ins, path), info);
+    }
+
+    /// Number of cached addresses
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .expect("address cache poisoned")
+            .len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -13,9 +13,12 @@
 *  See the License for the specific language governing permissions and
 *  limitations under the License.
 ********************************************************************************/
+use ledger_transport::{TransportError, TransportErrorKind};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::Version;
+
 /// App Error
 #[derive(Clone, Debug, Eq, Error, PartialEq, Deserialize, Serialize)]
 pub enum LedgerAppError<E: std::error::Error> {
@@ -41,8 +44,8 @@ pub enum LedgerAppError<E: std::error::Error> {
     #[error("received an invalid signature")]
     InvalidSignature,
     /// The derivation is invalid
-    #[error("invalid derivation path")]
-    InvalidDerivationPath,
+    #[error("invalid derivation path | {0}")]
+    InvalidDerivationPath(PathError),
     /// The derivation is invalid
     #[error("Transport | {0}")]
     TransportError(#[from] E),
@@ -61,7 +64,183 @@ pub enum LedgerAppError<E: std::error::Error> {
     /// Application specific error
     #[error("App Error: | {0} {1}")]
     AppSpecific(u16, String),
+    /// Status word defined by the app, see [App::STATUS_WORDS](crate::App::STATUS_WORDS)
+    #[error("App Error: | 0x{code:04X} {name}: {description}")]
+    AppStatus {
+        /// Raw status word
+        code: u16,
+        /// Name of the status word in the app table
+        name: String,
+        /// Description of the status word in the app table
+        description: String,
+    },
     ///Unknown error has occurred
     #[error("Unknown error: {0}")]
     Unknown(u16),
+    /// A chunk of a chunked message was rejected by the app
+    // `{error}` would make thiserror require `Box<Self>: Display` to implement Display
+    #[error("chunk {index} rejected with 0x{retcode:04X} | {}", .error)]
+    ChunkRejected {
+        /// Index of the rejected chunk (0-based, not counting the init APDU)
+        index: usize,
+        /// Status word returned for the chunk
+        retcode: u16,
+        /// Error the status word was mapped to
+        error: Box<LedgerAppError<E>>,
+    },
+    /// A chunked message upload was cancelled between two chunks
+    #[error("upload cancelled after {chunks_sent} chunks")]
+    Cancelled {
+        /// Number of chunks accepted by the app before cancelling
+        chunks_sent: usize,
+    },
+    /// The app to open is not installed on the device
+    #[error("app `{0}` is not installed")]
+    AppNotInstalled(String),
+    /// Another app than the expected one is open
+    #[error("expected app {expected:?}, found `{actual}`")]
+    WrongApp {
+        /// Names accepted for the app
+        expected: Vec<String>,
+        /// Name of the open app
+        actual: String,
+    },
+    /// The open app version is not supported
+    #[error("app version {actual} is not supported, requires {required}")]
+    UnsupportedVersion {
+        /// Semantic version requirement of the supported versions
+        required: String,
+        /// Version of the open app
+        actual: Version,
+    },
+    /// The supported versions are not a valid semantic version requirement
+    #[error("invalid version requirement | {0}")]
+    InvalidVersionRequirement(VersionParseError),
+    /// A resumed discovery state doesn't match the discovery
+    #[error("discovery state is at change {change}, only {changes} changes are scanned")]
+    InvalidDiscoveryState {
+        /// Position of the change in the state
+        change: usize,
+        /// Number of changes scanned by the discovery
+        changes: usize,
+    },
+    /// The address usage check of a discovery failed
+    #[error("address usage check failed | {0}")]
+    UsageCheck(String),
+    /// The user refused the request on the device
+    #[error("rejected by the user")]
+    UserRejected,
+    /// The operation didn't complete in time
+    #[error("timed out")]
+    Timeout,
+    /// The message to send in chunks could not be read
+    #[error("message source error | {0}")]
+    MessageSource(String),
+    /// The answer payload could not be decoded
+    #[error("Invalid response | {0}")]
+    InvalidResponse(DecodeError),
+    /// A chunked command sets a parameter the chunking scheme overwrites
+    #[error("{param} of a chunked command is 0x{value:02X}, but the chunking scheme overwrites it")]
+    ChunkParamOverwritten {
+        /// `P1` or `P2`
+        param: String,
+        /// Value set by the command
+        value: u8,
+    },
+    /// The command payload could not be encoded
+    #[error("Invalid command | {0}")]
+    InvalidCommand(EncodeError),
+}
+
+impl<E: std::error::Error> LedgerAppError<E> {
+    /// Raw status word returned by the device, if the error comes from one
+    pub fn status_word(&self) -> Option<u16> {
+        match self {
+            Self::AppSpecific(code, _) | Self::Unknown(code) | Self::AppStatus { code, .. } => Some(*code),
+            Self::ChunkRejected { retcode, .. } => Some(*retcode),
+            _ => None,
+        }
+    }
+}
+
+impl<E: TransportError> LedgerAppError<E> {
+    /// Category of the error, if it comes from the transport
+    ///
+    /// Lets callers react to e.g. [TransportErrorKind::Disconnected] whichever transport is used
+    pub fn transport_error_kind(&self) -> Option<TransportErrorKind> {
+        match self {
+            Self::TransportError(err) => Some(err.kind()),
+            _ => None,
+        }
+    }
+}
+
+/// Error decoding an APDU answer payload
+#[derive(Clone, Debug, Eq, Error, PartialEq, Deserialize, Serialize)]
+pub enum DecodeError {
+    /// Not enough bytes left
+    #[error("expected {expected} more bytes, only {got} left")]
+    TooShort {
+        /// Bytes needed
+        expected: usize,
+        /// Bytes available
+        got: usize,
+    },
+    /// Bytes left after decoding the whole answer
+    #[error("{0} unexpected trailing bytes")]
+    TrailingBytes(usize),
+    /// Utf8 related errors
+    #[error("Utf8 conversion error")]
+    Utf8,
+}
+
+/// Error encoding an APDU command payload
+#[derive(Clone, Debug, Eq, Error, PartialEq, Deserialize, Serialize)]
+pub enum EncodeError {
+    /// A field doesn't fit its length prefix
+    #[error("field of {len} bytes is longer than its length prefix allows ({max})")]
+    FieldTooLong {
+        /// Field length
+        len: usize,
+        /// Largest length the prefix can hold
+        max: usize,
+    },
+}
+
+/// A version or version requirement string could not be parsed
+#[derive(Clone, Debug, Eq, Error, PartialEq, Deserialize, Serialize)]
+#[error("invalid version `{0}`")]
+pub struct VersionParseError(pub String);
+
+/// Invalid derivation path, usually reported to callers as [LedgerAppError::InvalidDerivationPath]
+#[derive(Clone, Debug, Eq, Error, PartialEq, Deserialize, Serialize)]
+pub enum PathError {
+    /// A component is not a valid index
+    #[error("invalid path component `{0}`")]
+    InvalidComponent(String),
+    /// More components than Ledger apps accept
+    #[error("path has {0} components, more than allowed")]
+    TooDeep(usize),
+    /// Unexpected number of components
+    #[error("expected {expected} path components, got {got}")]
+    WrongDepth {
+        /// Components required
+        expected: usize,
+        /// Components in the path
+        got: usize,
+    },
+    /// A component is too large to be hardened or not
+    #[error("path component {0} is out of range")]
+    OutOfRange(u32),
+    /// A component that must be hardened is not
+    #[error("path component {0} must be hardened")]
+    NotHardened(usize),
+    /// The coin type is not the expected one
+    #[error("expected coin type {expected}, got {got:?}")]
+    WrongCoinType {
+        /// Coin type required
+        expected: u32,
+        /// Coin type of the path
+        got: Option<u32>,
+    },
 }
@@ -0,0 +1,103 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! BOLOS flags reported by the device and app info commands
+
+use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
+
+use crate::DeviceInfo;
+
+/// Target ID high nibble of a device running its firmware, other values come from the MCU bootloader
+const TARGET_ID_FIRMWARE: u8 = 0x30;
+
+bitflags! {
+    /// BOLOS state flags
+    ///
+    /// Reported by [AppExt::get_device_info](crate::AppExt::get_device_info) in the dashboard.
+    /// Unknown bits set by newer firmwares are kept as is
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+    #[serde(transparent)]
+    pub struct DeviceFlags: u32 {
+        /// The device is in recovery mode
+        const RECOVERY = 0x01;
+        /// The MCU code is signed
+        const SIGNED_MCU_CODE = 0x02;
+        /// The device is onboarded
+        const ONBOARDED = 0x04;
+        /// The Ledger Manager is allowed to connect
+        const MANAGER_ALLOWED = 0x08;
+        /// A custom certificate authority is trusted
+        const TRUST_CUSTOM_CA = 0x10;
+        /// The HSM has been initialized
+        const HSM_INITIALIZED = 0x20;
+        /// The factory provisioning is done
+        const FACTORY_FILLED = 0x40;
+        /// The PIN has been validated
+        const PIN_VALIDATED = 0x80;
+    }
+}
+
+bitflags! {
+    /// OS flags seen by an app
+    ///
+    /// Reported by [AppExt::get_app_info](crate::AppExt::get_app_info) in apps.
+    /// Unknown bits set by newer firmwares are kept as is
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+    #[serde(transparent)]
+    pub struct AppFlags: u32 {
+        /// The device is in recovery mode
+        const RECOVERY = 0x01;
+        /// The MCU code is signed
+        const SIGNED_MCU_CODE = 0x02;
+        /// The device is onboarded
+        const ONBOARDED = 0x04;
+        /// The PIN has been validated
+        const PIN_VALIDATED = 0x80;
+    }
+}
+
+impl DeviceFlags {
+    /// Decode the flags field of an answer, stored least significant byte first
+    ///
+    /// Bytes past the 4th are ignored
+    pub fn from_le_slice(bytes: &[u8]) -> Self {
+        Self::from_bits_retain(le_u32(bytes))
+    }
+}
+
+impl AppFlags {
+    /// Decode the flags field of an answer, stored least significant byte first
+    ///
+    /// Bytes past the 4th are ignored
+    pub fn from_le_slice(bytes: &[u8]) -> Self {
+        Self::from_bits_retain(le_u32(bytes))
+    }
+}
+
+impl DeviceInfo {
+    /// Whether the answer comes from the MCU bootloader rather than from the firmware, told by the target ID
+    pub fn is_bootloader(&self) -> bool {
+        self.target_id[0] & 0xf0 != TARGET_ID_FIRMWARE
+    }
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    let mut value = [0u8; 4];
+    let len = std::cmp::min(bytes.len(), value.len());
+    value[.. len].copy_from_slice(&bytes[.. len]);
+
+    u32::from_le_bytes(value)
+}
@@ -0,0 +1,50 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Checks that the expected app is open before talking to it
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Remembers a successful [AppExt::ensure_app](crate::AppExt::ensure_app) check
+///
+/// Keep one per transport and app, and create a new one when reconnecting
+/// since another app may have been opened in the meantime
+#[derive(Debug, Default)]
+pub struct AppCheckCache {
+    verified: AtomicBool,
+}
+
+impl AppCheckCache {
+    /// Create an empty cache
+    pub const fn new() -> Self {
+        Self { verified: AtomicBool::new(false) }
+    }
+
+    /// Whether the app has already been checked
+    pub fn is_verified(&self) -> bool {
+        self.verified.load(Ordering::Acquire)
+    }
+
+    /// Forget the previous check, the next one will query the device again
+    pub fn invalidate(&self) {
+        self.verified
+            .store(false, Ordering::Release)
+    }
+
+    pub(crate) fn set_verified(&self) {
+        self.verified
+            .store(true, Ordering::Release)
+    }
+}
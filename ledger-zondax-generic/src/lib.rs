@@ -21,20 +21,46 @@
 #![deny(unused_import_braces, unused_qualifications)]
 #![deny(missing_docs)]
 
+pub mod address;
+pub mod chunking;
+pub mod command;
+pub mod dashboard;
+pub mod discovery;
 mod errors;
-use std::str;
-
+mod flags;
+pub mod guard;
+pub mod path;
+pub mod session;
+pub mod signature;
+pub mod source;
+pub mod status;
+#[cfg(test)]
+mod tests;
+mod version;
+use std::{ops::ControlFlow, str};
+
+pub use address::{AddressInfo, AddressLayout, PublicKey};
 use async_trait::async_trait;
+pub use chunking::{ChunkHeader, ChunkProgress, Chunking, ChunkingScheme, CounterChunking, P2Chunking, ZondaxChunking};
+pub use command::{LedgerCommand, LedgerResponse};
+pub use dashboard::{wait_for_app, wait_for_app_with_interval, CurrentContext};
+pub use discovery::{AccountDiscovery, AddressCache, DiscoveredAddress, DiscoveryProgress, DiscoveryState};
 pub use errors::*;
-use ledger_transport::{APDUAnswer, APDUCommand, APDUErrorCode, Exchange};
+pub use flags::{AppFlags, DeviceFlags};
+pub use guard::AppCheckCache;
+use ledger_transport::{APDUAnswer, APDUCommand, Exchange};
+#[cfg(feature = "derive")]
+pub use ledger_zondax_derive::{LedgerCommand, LedgerResponse};
+pub use path::DerivationPath;
+pub use semver;
 use serde::{Deserialize, Serialize};
-
-const INS_GET_VERSION: u8 = 0x00;
-const CLA_APP_INFO: u8 = 0xb0;
-const INS_APP_INFO: u8 = 0x01;
-const CLA_DEVICE_INFO: u8 = 0xe0;
-const INS_DEVICE_INFO: u8 = 0x01;
-const USER_MESSAGE_CHUNK_SIZE: usize = 250;
+pub use session::{AppConfig, AppSession};
+pub use signature::{SignLayout, Signature, SignatureFormat};
+pub use source::{IterSource, MessageSource};
+#[cfg(feature = "stream")]
+pub use source::{ReaderSource, StreamSource};
+pub use status::StatusWord;
+pub use version::parse_version;
 
 /// Chunk payload type
 pub enum ChunkPayloadType {
@@ -65,6 +91,7 @@ pub struct Version {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
 /// App Information
 pub struct AppInfo {
     /// Name of the application
@@ -91,9 +118,13 @@ pub struct AppInfo {
     /// Flag Pin Validated
     #[serde(rename(serialize = "flagsPINValidated"))]
     pub flag_pin_validated: bool,
+    /// All flags
+    #[serde(default)]
+    pub flags: AppFlags,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
 /// App Device Info
 pub struct DeviceInfo {
     /// Target ID
@@ -104,21 +135,51 @@ pub struct DeviceInfo {
     pub se_version: String,
     /// Device Flag
     pub flag: Vec<u8>,
+    /// Device flags, decoded from [DeviceInfo::flag]
+    #[serde(default)]
+    pub flags: DeviceFlags,
     /// MCU Version
     #[serde(rename(serialize = "mcuVersion"))]
     pub mcu_version: String,
+    /// MCU bootloader version, not returned by older firmwares
+    #[serde(default, rename(serialize = "mcuBootloaderVersion"))]
+    pub mcu_bootloader_version: Option<String>,
+    /// Hardware revision, not returned by older firmwares
+    #[serde(default, rename(serialize = "hardwareVersion"))]
+    pub hardware_version: Option<Vec<u8>>,
+    /// Language pack identifier, not returned by older firmwares
+    #[serde(default, rename(serialize = "languageId"))]
+    pub language_id: Option<u8>,
 }
 
 /// Defines what we can consider an "App"
 pub trait App {
     /// App's APDU CLA
     const CLA: u8;
+
+    /// Payload size of each chunk sent by [AppExt::send_chunks], at most [chunking::MAX_CHUNK_SIZE]
+    const CHUNK_SIZE: usize = chunking::DEFAULT_CHUNK_SIZE;
+
+    /// Chunking scheme of [AppExt::send_chunks] and of the chunked commands
+    const CHUNKING: Chunking = Chunking::Zondax;
+
+    /// Names the app can be reported as by [AppExt::get_app_info], any name is accepted if empty
+    const APP_NAMES: &'static [&'static str] = &[];
+
+    /// Semantic version requirement of the app versions supported by this client, e.g. `">=2.30, <3"`
+    ///
+    /// Any version is accepted if `None`
+    const SUPPORTED_VERSIONS: Option<&'static str> = None;
+
+    /// Status words defined by the app, reported as [LedgerAppError::AppStatus]
+    const STATUS_WORDS: &'static [StatusWord] = &[];
 }
 
 #[async_trait]
 /// Common commands for any given APP
 ///
-/// This trait is automatically implemented for any type that implements [App]
+/// This trait is automatically implemented for any type that implements [App].
+/// Every command is forwarded to an [AppSession] configured from the [App] constants
 pub trait AppExt<E>: App
 where
     E: Exchange + Send + Sync,
@@ -132,11 +193,7 @@ where
     /// # Returns
     /// A result indicating success or containing a specific ledger application error.
     fn handle_response_error(response: &APDUAnswer<E::AnswerType>) -> Result<(), LedgerAppError<E::Error>> {
-        match response.error_code() {
-            Ok(APDUErrorCode::NoError) => Ok(()),
-            Ok(err) => Err(LedgerAppError::AppSpecific(err as _, err.description())),
-            Err(err) => Err(LedgerAppError::Unknown(err)),
-        }
+        session::check_response(response, Self::STATUS_WORDS)
     }
 
     /// Handles the error response from APDU exchange.
@@ -147,178 +204,144 @@ where
     /// # Returns
     /// A result indicating success or containing a specific ledger application error.
     fn handle_response_error_signature(response: &APDUAnswer<E::AnswerType>) -> Result<(), LedgerAppError<E::Error>> {
-        match response.error_code() {
-            Ok(APDUErrorCode::NoError) if response.data().is_empty() => Err(LedgerAppError::NoSignature),
-            Ok(APDUErrorCode::NoError) => Ok(()),
-            Ok(err) => Err(LedgerAppError::AppSpecific(err as _, err.description())),
-            Err(err) => Err(LedgerAppError::AppSpecific(err, "[APDU_ERROR] Unknown".to_string())),
-        }
+        session::check_signature_response(response, Self::STATUS_WORDS)
     }
 
-    /// Retrieve the device info
+    /// Retrieve the device info, see [AppSession::get_device_info]
     ///
     /// Works only in the dashboard
     async fn get_device_info(transport: &E) -> Result<DeviceInfo, LedgerAppError<E::Error>> {
-        let command = APDUCommand { cla: CLA_DEVICE_INFO, ins: INS_DEVICE_INFO, p1: 0x00, p2: 0x00, data: Vec::new() };
-
-        let response = transport.exchange(&command).await?;
-        match response.error_code() {
-            Ok(APDUErrorCode::NoError) => {},
-            Ok(err) => return Err(LedgerAppError::Unknown(err as _)),
-            Err(err) => return Err(LedgerAppError::Unknown(err)),
-        }
-
-        let response_data = response.data();
-
-        let target_id_slice = &response_data[0 .. 4];
-        let mut idx = 4;
-        let se_version_len: usize = response_data[idx] as usize;
-        idx += 1;
-        let se_version_bytes = &response_data[idx .. idx + se_version_len];
-
-        idx += se_version_len;
-
-        let flags_len: usize = response_data[idx] as usize;
-        idx += 1;
-        let flag = &response_data[idx .. idx + flags_len];
-        idx += flags_len;
-
-        let mcu_version_len: usize = response_data[idx] as usize;
-        idx += 1;
-        let mut tmp = &response_data[idx .. idx + mcu_version_len];
-        if tmp[mcu_version_len - 1] == 0 {
-            tmp = &response_data[idx .. idx + mcu_version_len - 1];
-        }
-
-        let mut target_id = [Default::default(); 4];
-        target_id.copy_from_slice(target_id_slice);
-
-        let se_version = str::from_utf8(se_version_bytes).map_err(|_e| LedgerAppError::Utf8)?;
-        let mcu_version = str::from_utf8(tmp).map_err(|_e| LedgerAppError::Utf8)?;
-
-        let device_info = DeviceInfo {
-            target_id,
-            se_version: se_version.to_string(),
-            flag: flag.to_vec(),
-            mcu_version: mcu_version.to_string(),
-        };
-
-        Ok(device_info)
+        AppSession::for_app::<Self>(transport)
+            .get_device_info()
+            .await
     }
 
-    /// Retrieve the app info
-    ///
-    /// Works only in app (TOOD: dashboard support)
+    /// Retrieve the app info, see [AppSession::get_app_info]
     async fn get_app_info(transport: &E) -> Result<AppInfo, LedgerAppError<E::Error>> {
-        let command = APDUCommand { cla: CLA_APP_INFO, ins: INS_APP_INFO, p1: 0x00, p2: 0x00, data: Vec::new() };
-
-        let response = transport.exchange(&command).await?;
-        match response.error_code() {
-            Ok(APDUErrorCode::NoError) => {},
-            Ok(err) => return Err(LedgerAppError::AppSpecific(err as _, err.description())),
-            Err(err) => return Err(LedgerAppError::Unknown(err as _)),
-        }
-
-        let response_data = response.data();
-
-        if response_data[0] != 1 {
-            return Err(LedgerAppError::InvalidFormatID);
-        }
-
-        let app_name_len: usize = response_data[1] as usize;
-        let app_name_bytes = &response_data[2 .. app_name_len];
-
-        let mut idx = 2 + app_name_len;
-        let app_version_len: usize = response_data[idx] as usize;
-        idx += 1;
-        let app_version_bytes = &response_data[idx .. idx + app_version_len];
-
-        idx += app_version_len;
-
-        let app_flags_len = response_data[idx];
-        idx += 1;
-        let flags_value = response_data[idx];
-
-        let app_name = str::from_utf8(app_name_bytes).map_err(|_e| LedgerAppError::Utf8)?;
-        let app_version = str::from_utf8(app_version_bytes).map_err(|_e| LedgerAppError::Utf8)?;
-
-        let app_info = AppInfo {
-            app_name: app_name.to_string(),
-            app_version: app_version.to_string(),
-            flag_len: app_flags_len,
-            flags_value,
-            flag_recovery: (flags_value & 1) != 0,
-            flag_signed_mcu_code: (flags_value & 2) != 0,
-            flag_onboarded: (flags_value & 4) != 0,
-            flag_pin_validated: (flags_value & 128) != 0,
-        };
-
-        Ok(app_info)
+        AppSession::for_app::<Self>(transport)
+            .get_app_info()
+            .await
+    }
+
+    /// Tell whether the dashboard or an app is open, and which one
+    async fn current_context(transport: &E) -> Result<CurrentContext, LedgerAppError<E::Error>> {
+        AppSession::for_app::<Self>(transport)
+            .current_context()
+            .await
+    }
+
+    /// Open the app with the given name, see [AppSession::open_app]
+    async fn open_app(
+        transport: &E,
+        name: &str,
+    ) -> Result<(), LedgerAppError<E::Error>> {
+        AppSession::for_app::<Self>(transport)
+            .open_app(name)
+            .await
+    }
+
+    /// Quit the currently open app, see [AppSession::quit_app]
+    async fn quit_app(transport: &E) -> Result<(), LedgerAppError<E::Error>> {
+        AppSession::for_app::<Self>(transport)
+            .quit_app()
+            .await
     }
 
     /// Retrieve the app version
     async fn get_version(transport: &E) -> Result<Version, LedgerAppError<E::Error>> {
-        let command = APDUCommand { cla: Self::CLA, ins: INS_GET_VERSION, p1: 0x00, p2: 0x00, data: Vec::new() };
-
-        let response = transport.exchange(&command).await?;
-        match response.error_code() {
-            Ok(APDUErrorCode::NoError) => {},
-            Ok(err) => return Err(LedgerAppError::Unknown(err as _)),
-            Err(err) => return Err(LedgerAppError::Unknown(err)),
-        }
-
-        let response_data = response.data();
-
-        let version = match response_data.len() {
-            // single byte version numbers
-            4 => Version {
-                mode: response_data[0],
-                major: response_data[1] as u16,
-                minor: response_data[2] as u16,
-                patch: response_data[3] as u16,
-                locked: false,
-                target_id: [0, 0, 0, 0],
-            },
-            // double byte version numbers
-            7 => Version {
-                mode: response_data[0],
-                major: response_data[1] as u16 * 256 + response_data[2] as u16,
-                minor: response_data[3] as u16 * 256 + response_data[4] as u16,
-                patch: response_data[5] as u16 * 256 + response_data[6] as u16,
-                locked: false,
-                target_id: [0, 0, 0, 0],
-            },
-            // double byte version numbers + lock + target id
-            9 => Version {
-                mode: response_data[0],
-                major: response_data[1] as u16,
-                minor: response_data[2] as u16,
-                patch: response_data[3] as u16,
-                locked: response_data[4] != 0,
-                target_id: [response_data[5], response_data[6], response_data[7], response_data[8]],
-            },
-            // double byte version numbers + lock + target id
-            12 => Version {
-                mode: response_data[0],
-                major: response_data[1] as u16 * 256 + response_data[2] as u16,
-                minor: response_data[3] as u16 * 256 + response_data[4] as u16,
-                patch: response_data[5] as u16 * 256 + response_data[6] as u16,
-                locked: response_data[7] != 0,
-                target_id: [response_data[8], response_data[9], response_data[10], response_data[11]],
-            },
-            _ => return Err(LedgerAppError::InvalidVersion),
-        };
-        Ok(version)
+        AppSession::for_app::<Self>(transport)
+            .get_version()
+            .await
+    }
+
+    /// Check that the open app is this one, see [App::APP_NAMES] and [App::SUPPORTED_VERSIONS]
+    ///
+    /// Fails with [LedgerAppError::WrongApp] or [LedgerAppError::UnsupportedVersion]
+    async fn ensure_app(transport: &E) -> Result<(), LedgerAppError<E::Error>> {
+        AppSession::for_app::<Self>(transport)
+            .ensure_app()
+            .await
+    }
+
+    /// Same as [AppExt::ensure_app], querying the device only until a check succeeds
+    async fn ensure_app_cached(
+        transport: &E,
+        cache: &AppCheckCache,
+    ) -> Result<(), LedgerAppError<E::Error>> {
+        AppSession::for_app::<Self>(transport)
+            .ensure_app_cached(cache)
+            .await
+    }
+
+    /// Retrieve the public key and address at `path`, see [AppSession::get_pubkey]
+    async fn get_pubkey(
+        transport: &E,
+        layout: &AddressLayout,
+        path: &DerivationPath,
+        show: bool,
+    ) -> Result<AddressInfo, LedgerAppError<E::Error>> {
+        AppSession::for_app::<Self>(transport)
+            .get_pubkey(layout, path, show)
+            .await
+    }
+
+    /// Run a BIP44 account discovery through the app's address command, see [AppSession::discover_accounts]
+    async fn discover_accounts<F, Fut, P>(
+        transport: &E,
+        layout: &AddressLayout,
+        discovery: &mut AccountDiscovery,
+        cache: &AddressCache,
+        device: &str,
+        is_used: F,
+        progress: P,
+    ) -> Result<(), LedgerAppError<E::Error>>
+    where
+        F: FnMut(DiscoveredAddress) -> Fut + Send,
+        Fut: std::future::Future<Output = Result<bool, Box<dyn std::error::Error + Send + Sync>>> + Send,
+        P: FnMut(&DiscoveryProgress<'_>) -> ControlFlow<()> + Send,
+    {
+        AppSession::for_app::<Self>(transport)
+            .discover_accounts(layout, discovery, cache, device, is_used, progress)
+            .await
+    }
+
+    /// Sign `message` with the key at `path`, see [AppSession::sign]
+    async fn sign(
+        transport: &E,
+        layout: &SignLayout,
+        path: &DerivationPath,
+        message: &[u8],
+    ) -> Result<Signature, LedgerAppError<E::Error>> {
+        AppSession::for_app::<Self>(transport)
+            .sign(layout, path, message)
+            .await
+    }
+
+    /// Same as [AppExt::sign], checking the signature against `public_key` on the host
+    ///
+    /// ECDSA signatures are checked over the `digest` of the message, see [Signature::verify].
+    /// A signature that doesn't match is reported as [LedgerAppError::InvalidSignature]
+    #[cfg(feature = "verify")]
+    async fn sign_verified(
+        transport: &E,
+        layout: &SignLayout,
+        path: &DerivationPath,
+        message: &[u8],
+        public_key: &PublicKey,
+        digest: signature::MessageDigest,
+    ) -> Result<Signature, LedgerAppError<E::Error>> {
+        AppSession::for_app::<Self>(transport)
+            .sign_verified(layout, path, message, public_key, digest)
+            .await
     }
 
-    /// Stream a long request in chunks, with an option to set P2 for all chunks.
-    /// This method combines the functionality of sending chunks with or without P2 set for all chunks.
+    /// Stream a long request in chunks, using the [App::CHUNKING] scheme with [App::CHUNK_SIZE]
     ///
     /// # Arguments
     /// * `transport` - The transport layer used for communication.
-    /// * `command` - The initial APDU command.
+    /// * `command` - The initial APDU command, its header must be the init one of the scheme
+    ///   (e.g. P1 is [ChunkPayloadType::Init] with [ZondaxChunking]).
     /// * `message` - The message to be sent in chunks.
-    /// * `set_p2_for_all` - A boolean to determine if P2 should be set for all chunks.
     ///
     /// # Returns
     /// A result containing the final APDU answer or a ledger application error.
@@ -327,36 +350,150 @@ where
         command: APDUCommand<I>,
         message: &[u8],
     ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>> {
-        let chunks = message.chunks(USER_MESSAGE_CHUNK_SIZE);
-        match chunks.len() {
-            0 => return Err(LedgerAppError::InvalidEmptyMessage),
-            n if n > 255 => return Err(LedgerAppError::InvalidMessageSize),
-            _ => (),
-        }
-
-        if command.p1 != ChunkPayloadType::Init as u8 {
-            return Err(LedgerAppError::InvalidChunkPayloadType);
-        }
-
-        let mut response = transport.exchange(&command).await?;
-        Self::handle_response_error(&response)?;
-
-        // Send message chunks
-        let last_chunk_index = chunks.len() - 1;
-        for (packet_idx, chunk) in chunks.enumerate() {
-            let mut p1 = ChunkPayloadType::Add as u8;
-            if packet_idx == last_chunk_index {
-                p1 = ChunkPayloadType::Last as u8;
-            }
-
-            let command = APDUCommand { cla: command.cla, ins: command.ins, p1, p2: command.p2, data: chunk.to_vec() };
-
-            response = transport.exchange(&command).await?;
-            Self::handle_response_error(&response)?;
-        }
-
-        Ok(response)
+        AppSession::for_app::<Self>(transport)
+            .send_chunks(command, message)
+            .await
     }
+
+    /// Stream a long request in chunks following the given [ChunkingScheme]
+    ///
+    /// # Arguments
+    /// * `transport` - The transport layer used for communication.
+    /// * `scheme` - How to split the message and build each APDU.
+    /// * `header` - CLA/INS/P1/P2 the scheme derives each APDU from.
+    /// * `init` - Payload of the init APDU (e.g. a derivation path), can be empty.
+    /// * `message` - The message to be sent in chunks.
+    ///
+    /// # Returns
+    /// A result containing the final APDU answer or a ledger application error.
+    async fn send_chunks_with<S: ChunkingScheme + ?Sized>(
+        transport: &E,
+        scheme: &S,
+        header: ChunkHeader,
+        init: &[u8],
+        message: &[u8],
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>> {
+        AppSession::for_app::<Self>(transport)
+            .send_chunks_with(scheme, header, init, message)
+            .await
+    }
+
+    /// Stream a long request in chunks following the given [ChunkingScheme], reporting progress
+    ///
+    /// `progress` is called after every chunk accepted by the app. Returning [ControlFlow::Break]
+    /// cancels the upload before the next chunk is sent, with [LedgerAppError::Cancelled].
+    ///
+    /// A chunk rejected midway is reported as [LedgerAppError::ChunkRejected], with its index and status word.
+    ///
+    /// # Arguments
+    /// See [AppExt::send_chunks_with]
+    async fn send_chunks_with_progress<S, F>(
+        transport: &E,
+        scheme: &S,
+        header: ChunkHeader,
+        init: &[u8],
+        message: &[u8],
+        progress: F,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        S: ChunkingScheme + ?Sized,
+        F: FnMut(&ChunkProgress) -> ControlFlow<()> + Send,
+    {
+        AppSession::for_app::<Self>(transport)
+            .send_chunks_with_progress(scheme, header, init, message, progress)
+            .await
+    }
+
+    /// Stream a long request in chunks, reading the message from `source` while it is sent
+    ///
+    /// Works like [AppExt::send_chunks_with_progress]. When the source doesn't know its length in advance,
+    /// [ChunkProgress] has no totals and a message exceeding [ChunkingScheme::max_chunks] fails
+    /// with [LedgerAppError::InvalidMessageSize] once the limit is reached.
+    async fn send_chunks_from_source<S, M, F>(
+        transport: &E,
+        scheme: &S,
+        header: ChunkHeader,
+        init: &[u8],
+        source: M,
+        progress: F,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        S: ChunkingScheme + ?Sized,
+        M: MessageSource,
+        F: FnMut(&ChunkProgress) -> ControlFlow<()> + Send,
+    {
+        AppSession::for_app::<Self>(transport)
+            .send_chunks_from_source(scheme, header, init, source, progress)
+            .await
+    }
+
+    /// Same as [AppExt::send_chunks], with the message produced lazily by an iterator of byte slices
+    async fn send_chunks_from_iter<'a, I, It>(
+        transport: &E,
+        command: APDUCommand<I>,
+        message: It,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        I: std::ops::Deref<Target = [u8]> + Send + Sync,
+        It: IntoIterator<Item = &'a [u8]> + Send,
+        It::IntoIter: Send,
+    {
+        AppSession::for_app::<Self>(transport)
+            .send_chunks_from_iter(command, message)
+            .await
+    }
+
+    /// Same as [AppExt::send_chunks], with the message read from an [AsyncRead](futures_util::io::AsyncRead)
+    #[cfg(feature = "stream")]
+    async fn send_chunks_from_reader<I, R>(
+        transport: &E,
+        command: APDUCommand<I>,
+        message: R,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        I: std::ops::Deref<Target = [u8]> + Send + Sync,
+        R: futures_util::io::AsyncRead + Unpin + Send,
+    {
+        AppSession::for_app::<Self>(transport)
+            .send_chunks_from_reader(command, message)
+            .await
+    }
+
+    /// Same as [AppExt::send_chunks], with the message produced by a [Stream](futures_util::Stream) of [bytes::Bytes]
+    #[cfg(feature = "stream")]
+    async fn send_chunks_from_stream<I, St>(
+        transport: &E,
+        command: APDUCommand<I>,
+        message: St,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        I: std::ops::Deref<Target = [u8]> + Send + Sync,
+        St: futures_util::Stream<Item = bytes::Bytes> + Unpin + Send,
+    {
+        AppSession::for_app::<Self>(transport)
+            .send_chunks_from_stream(command, message)
+            .await
+    }
+
+    /// Send a typed command and decode its answer
+    ///
+    /// Commands with [LedgerCommand::chunked_data] are streamed with [AppExt::send_chunks],
+    /// using [LedgerCommand::payload] as the `Init` chunk
+    async fn send_command<C: LedgerCommand + Sync>(
+        transport: &E,
+        command: &C,
+    ) -> Result<C::Response, LedgerAppError<E::Error>> {
+        AppSession::for_app::<Self>(transport)
+            .send_command(command)
+            .await
+    }
+}
+
+/// Items used by the code generated by `ledger-zondax-derive`
+#[doc(hidden)]
+pub mod __private {
+    pub use async_trait::async_trait;
+    pub use ledger_transport::Exchange;
 }
 
 impl<T, E> AppExt<E> for T
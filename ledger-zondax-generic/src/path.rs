@@ -0,0 +1,279 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! BIP32 derivation paths and their Ledger encodings
+
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::PathError;
+
+/// Flag of hardened path components
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// BIP44 purpose
+pub const BIP44_PURPOSE: u32 = 44;
+
+/// SLIP-44 coin types of common networks
+pub mod slip44 {
+    /// Bitcoin
+    pub const BITCOIN: u32 = 0;
+    /// Any testnet
+    pub const TESTNET: u32 = 1;
+    /// Litecoin
+    pub const LITECOIN: u32 = 2;
+    /// Ethereum
+    pub const ETHEREUM: u32 = 60;
+    /// Cosmos Hub
+    pub const COSMOS: u32 = 118;
+    /// Zcash
+    pub const ZCASH: u32 = 133;
+    /// XRP
+    pub const XRP: u32 = 144;
+    /// Stellar
+    pub const STELLAR: u32 = 148;
+    /// Tron
+    pub const TRON: u32 = 195;
+    /// Internet Computer
+    pub const ICP: u32 = 223;
+    /// Algorand
+    pub const ALGORAND: u32 = 283;
+    /// Polkadot
+    pub const POLKADOT: u32 = 354;
+    /// NEAR
+    pub const NEAR: u32 = 397;
+    /// Kusama
+    pub const KUSAMA: u32 = 434;
+    /// Filecoin
+    pub const FILECOIN: u32 = 461;
+    /// Oasis
+    pub const OASIS: u32 = 474;
+    /// Solana
+    pub const SOLANA: u32 = 501;
+    /// Aptos
+    pub const APTOS: u32 = 637;
+    /// Sui
+    pub const SUI: u32 = 784;
+    /// Tezos
+    pub const TEZOS: u32 = 1729;
+    /// Cardano
+    pub const CARDANO: u32 = 1815;
+    /// Stacks
+    pub const STACKS: u32 = 5757;
+    /// Avalanche
+    pub const AVALANCHE: u32 = 9000;
+}
+
+/// A BIP32 derivation path such as `m/44'/118'/0'/0/0`
+///
+/// Parsed from strings with `'`, `h` or `H` marking hardened components, the `m/` prefix
+/// being optional. Serialized with serde as its [Display](fmt::Display) form
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    /// Maximum number of components accepted by Ledger apps
+    pub const MAX_DEPTH: usize = 10;
+
+    /// Create a path from raw components, hardened ones including [HARDENED]
+    pub fn new(components: Vec<u32>) -> Result<Self, PathError> {
+        if components.len() > Self::MAX_DEPTH {
+            return Err(PathError::TooDeep(components.len()));
+        }
+        Ok(Self(components))
+    }
+
+    /// The BIP44 path `m/44'/coin_type'/account'/change/index`
+    ///
+    /// Components are given without the hardened flag, a component already including it
+    /// fails with [PathError::OutOfRange]
+    pub fn bip44(
+        coin_type: u32,
+        account: u32,
+        change: u32,
+        index: u32,
+    ) -> Result<Self, PathError> {
+        if let Some(&component) = [coin_type, account, change, index]
+            .iter()
+            .find(|&&component| component >= HARDENED)
+        {
+            return Err(PathError::OutOfRange(component));
+        }
+
+        Ok(Self(vec![BIP44_PURPOSE | HARDENED, coin_type | HARDENED, account | HARDENED, change, index]))
+    }
+
+    /// Raw components, hardened ones including [HARDENED]
+    pub fn components(&self) -> &[u32] {
+        &self.0
+    }
+
+    /// Number of components
+    pub fn depth(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Coin type of a BIP44 path (second component, without the hardened flag)
+    pub fn coin_type(&self) -> Option<u32> {
+        self.0
+            .get(1)
+            .map(|component| component & !HARDENED)
+    }
+
+    /// Check that the path has exactly `depth` components
+    pub fn check_depth(
+        &self,
+        depth: usize,
+    ) -> Result<(), PathError> {
+        if self.depth() != depth {
+            return Err(PathError::WrongDepth { expected: depth, got: self.depth() });
+        }
+        Ok(())
+    }
+
+    /// Check that the first `count` components are hardened
+    pub fn check_hardened(
+        &self,
+        count: usize,
+    ) -> Result<(), PathError> {
+        match self
+            .0
+            .iter()
+            .take(count)
+            .position(|component| component & HARDENED == 0)
+        {
+            Some(position) => Err(PathError::NotHardened(position)),
+            None if self.depth() < count => Err(PathError::NotHardened(self.depth())),
+            None => Ok(()),
+        }
+    }
+
+    /// Check that the path coin type is `coin_type`
+    pub fn check_coin_type(
+        &self,
+        coin_type: u32,
+    ) -> Result<(), PathError> {
+        match self.coin_type() {
+            Some(got) if got == coin_type => Ok(()),
+            got => Err(PathError::WrongCoinType { expected: coin_type, got }),
+        }
+    }
+
+    /// Check that the path is a BIP44 path of `coin_type`, with hardened purpose, coin type and account
+    pub fn check_bip44(
+        &self,
+        coin_type: u32,
+    ) -> Result<(), PathError> {
+        self.check_depth(5)?;
+        self.check_hardened(3)?;
+        if self.0[0] != BIP44_PURPOSE | HARDENED {
+            return Err(PathError::InvalidComponent(format!("purpose {}", self.0[0] & !HARDENED)));
+        }
+        self.check_coin_type(coin_type)
+    }
+
+    /// Length byte followed by each component as a big endian `u32`, as most Ledger apps expect
+    pub fn serialize_be(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + 4 * self.depth());
+        out.push(self.depth() as u8);
+        for component in &self.0 {
+            out.extend_from_slice(&component.to_be_bytes());
+        }
+        out
+    }
+
+    /// Exactly five little endian `u32`, as Zondax apps expect
+    pub fn serialize_zondax(&self) -> Result<[u8; 20], PathError> {
+        self.check_depth(5)?;
+
+        let mut out = [0u8; 20];
+        for (chunk, component) in out.chunks_exact_mut(4).zip(&self.0) {
+            chunk.copy_from_slice(&component.to_le_bytes());
+        }
+        Ok(out)
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = PathError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let trimmed = path.trim();
+        let trimmed = match trimmed {
+            "m" | "M" => "",
+            _ => trimmed
+                .strip_prefix("m/")
+                .or_else(|| trimmed.strip_prefix("M/"))
+                .unwrap_or(trimmed),
+        };
+        if trimmed.is_empty() {
+            return Ok(Self(Vec::new()));
+        }
+
+        let components = trimmed
+            .split('/')
+            .map(|component| {
+                let (index, hardened) = match component.strip_suffix(['\'', 'h', 'H']) {
+                    Some(index) => (index, true),
+                    None => (component, false),
+                };
+
+                let index: u32 = index
+                    .parse()
+                    .map_err(|_| PathError::InvalidComponent(component.to_string()))?;
+                if index & HARDENED != 0 {
+                    return Err(PathError::InvalidComponent(component.to_string()));
+                }
+
+                Ok(if hardened { index | HARDENED } else { index })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::new(components)
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "m")?;
+        for component in &self.0 {
+            match component & HARDENED {
+                0 => write!(f, "/{}", component)?,
+                _ => write!(f, "/{}'", component & !HARDENED)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for DerivationPath {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DerivationPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = String::deserialize(deserializer)?;
+        path.parse().map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,907 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Apps configured at runtime, bound to a transport
+
+use std::{ops::ControlFlow, str};
+
+use ledger_transport::{APDUAnswer, APDUCommand, APDUErrorCode, Exchange};
+
+use crate::{
+    chunking,
+    command::{self, ApduDeserialize, Endianness},
+    dashboard, status, AccountDiscovery, AddressCache, AddressInfo, AddressLayout, App, AppCheckCache, AppFlags,
+    AppInfo, ChunkHeader, ChunkProgress, Chunking, ChunkingScheme, CurrentContext, DerivationPath, DeviceFlags,
+    DeviceInfo, DiscoveredAddress, DiscoveryProgress, IterSource, LedgerAppError, LedgerCommand, LedgerResponse,
+    MessageSource, SignLayout, Signature, StatusWord, Version,
+};
+
+const INS_GET_VERSION: u8 = 0x00;
+const CLA_APP_INFO: u8 = 0xb0;
+const INS_APP_INFO: u8 = 0x01;
+const CLA_DEVICE_INFO: u8 = 0xe0;
+const INS_DEVICE_INFO: u8 = 0x01;
+const CLA_OPEN_APP: u8 = 0xe0;
+const INS_OPEN_APP: u8 = 0xd8;
+const CLA_QUIT_APP: u8 = 0xb0;
+const INS_QUIT_APP: u8 = 0xa7;
+const SW_APP_NOT_INSTALLED: u16 = 0x6807;
+
+/// Error for a status word defined by the app, `Unknown` if it's not in `status_words`
+fn app_status<E: std::error::Error>(
+    status_words: &[StatusWord],
+    code: u16,
+) -> LedgerAppError<E> {
+    match status::lookup(status_words, code) {
+        Some(status) => LedgerAppError::AppStatus {
+            code,
+            name: status.name.to_string(),
+            description: status.description.to_string(),
+        },
+        None => LedgerAppError::Unknown(code),
+    }
+}
+
+/// Map the status word of an answer to an error, looking it up in the app `status_words` first
+pub(crate) fn check_response<A, E>(
+    response: &APDUAnswer<A>,
+    status_words: &[StatusWord],
+) -> Result<(), LedgerAppError<E>>
+where
+    A: std::ops::Deref<Target = [u8]>,
+    E: std::error::Error,
+{
+    match response.error_code() {
+        Ok(APDUErrorCode::NoError) => Ok(()),
+        _ if status::lookup(status_words, response.retcode()).is_some() => {
+            Err(app_status(status_words, response.retcode()))
+        },
+        Ok(err) => Err(LedgerAppError::AppSpecific(err as _, err.description())),
+        Err(err) => Err(LedgerAppError::Unknown(err)),
+    }
+}
+
+/// Same as [check_response], also failing when no signature is returned
+pub(crate) fn check_signature_response<A, E>(
+    response: &APDUAnswer<A>,
+    status_words: &[StatusWord],
+) -> Result<(), LedgerAppError<E>>
+where
+    A: std::ops::Deref<Target = [u8]>,
+    E: std::error::Error,
+{
+    match response.error_code() {
+        Ok(APDUErrorCode::NoError) if response.data().is_empty() => Err(LedgerAppError::NoSignature),
+        Ok(APDUErrorCode::NoError) => Ok(()),
+        _ if status::lookup(status_words, response.retcode()).is_some() => {
+            Err(app_status(status_words, response.retcode()))
+        },
+        Ok(err) => Err(LedgerAppError::AppSpecific(err as _, err.description())),
+        Err(err) => Err(LedgerAppError::AppSpecific(err, "[APDU_ERROR] Unknown".to_string())),
+    }
+}
+
+/// Runtime description of an app, the counterpart of the [App] constants
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppConfig {
+    /// App's APDU CLA
+    pub cla: u8,
+    /// Payload size of each chunk sent by [AppSession::send_chunks]
+    pub chunk_size: usize,
+    /// Chunking scheme of [AppSession::send_chunks]
+    pub chunking: Chunking,
+    /// Names the app can be reported as, any name is accepted if empty
+    pub app_names: Vec<String>,
+    /// Semantic version requirement of the supported app versions, any version is accepted if `None`
+    pub supported_versions: Option<String>,
+    /// Status words defined by the app, checked before the generic ones
+    pub status_words: Vec<StatusWord>,
+}
+
+impl AppConfig {
+    /// Configuration of an app with the given CLA, with the defaults of [App]
+    pub fn new(cla: u8) -> Self {
+        Self {
+            cla,
+            chunk_size: chunking::DEFAULT_CHUNK_SIZE,
+            chunking: Chunking::default(),
+            app_names: Vec::new(),
+            supported_versions: None,
+            status_words: Vec::new(),
+        }
+    }
+
+    /// Configuration from the constants of `A`
+    pub fn of<A: App + ?Sized>() -> Self {
+        Self {
+            cla: A::CLA,
+            chunk_size: A::CHUNK_SIZE,
+            chunking: A::CHUNKING,
+            app_names: A::APP_NAMES
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            supported_versions: A::SUPPORTED_VERSIONS.map(ToString::to_string),
+            status_words: A::STATUS_WORDS.to_vec(),
+        }
+    }
+
+    /// Use another CLA, e.g. for a testnet build of the app
+    pub fn with_cla(
+        mut self,
+        cla: u8,
+    ) -> Self {
+        self.cla = cla;
+        self
+    }
+
+    /// Use another chunk size
+    pub fn with_chunk_size(
+        mut self,
+        chunk_size: usize,
+    ) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Use another chunking scheme
+    pub fn with_chunking(
+        mut self,
+        chunking: Chunking,
+    ) -> Self {
+        self.chunking = chunking;
+        self
+    }
+
+    /// Accept only these app names in [AppSession::ensure_app]
+    pub fn with_app_names<I: IntoIterator<Item = S>, S: Into<String>>(
+        mut self,
+        names: I,
+    ) -> Self {
+        self.app_names = names
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        self
+    }
+
+    /// Accept only the versions matching this requirement in [AppSession::ensure_app], e.g. `">=2.30, <3"`
+    pub fn with_supported_versions(
+        mut self,
+        requirement: impl Into<String>,
+    ) -> Self {
+        self.supported_versions = Some(requirement.into());
+        self
+    }
+
+    /// Use another status word table, e.g. one loaded at runtime
+    pub fn with_status_words<I: IntoIterator<Item = StatusWord>>(
+        mut self,
+        status_words: I,
+    ) -> Self {
+        self.status_words = status_words.into_iter().collect();
+        self
+    }
+}
+
+/// An app bound to a transport, owned or borrowed
+///
+/// Exposes the operations of [AppExt](crate::AppExt) as methods, using an [AppConfig]
+/// that can be set at runtime
+///
+/// ```ignore
+/// let app = AppSession::new(&transport, AppConfig::of::<Cosmos>().with_cla(0x56));
+/// let version = app.get_version().await?;
+/// ```
+#[derive(Debug)]
+pub struct AppSession<E> {
+    transport: E,
+    config: AppConfig,
+}
+
+impl<E> AppSession<E> {
+    /// Bind the app described by `config` to `transport`
+    pub fn new(
+        transport: E,
+        config: AppConfig,
+    ) -> Self {
+        Self { transport, config }
+    }
+
+    /// Bind the app `A` to `transport`, see [AppConfig::of]
+    pub fn for_app<A: App + ?Sized>(transport: E) -> Self {
+        Self::new(transport, AppConfig::of::<A>())
+    }
+
+    /// The transport
+    pub fn transport(&self) -> &E {
+        &self.transport
+    }
+
+    /// The app configuration
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// Change the app configuration
+    pub fn config_mut(&mut self) -> &mut AppConfig {
+        &mut self.config
+    }
+
+    /// Give back the transport
+    pub fn into_transport(self) -> E {
+        self.transport
+    }
+}
+
+impl<E> AppSession<E>
+where
+    E: Exchange + Send + Sync,
+    E::Error: std::error::Error,
+{
+    /// Map the status word of an answer to an error
+    pub fn handle_response_error(
+        &self,
+        response: &APDUAnswer<E::AnswerType>,
+    ) -> Result<(), LedgerAppError<E::Error>> {
+        check_response(response, &self.config.status_words)
+    }
+
+    /// Same as [AppSession::handle_response_error], also failing when no signature is returned
+    pub fn handle_response_error_signature(
+        &self,
+        response: &APDUAnswer<E::AnswerType>,
+    ) -> Result<(), LedgerAppError<E::Error>> {
+        check_signature_response(response, &self.config.status_words)
+    }
+
+    /// Retrieve the device info
+    ///
+    /// Works only in the dashboard
+    pub async fn get_device_info(&self) -> Result<DeviceInfo, LedgerAppError<E::Error>> {
+        let command = APDUCommand { cla: CLA_DEVICE_INFO, ins: INS_DEVICE_INFO, p1: 0x00, p2: 0x00, data: Vec::new() };
+
+        let response = self
+            .transport
+            .exchange(&command)
+            .await?;
+        match response.error_code() {
+            Ok(APDUErrorCode::NoError) => {},
+            Ok(err) => return Err(LedgerAppError::Unknown(err as _)),
+            Err(err) => return Err(LedgerAppError::Unknown(err)),
+        }
+
+        let mut data = response.data();
+        let invalid = LedgerAppError::InvalidResponse;
+
+        let mut target_id = [0u8; 4];
+        target_id.copy_from_slice(command::take(&mut data, 4).map_err(invalid)?);
+        let se_version = command::take_u8_prefixed(&mut data).map_err(invalid)?;
+        let flag = command::take_u8_prefixed(&mut data).map_err(invalid)?;
+        let mcu_version = command::take_u8_prefixed(&mut data).map_err(invalid)?;
+
+        // only returned by recent firmwares (Stax, Flex, ...)
+        let mut extra_fields = Vec::new();
+        while !data.is_empty() && extra_fields.len() < 3 {
+            extra_fields.push(command::take_u8_prefixed(&mut data).map_err(invalid)?);
+        }
+        let mut extra_fields = extra_fields.into_iter();
+
+        let utf8 = |bytes: &[u8]| {
+            let bytes = bytes
+                .strip_suffix(&[0])
+                .unwrap_or(bytes);
+            str::from_utf8(bytes)
+                .map(str::to_string)
+                .map_err(|_e| LedgerAppError::Utf8)
+        };
+
+        let se_version = utf8(se_version)?;
+        let mcu_version = utf8(mcu_version)?;
+        let mcu_bootloader_version = extra_fields
+            .next()
+            .map(utf8)
+            .transpose()?;
+        let hardware_version = extra_fields.next().map(<[u8]>::to_vec);
+        let language_id = extra_fields
+            .next()
+            .and_then(|id| id.first().copied());
+
+        let device_info = DeviceInfo {
+            target_id,
+            se_version,
+            flag: flag.to_vec(),
+            flags: DeviceFlags::from_le_slice(flag),
+            mcu_version,
+            mcu_bootloader_version,
+            hardware_version,
+            language_id,
+        };
+
+        Ok(device_info)
+    }
+
+    /// Retrieve the app info
+    ///
+    /// In the dashboard, recent firmwares report the OS as an app named
+    /// [DASHBOARD_NAME](dashboard::DASHBOARD_NAME), older ones reject the command.
+    /// See [AppSession::current_context] to handle both
+    pub async fn get_app_info(&self) -> Result<AppInfo, LedgerAppError<E::Error>> {
+        let command = APDUCommand { cla: CLA_APP_INFO, ins: INS_APP_INFO, p1: 0x00, p2: 0x00, data: Vec::new() };
+
+        let response = self
+            .transport
+            .exchange(&command)
+            .await?;
+        match response.error_code() {
+            Ok(APDUErrorCode::NoError) => {},
+            Ok(err) => return Err(LedgerAppError::AppSpecific(err as _, err.description())),
+            Err(err) => return Err(LedgerAppError::Unknown(err as _)),
+        }
+
+        let mut data = response.data();
+        let invalid = LedgerAppError::InvalidResponse;
+
+        if u8::read_from(&mut data, Endianness::Big).map_err(invalid)? != 1 {
+            return Err(LedgerAppError::InvalidFormatID);
+        }
+
+        let app_name_bytes = command::take_u8_prefixed(&mut data).map_err(invalid)?;
+        let app_version_bytes = command::take_u8_prefixed(&mut data).map_err(invalid)?;
+        let flags_bytes = command::take_u8_prefixed(&mut data).map_err(invalid)?;
+        let flags_value = flags_bytes
+            .first()
+            .copied()
+            .unwrap_or_default();
+
+        let app_name = str::from_utf8(app_name_bytes).map_err(|_e| LedgerAppError::Utf8)?;
+        let app_version = str::from_utf8(app_version_bytes).map_err(|_e| LedgerAppError::Utf8)?;
+
+        let app_info = AppInfo {
+            app_name: app_name.to_string(),
+            app_version: app_version.to_string(),
+            flag_len: flags_bytes.len() as u8,
+            flags_value,
+            flag_recovery: (flags_value & 1) != 0,
+            flag_signed_mcu_code: (flags_value & 2) != 0,
+            flag_onboarded: (flags_value & 4) != 0,
+            flag_pin_validated: (flags_value & 128) != 0,
+            flags: AppFlags::from_le_slice(flags_bytes),
+        };
+
+        Ok(app_info)
+    }
+
+    /// Tell whether the dashboard or an app is open, and which one
+    ///
+    /// A device rejecting the command, as older dashboards do, is reported as [CurrentContext::Unknown]
+    pub async fn current_context(&self) -> Result<CurrentContext, LedgerAppError<E::Error>> {
+        match self.get_app_info().await {
+            Ok(info) if info.app_name == dashboard::DASHBOARD_NAME => Ok(CurrentContext::Dashboard(info)),
+            Ok(info) => Ok(CurrentContext::App(info)),
+            Err(LedgerAppError::AppSpecific(code, _))
+                if code == APDUErrorCode::ClaNotSupported as u16 || code == APDUErrorCode::InsNotSupported as u16 =>
+            {
+                Ok(CurrentContext::Unknown { status_word: code })
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Open the app with the given name
+    ///
+    /// Works only in the dashboard. The device re-enumerates once the app is started,
+    /// see [wait_for_app](crate::wait_for_app) to reconnect to it
+    pub async fn open_app(
+        &self,
+        name: &str,
+    ) -> Result<(), LedgerAppError<E::Error>> {
+        let command =
+            APDUCommand { cla: CLA_OPEN_APP, ins: INS_OPEN_APP, p1: 0x00, p2: 0x00, data: name.as_bytes().to_vec() };
+
+        let response = self
+            .transport
+            .exchange(&command)
+            .await?;
+        match response.retcode() {
+            SW_APP_NOT_INSTALLED => Err(LedgerAppError::AppNotInstalled(name.to_string())),
+            _ => self.handle_response_error(&response),
+        }
+    }
+
+    /// Quit the currently open app, going back to the dashboard
+    ///
+    /// Works only in app. The device re-enumerates once the dashboard is back,
+    /// so the answer might never be received
+    pub async fn quit_app(&self) -> Result<(), LedgerAppError<E::Error>> {
+        let command = APDUCommand { cla: CLA_QUIT_APP, ins: INS_QUIT_APP, p1: 0x00, p2: 0x00, data: Vec::new() };
+
+        let response = self
+            .transport
+            .exchange(&command)
+            .await?;
+        self.handle_response_error(&response)
+    }
+
+    /// Retrieve the app version
+    pub async fn get_version(&self) -> Result<Version, LedgerAppError<E::Error>> {
+        let command = APDUCommand { cla: self.config.cla, ins: INS_GET_VERSION, p1: 0x00, p2: 0x00, data: Vec::new() };
+
+        let response = self
+            .transport
+            .exchange(&command)
+            .await?;
+        match response.error_code() {
+            Ok(APDUErrorCode::NoError) => {},
+            Ok(err) => return Err(LedgerAppError::Unknown(err as _)),
+            Err(err) => return Err(LedgerAppError::Unknown(err)),
+        }
+
+        let response_data = response.data();
+
+        let version = match response_data.len() {
+            // single byte version numbers
+            4 => Version {
+                mode: response_data[0],
+                major: response_data[1] as u16,
+                minor: response_data[2] as u16,
+                patch: response_data[3] as u16,
+                locked: false,
+                target_id: [0, 0, 0, 0],
+            },
+            // double byte version numbers
+            7 => Version {
+                mode: response_data[0],
+                major: response_data[1] as u16 * 256 + response_data[2] as u16,
+                minor: response_data[3] as u16 * 256 + response_data[4] as u16,
+                patch: response_data[5] as u16 * 256 + response_data[6] as u16,
+                locked: false,
+                target_id: [0, 0, 0, 0],
+            },
+            // double byte version numbers + lock + target id
+            9 => Version {
+                mode: response_data[0],
+                major: response_data[1] as u16,
+                minor: response_data[2] as u16,
+                patch: response_data[3] as u16,
+                locked: response_data[4] != 0,
+                target_id: [response_data[5], response_data[6], response_data[7], response_data[8]],
+            },
+            // double byte version numbers + lock + target id
+            12 => Version {
+                mode: response_data[0],
+                major: response_data[1] as u16 * 256 + response_data[2] as u16,
+                minor: response_data[3] as u16 * 256 + response_data[4] as u16,
+                patch: response_data[5] as u16 * 256 + response_data[6] as u16,
+                locked: response_data[7] != 0,
+                target_id: [response_data[8], response_data[9], response_data[10], response_data[11]],
+            },
+            _ => return Err(LedgerAppError::InvalidVersion),
+        };
+        Ok(version)
+    }
+
+    /// Check that the open app is this one, see [AppConfig::app_names] and [AppConfig::supported_versions]
+    ///
+    /// Fails with [LedgerAppError::WrongApp] or [LedgerAppError::UnsupportedVersion].
+    /// The transport is locked for the whole check, see [Exchange::lock_session]
+    pub async fn ensure_app(&self) -> Result<(), LedgerAppError<E::Error>> {
+        let session = self.transport.lock_session().await?;
+        AppSession::new(&session, self.config.clone())
+            .check_app()
+            .await
+    }
+
+    /// [AppSession::ensure_app] without locking the transport
+    async fn check_app(&self) -> Result<(), LedgerAppError<E::Error>> {
+        let config = &self.config;
+
+        if !config.app_names.is_empty() {
+            let info = self.get_app_info().await?;
+            if !config
+                .app_names
+                .contains(&info.app_name)
+            {
+                return Err(LedgerAppError::WrongApp { expected: config.app_names.clone(), actual: info.app_name });
+            }
+        }
+
+        if let Some(required) = &config.supported_versions {
+            let actual = self.get_version().await?;
+            if !actual
+                .satisfies(required)
+                .map_err(LedgerAppError::InvalidVersionRequirement)?
+            {
+                return Err(LedgerAppError::UnsupportedVersion { required: required.clone(), actual });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Same as [AppSession::ensure_app], querying the device only until a check succeeds
+    pub async fn ensure_app_cached(
+        &self,
+        cache: &AppCheckCache,
+    ) -> Result<(), LedgerAppError<E::Error>> {
+        if cache.is_verified() {
+            return Ok(());
+        }
+
+        self.ensure_app().await?;
+        cache.set_verified();
+        Ok(())
+    }
+
+    /// Retrieve the public key and address at `path`, following the app's `layout`
+    ///
+    /// With `show`, the address is displayed for the user to confirm, a refusal is reported
+    /// as [LedgerAppError::UserRejected]
+    pub async fn get_pubkey(
+        &self,
+        layout: &AddressLayout,
+        path: &DerivationPath,
+        show: bool,
+    ) -> Result<AddressInfo, LedgerAppError<E::Error>> {
+        let data = layout
+            .path
+            .encode(path)
+            .map_err(LedgerAppError::InvalidDerivationPath)?;
+        let command = APDUCommand { cla: self.config.cla, ins: layout.ins, p1: show as u8, p2: 0x00, data };
+
+        let response = self
+            .transport
+            .exchange(&command)
+            .await?;
+        match response.error_code() {
+            Ok(APDUErrorCode::ConditionsNotSatisfied | APDUErrorCode::CommandNotAllowed) if show => {
+                return Err(LedgerAppError::UserRejected)
+            },
+            _ => self.handle_response_error(&response)?,
+        }
+
+        layout
+            .decode(response.data())
+            .map_err(LedgerAppError::InvalidResponse)
+    }
+
+    /// Run a BIP44 account discovery through the app's address command
+    ///
+    /// `is_used` tells whether an address has any history. Addresses are read from `cache` when
+    /// possible and added to it otherwise, under the `device` identifier, see [AddressCache].
+    /// `progress` is called after every address and can stop the discovery, which can then be
+    /// resumed from [AccountDiscovery::state] by calling this again.
+    /// The discovery also stays resumable after an error.
+    ///
+    /// The transport is locked until the discovery stops, see [Exchange::lock_session]
+    pub async fn discover_accounts<F, Fut, P>(
+        &self,
+        layout: &AddressLayout,
+        discovery: &mut AccountDiscovery,
+        cache: &AddressCache,
+        device: &str,
+        is_used: F,
+        progress: P,
+    ) -> Result<(), LedgerAppError<E::Error>>
+    where
+        F: FnMut(DiscoveredAddress) -> Fut + Send,
+        Fut: std::future::Future<Output = Result<bool, Box<dyn std::error::Error + Send + Sync>>> + Send,
+        P: FnMut(&DiscoveryProgress<'_>) -> ControlFlow<()> + Send,
+    {
+        let session = self.transport.lock_session().await?;
+        AppSession::new(&session, self.config.clone())
+            .discover(layout, discovery, cache, device, is_used, progress)
+            .await
+    }
+
+    /// [AppSession::discover_accounts] without locking the transport
+    async fn discover<F, Fut, P>(
+        &self,
+        layout: &AddressLayout,
+        discovery: &mut AccountDiscovery,
+        cache: &AddressCache,
+        device: &str,
+        mut is_used: F,
+        mut progress: P,
+    ) -> Result<(), LedgerAppError<E::Error>>
+    where
+        F: FnMut(DiscoveredAddress) -> Fut + Send,
+        Fut: std::future::Future<Output = Result<bool, Box<dyn std::error::Error + Send + Sync>>> + Send,
+        P: FnMut(&DiscoveryProgress<'_>) -> ControlFlow<()> + Send,
+    {
+        let cla = self.config.cla;
+
+        loop {
+            let Some(path) = discovery.next_path()? else {
+                break;
+            };
+
+            let info = match cache.get(device, cla, layout, &path) {
+                Some(info) => info,
+                None => {
+                    let info = self
+                        .get_pubkey(layout, &path, false)
+                        .await?;
+                    cache.insert(device, cla, layout, path.clone(), info.clone());
+                    info
+                },
+            };
+
+            let address = DiscoveredAddress { path: path.clone(), info };
+            let used = is_used(address.clone())
+                .await
+                .map_err(|err| LedgerAppError::UsageCheck(err.to_string()))?;
+            discovery.record(address, used);
+
+            let flow = progress(&DiscoveryProgress {
+                path: &path,
+                used,
+                scanned: discovery.state.scanned,
+                found: discovery.state.used.len(),
+            });
+            if flow.is_break() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sign `message` with the key at `path`, following the app's `layout`
+    ///
+    /// The path is sent as the `Init` chunk, the message is streamed with [AppSession::send_chunks]
+    pub async fn sign(
+        &self,
+        layout: &SignLayout,
+        path: &DerivationPath,
+        message: &[u8],
+    ) -> Result<Signature, LedgerAppError<E::Error>> {
+        let data = layout
+            .path
+            .encode(path)
+            .map_err(LedgerAppError::InvalidDerivationPath)?;
+        let header = ChunkHeader { cla: self.config.cla, ins: layout.ins, p1: 0x00, p2: 0x00 };
+        let scheme = self.chunking_scheme();
+
+        let response = match self
+            .send_chunks_with(&*scheme, header, &data, message)
+            .await
+        {
+            Err(err)
+                if err.status_word() == Some(APDUErrorCode::ConditionsNotSatisfied as u16)
+                    || err.status_word() == Some(APDUErrorCode::CommandNotAllowed as u16) =>
+            {
+                return Err(LedgerAppError::UserRejected)
+            },
+            response => response?,
+        };
+        self.handle_response_error_signature(&response)?;
+
+        Signature::parse(layout.signature, response.data()).ok_or(LedgerAppError::InvalidSignature)
+    }
+
+    /// Same as [AppSession::sign], checking the signature against `public_key` on the host
+    ///
+    /// ECDSA signatures are checked over the `digest` of the message, see [Signature::verify].
+    /// A signature that doesn't match is reported as [LedgerAppError::InvalidSignature]
+    #[cfg(feature = "verify")]
+    pub async fn sign_verified(
+        &self,
+        layout: &SignLayout,
+        path: &DerivationPath,
+        message: &[u8],
+        public_key: &crate::PublicKey,
+        digest: crate::signature::MessageDigest,
+    ) -> Result<Signature, LedgerAppError<E::Error>> {
+        let signature = self.sign(layout, path, message).await?;
+        if !signature.verify(public_key, message, digest) {
+            return Err(LedgerAppError::InvalidSignature);
+        }
+        Ok(signature)
+    }
+
+    /// Stream a long request in chunks, using the [AppConfig::chunking] scheme with [AppConfig::chunk_size]
+    ///
+    /// `command` is the initial APDU, its header must be the init one of the scheme, e.g. P1 is
+    /// [ChunkPayloadType::Init](crate::ChunkPayloadType::Init) with [ZondaxChunking](crate::ZondaxChunking)
+    pub async fn send_chunks<I: std::ops::Deref<Target = [u8]> + Send + Sync>(
+        &self,
+        command: APDUCommand<I>,
+        message: &[u8],
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>> {
+        let header = ChunkHeader::from(&command);
+        if !self.config.chunking.is_init(&header) {
+            return Err(LedgerAppError::InvalidChunkPayloadType);
+        }
+
+        let scheme = self.chunking_scheme();
+        self.send_chunks_with(&*scheme, header, &command.data, message)
+            .await
+    }
+
+    /// The [AppConfig::chunking] scheme with [AppConfig::chunk_size]
+    fn chunking_scheme(&self) -> Box<dyn ChunkingScheme> {
+        self.config
+            .chunking
+            .scheme(self.config.chunk_size)
+    }
+
+    /// Stream a long request in chunks following the given [ChunkingScheme]
+    ///
+    /// `header` is the CLA/INS/P1/P2 the scheme derives each APDU from, `init` the payload of
+    /// the init APDU (e.g. a derivation path) which can be empty
+    pub async fn send_chunks_with<S: ChunkingScheme + ?Sized>(
+        &self,
+        scheme: &S,
+        header: ChunkHeader,
+        init: &[u8],
+        message: &[u8],
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>> {
+        self.send_chunks_from_source(scheme, header, init, message, |_| ControlFlow::Continue(()))
+            .await
+            .map_err(chunking::flatten_rejection)
+    }
+
+    /// Stream a long request in chunks following the given [ChunkingScheme], reporting progress
+    ///
+    /// `progress` is called after every chunk accepted by the app. Returning [ControlFlow::Break]
+    /// cancels the upload before the next chunk is sent, with [LedgerAppError::Cancelled].
+    ///
+    /// A chunk rejected midway is reported as [LedgerAppError::ChunkRejected], with its index and status word.
+    pub async fn send_chunks_with_progress<S, F>(
+        &self,
+        scheme: &S,
+        header: ChunkHeader,
+        init: &[u8],
+        message: &[u8],
+        progress: F,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        S: ChunkingScheme + ?Sized,
+        F: FnMut(&ChunkProgress) -> ControlFlow<()> + Send,
+    {
+        self.send_chunks_from_source(scheme, header, init, message, progress)
+            .await
+    }
+
+    /// Stream a long request in chunks, reading the message from `source` while it is sent
+    ///
+    /// Works like [AppSession::send_chunks_with_progress]. When the source doesn't know its length in advance,
+    /// [ChunkProgress] has no totals and a message exceeding [ChunkingScheme::max_chunks] fails
+    /// with [LedgerAppError::InvalidMessageSize] once the limit is reached.
+    pub async fn send_chunks_from_source<S, M, F>(
+        &self,
+        scheme: &S,
+        header: ChunkHeader,
+        init: &[u8],
+        source: M,
+        progress: F,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        S: ChunkingScheme + ?Sized,
+        M: MessageSource,
+        F: FnMut(&ChunkProgress) -> ControlFlow<()> + Send,
+    {
+        let check = |response: &APDUAnswer<E::AnswerType>| self.handle_response_error(response);
+        chunking::upload(&self.transport, check, scheme, header, init, source, progress).await
+    }
+
+    /// Same as [AppSession::send_chunks], with the message read from `source`
+    async fn send_chunks_from<I, M>(
+        &self,
+        command: APDUCommand<I>,
+        source: M,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        I: std::ops::Deref<Target = [u8]> + Send + Sync,
+        M: MessageSource,
+    {
+        let header = ChunkHeader::from(&command);
+        if !self.config.chunking.is_init(&header) {
+            return Err(LedgerAppError::InvalidChunkPayloadType);
+        }
+
+        let scheme = self.chunking_scheme();
+        self.send_chunks_from_source(&*scheme, header, &command.data, source, |_| ControlFlow::Continue(()))
+            .await
+            .map_err(chunking::flatten_rejection)
+    }
+
+    /// Same as [AppSession::send_chunks], with the message produced lazily by an iterator of byte slices
+    pub async fn send_chunks_from_iter<'a, I, It>(
+        &self,
+        command: APDUCommand<I>,
+        message: It,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        I: std::ops::Deref<Target = [u8]> + Send + Sync,
+        It: IntoIterator<Item = &'a [u8]> + Send,
+        It::IntoIter: Send,
+    {
+        self.send_chunks_from(command, IterSource(message.into_iter()))
+            .await
+    }
+
+    /// Same as [AppSession::send_chunks], with the message read from an [AsyncRead](futures_util::io::AsyncRead)
+    #[cfg(feature = "stream")]
+    pub async fn send_chunks_from_reader<I, R>(
+        &self,
+        command: APDUCommand<I>,
+        message: R,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        I: std::ops::Deref<Target = [u8]> + Send + Sync,
+        R: futures_util::io::AsyncRead + Unpin + Send,
+    {
+        self.send_chunks_from(command, crate::ReaderSource(message))
+            .await
+    }
+
+    /// Same as [AppSession::send_chunks], with the message produced by a [Stream](futures_util::Stream) of
+    /// [bytes::Bytes]
+    #[cfg(feature = "stream")]
+    pub async fn send_chunks_from_stream<I, St>(
+        &self,
+        command: APDUCommand<I>,
+        message: St,
+    ) -> Result<APDUAnswer<E::AnswerType>, LedgerAppError<E::Error>>
+    where
+        I: std::ops::Deref<Target = [u8]> + Send + Sync,
+        St: futures_util::Stream<Item = bytes::Bytes> + Unpin + Send,
+    {
+        self.send_chunks_from(command, crate::StreamSource(message))
+            .await
+    }
+
+    /// Send a typed command and decode its answer
+    ///
+    /// Commands with [LedgerCommand::chunked_data] are streamed with the [AppConfig::chunking] scheme,
+    /// using [LedgerCommand::payload] as the init payload, and fail with [LedgerAppError::ChunkParamOverwritten]
+    /// if they set a P1 or P2 the scheme overwrites. A payload that can't be encoded fails with
+    /// [LedgerAppError::InvalidCommand]
+    pub async fn send_command<C: LedgerCommand + Sync>(
+        &self,
+        command: &C,
+    ) -> Result<C::Response, LedgerAppError<E::Error>> {
+        let payload = command
+            .payload()
+            .map_err(LedgerAppError::InvalidCommand)?;
+        let response = match command.chunked_data() {
+            Some(message) => {
+                let header = ChunkHeader { cla: self.config.cla, ins: C::INS, p1: command.p1(), p2: command.p2() };
+                let scheme = self.chunking_scheme();
+                header.check(&*scheme)?;
+
+                self.send_chunks_with(&*scheme, header, &payload, message)
+                    .await?
+            },
+            None => {
+                let apdu = APDUCommand {
+                    cla: self.config.cla,
+                    ins: C::INS,
+                    p1: command.p1(),
+                    p2: command.p2(),
+                    data: payload,
+                };
+
+                let response = self.transport.exchange(&apdu).await?;
+                self.handle_response_error(&response)?;
+                response
+            },
+        };
+
+        C::Response::from_response(response.data()).map_err(LedgerAppError::InvalidResponse)
+    }
+}
@@ -0,0 +1,191 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Signatures returned by apps, see [AppExt::sign](crate::AppExt::sign)
+
+use serde::{Deserialize, Serialize};
+
+use crate::address::PathEncoding;
+
+/// Encoding of the signature in the answer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SignatureFormat {
+    /// ASN.1 DER encoded ECDSA signature
+    Der,
+    /// ECDSA `r || s`, 32 bytes each
+    Compact,
+    /// ECDSA `r || s || v`, `v` being the recovery id
+    Recoverable,
+    /// 64 bytes ed25519 signature
+    Ed25519,
+}
+
+/// Shape of an app "sign" instruction
+///
+/// The path is sent in the [Init](crate::ChunkPayloadType::Init) chunk and the message in the
+/// following ones, see [AppExt::send_chunks](crate::AppExt::send_chunks)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SignLayout {
+    /// Instruction of the command
+    pub ins: u8,
+    /// Encoding of the path in the init chunk
+    pub path: PathEncoding,
+    /// Encoding of the signature in the answer
+    pub signature: SignatureFormat,
+}
+
+impl SignLayout {
+    /// Layout of most Zondax secp256k1 apps
+    pub const ZONDAX_SECP256K1: Self = Self { ins: 0x02, path: PathEncoding::Zondax, signature: SignatureFormat::Der };
+
+    /// Layout of Zondax ed25519 apps
+    pub const ZONDAX_ED25519: Self =
+        Self { ins: 0x02, path: PathEncoding::Zondax, signature: SignatureFormat::Ed25519 };
+}
+
+/// Hash of the message signed with ECDSA, as computed by the app, e.g. [sha256]
+#[cfg(feature = "verify")]
+pub type MessageDigest = fn(&[u8]) -> [u8; 32];
+
+/// SHA-256 digest of `message`, the [MessageDigest] of most secp256k1 apps
+#[cfg(feature = "verify")]
+pub fn sha256(message: &[u8]) -> [u8; 32] {
+    use k256::sha2::Digest;
+
+    k256::sha2::Sha256::digest(message).into()
+}
+
+/// A signature parsed from an app answer
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Signature {
+    /// secp256k1 ECDSA signature
+    Ecdsa {
+        /// `r` scalar, big endian
+        r: [u8; 32],
+        /// `s` scalar, big endian
+        s: [u8; 32],
+        /// Recovery id, when returned by the app
+        recovery_id: Option<u8>,
+    },
+    /// ed25519 signature
+    Ed25519(Vec<u8>),
+}
+
+impl Signature {
+    /// Parse `data` in the given format, `None` if it is malformed
+    pub fn parse(
+        format: SignatureFormat,
+        data: &[u8],
+    ) -> Option<Self> {
+        match format {
+            SignatureFormat::Der => {
+                let (r, s) = parse_der(data)?;
+                Some(Self::Ecdsa { r, s, recovery_id: None })
+            },
+            SignatureFormat::Compact if data.len() == 64 => Some(Self::Ecdsa {
+                r: data[.. 32].try_into().ok()?,
+                s: data[32 ..].try_into().ok()?,
+                recovery_id: None,
+            }),
+            SignatureFormat::Recoverable if data.len() == 65 => Some(Self::Ecdsa {
+                r: data[.. 32].try_into().ok()?,
+                s: data[32 .. 64].try_into().ok()?,
+                recovery_id: Some(data[64]),
+            }),
+            SignatureFormat::Ed25519 if data.len() == 64 => Some(Self::Ed25519(data.to_vec())),
+            _ => None,
+        }
+    }
+
+    /// Check the signature of `message` against `public_key`
+    ///
+    /// ECDSA signatures are checked over the `digest` of the message, ed25519 ones over the message itself
+    #[cfg(feature = "verify")]
+    pub fn verify(
+        &self,
+        public_key: &crate::PublicKey,
+        message: &[u8],
+        digest: MessageDigest,
+    ) -> bool {
+        match self {
+            Self::Ecdsa { r, s, .. } => {
+                use k256::ecdsa::signature::hazmat::PrehashVerifier;
+
+                let Some(key) = public_key.to_secp256k1() else { return false };
+                let Ok(signature) = k256::ecdsa::Signature::from_scalars(*r, *s) else { return false };
+                let signature = signature
+                    .normalize_s()
+                    .unwrap_or(signature);
+
+                k256::ecdsa::VerifyingKey::from(key)
+                    .verify_prehash(&digest(message), &signature)
+                    .is_ok()
+            },
+            Self::Ed25519(bytes) => {
+                use ed25519_dalek::Verifier;
+
+                let Some(key) = public_key.to_ed25519() else { return false };
+                let Ok(signature) = ed25519_dalek::Signature::from_slice(bytes) else { return false };
+
+                key.verify(message, &signature).is_ok()
+            },
+        }
+    }
+}
+
+/// Read a DER `INTEGER` from the front of `data` as a 32 bytes big endian scalar
+///
+/// Only the minimal encoding of a positive integer is accepted
+fn der_integer(data: &mut &[u8]) -> Option<[u8; 32]> {
+    let [0x02, len, rest @ ..] = *data else { return None };
+    let len = *len as usize;
+    // long form lengths never fit a scalar
+    if len == 0 || len >= 0x80 || rest.len() < len {
+        return None;
+    }
+
+    let (value, tail) = rest.split_at(len);
+    *data = tail;
+
+    let value = match value {
+        // negative
+        [first, ..] if first & 0x80 != 0 => return None,
+        // the sign padding is only allowed before a byte with its high bit set
+        [0, next, ..] if next & 0x80 != 0 => &value[1 ..],
+        [0, _, ..] => return None,
+        value => value,
+    };
+    if value.len() > 32 {
+        return None;
+    }
+
+    let mut scalar = [0u8; 32];
+    scalar[32 - value.len() ..].copy_from_slice(value);
+    Some(scalar)
+}
+
+/// Parse an ASN.1 DER `SEQUENCE { r INTEGER, s INTEGER }`
+fn parse_der(data: &[u8]) -> Option<([u8; 32], [u8; 32])> {
+    let [0x30, len, rest @ ..] = data else { return None };
+    if *len >= 0x80 || *len as usize != rest.len() {
+        return None;
+    }
+
+    let mut rest = rest;
+    let r = der_integer(&mut rest)?;
+    let s = der_integer(&mut rest)?;
+
+    rest.is_empty().then_some((r, s))
+}
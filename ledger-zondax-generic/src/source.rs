@@ -0,0 +1,122 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Messages read incrementally while they are sent in chunks
+
+use async_trait::async_trait;
+#[cfg(feature = "stream")]
+use futures_util::{io::AsyncRead, AsyncReadExt, Stream, StreamExt};
+
+/// A message to send in chunks, read piece by piece
+///
+/// Pieces can be of any size, they are regrouped in chunks of the size required by the [ChunkingScheme](crate::ChunkingScheme)
+#[async_trait]
+pub trait MessageSource: Send {
+    /// Read the next piece of the message, `None` once the message is over
+    ///
+    /// `max` is the preferred size of the piece, it can be ignored
+    async fn next_piece(
+        &mut self,
+        max: usize,
+    ) -> Result<Option<Vec<u8>>, std::io::Error>;
+
+    /// Total length of the message, if known in advance
+    fn len_hint(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[async_trait]
+impl MessageSource for &[u8] {
+    async fn next_piece(
+        &mut self,
+        max: usize,
+    ) -> Result<Option<Vec<u8>>, std::io::Error> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+
+        let (piece, rest) = self.split_at(std::cmp::min(max, self.len()));
+        *self = rest;
+        Ok(Some(piece.to_vec()))
+    }
+
+    fn len_hint(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+/// Message produced lazily by an iterator of byte slices
+pub struct IterSource<I>(pub I);
+
+#[async_trait]
+impl<'a, I> MessageSource for IterSource<I>
+where
+    I: Iterator<Item = &'a [u8]> + Send,
+{
+    async fn next_piece(
+        &mut self,
+        _: usize,
+    ) -> Result<Option<Vec<u8>>, std::io::Error> {
+        Ok(self.0.next().map(<[u8]>::to_vec))
+    }
+}
+
+/// Message read from an [AsyncRead]
+#[cfg(feature = "stream")]
+pub struct ReaderSource<R>(pub R);
+
+#[cfg(feature = "stream")]
+#[async_trait]
+impl<R> MessageSource for ReaderSource<R>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    async fn next_piece(
+        &mut self,
+        max: usize,
+    ) -> Result<Option<Vec<u8>>, std::io::Error> {
+        let mut piece = vec![0u8; max];
+        let read = self.0.read(&mut piece).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        piece.truncate(read);
+        Ok(Some(piece))
+    }
+}
+
+/// Message produced by a [Stream] of [bytes::Bytes]
+#[cfg(feature = "stream")]
+pub struct StreamSource<S>(pub S);
+
+#[cfg(feature = "stream")]
+#[async_trait]
+impl<S> MessageSource for StreamSource<S>
+where
+    S: Stream<Item = bytes::Bytes> + Unpin + Send,
+{
+    async fn next_piece(
+        &mut self,
+        _: usize,
+    ) -> Result<Option<Vec<u8>>, std::io::Error> {
+        Ok(self
+            .0
+            .next()
+            .await
+            .map(|bytes| bytes.to_vec()))
+    }
+}
@@ -0,0 +1,136 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! App-specific status words
+//!
+//! Apps declare their own status words in [App::STATUS_WORDS](crate::App::STATUS_WORDS),
+//! usually generated with [status_words!](crate::status_words)
+
+use std::borrow::Cow;
+
+/// A status word defined by an app
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusWord {
+    /// Raw code
+    pub code: u16,
+    /// Short name, e.g. `InvalidTxType`
+    pub name: Cow<'static, str>,
+    /// Human readable description
+    pub description: Cow<'static, str>,
+}
+
+impl StatusWord {
+    /// Describe a status word
+    pub const fn new(
+        code: u16,
+        name: &'static str,
+        description: &'static str,
+    ) -> Self {
+        Self { code, name: Cow::Borrowed(name), description: Cow::Borrowed(description) }
+    }
+}
+
+/// Find `code` in `table`
+pub fn lookup(
+    table: &[StatusWord],
+    code: u16,
+) -> Option<&StatusWord> {
+    table
+        .iter()
+        .find(|status| status.code == code)
+}
+
+/// Declare an enum of app status words along with its [StatusWord] table
+///
+/// ```
+/// use ledger_zondax_generic::{status_words, App};
+///
+/// status_words! {
+///     /// Status words of the Cosmos app
+///     pub enum CosmosStatus {
+///         /// The transaction type is not supported
+///         InvalidTxType = 0x6988 => "invalid transaction type",
+///     }
+/// }
+///
+/// struct Cosmos;
+///
+/// impl App for Cosmos {
+///     const CLA: u8 = 0x55;
+///     const STATUS_WORDS: &'static [ledger_zondax_generic::StatusWord] = CosmosStatus::TABLE;
+/// }
+///
+/// assert_eq!(CosmosStatus::try_from(0x6988), Ok(CosmosStatus::InvalidTxType));
+/// assert_eq!(CosmosStatus::InvalidTxType.description(), "invalid transaction type");
+/// ```
+#[macro_export]
+macro_rules! status_words {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident = $code:literal => $description:literal
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        #[repr(u16)]
+        $vis enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant = $code,
+            )*
+        }
+
+        impl $name {
+            /// Table of every status word, for [App::STATUS_WORDS]($crate::App::STATUS_WORDS)
+            pub const TABLE: &'static [$crate::StatusWord] = &[
+                $($crate::StatusWord::new($code, stringify!($variant), $description),)*
+            ];
+
+            /// Raw code
+            pub const fn code(self) -> u16 {
+                self as u16
+            }
+
+            /// Short name
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => stringify!($variant),)*
+                }
+            }
+
+            /// Human readable description
+            pub const fn description(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $description,)*
+                }
+            }
+        }
+
+        impl ::core::convert::TryFrom<u16> for $name {
+            type Error = u16;
+
+            fn try_from(code: u16) -> Result<Self, u16> {
+                match code {
+                    $($code => Ok(Self::$variant),)*
+                    code => Err(code),
+                }
+            }
+        }
+    };
+}
@@ -0,0 +1,1102 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use futures::executor::block_on;
+use ledger_transport::{
+    mock::{MockError, MockTransport},
+    APDUErrorCode,
+};
+
+use super::*;
+
+pub(crate) struct Dummy;
+
+impl App for Dummy {
+    const CLA: u8 = 0x55;
+    const CHUNK_SIZE: usize = 4;
+}
+
+#[test]
+fn send_chunks_zondax() {
+    let transport = MockTransport::ok(4);
+    let command = APDUCommand { cla: Dummy::CLA, ins: 0x02, p1: 0x00, p2: 0x07, data: vec![0xAA] };
+
+    block_on(Dummy::send_chunks(&transport, command, &[1, 2, 3, 4, 5, 6, 7, 8, 9])).expect("chunks sent");
+
+    assert_eq!(transport.sent(), vec![
+        vec![0x55, 0x02, 0x00, 0x07, 1, 0xAA],
+        vec![0x55, 0x02, 0x01, 0x07, 4, 1, 2, 3, 4],
+        vec![0x55, 0x02, 0x01, 0x07, 4, 5, 6, 7, 8],
+        vec![0x55, 0x02, 0x02, 0x07, 1, 9],
+    ]);
+}
+
+#[test]
+fn send_chunks_invalid_init() {
+    let transport = MockTransport::ok(1);
+    let command = APDUCommand { cla: Dummy::CLA, ins: 0x02, p1: 0x01, p2: 0x00, data: vec![] };
+
+    let err = block_on(Dummy::send_chunks(&transport, command, &[1])).expect_err("P1 is not Init");
+    assert_eq!(err, LedgerAppError::InvalidChunkPayloadType);
+    assert!(transport.sent().is_empty());
+}
+
+struct P2App;
+
+impl App for P2App {
+    const CLA: u8 = 0x55;
+    const CHUNK_SIZE: usize = 4;
+    const CHUNKING: Chunking = Chunking::P2;
+}
+
+#[test]
+fn send_chunks_app_scheme() {
+    let transport = MockTransport::ok(3);
+    let command = APDUCommand { cla: P2App::CLA, ins: 0x02, p1: 0x07, p2: 0x00, data: vec![0xAA] };
+
+    block_on(P2App::send_chunks(&transport, command, &[1, 2, 3, 4, 5])).expect("chunks sent");
+
+    assert_eq!(transport.sent(), vec![
+        vec![0x55, 0x02, 0x07, 0x00, 1, 0xAA],
+        vec![0x55, 0x02, 0x07, 0x01, 4, 1, 2, 3, 4],
+        vec![0x55, 0x02, 0x07, 0x02, 1, 5],
+    ]);
+
+    let command = APDUCommand { cla: P2App::CLA, ins: 0x02, p1: 0x00, p2: 0x01, data: vec![] };
+    let err = block_on(P2App::send_chunks(&transport, command, &[1])).expect_err("P2 is not Init");
+    assert_eq!(err, LedgerAppError::InvalidChunkPayloadType);
+}
+
+#[test]
+fn send_chunks_counter_inlines_init() {
+    let transport = MockTransport::ok(2);
+    let scheme = CounterChunking::new(3).with_last_cla_flag(0x80);
+    let header = ChunkHeader { cla: 0x55, ins: 0x02, p1: 0, p2: 0 };
+
+    block_on(Dummy::send_chunks_with(&transport, &scheme, header, &[0xAA], &[1, 2, 3])).expect("chunks sent");
+
+    assert_eq!(transport.sent(), vec![vec![0x55, 0x02, 0x00, 0x00, 3, 0xAA, 1, 2], vec![0xD5, 0x02, 0x00, 0x01, 1, 3]]);
+}
+
+#[test]
+fn send_chunks_limits() {
+    let transport = MockTransport::ok(0);
+    let header = ChunkHeader { cla: 0x55, ins: 0x02, p1: 0, p2: 0 };
+
+    let err = block_on(Dummy::send_chunks_with(&transport, &ZondaxChunking::new(1), header, &[], &[0; 256]))
+        .expect_err("too many chunks");
+    assert_eq!(err, LedgerAppError::InvalidMessageSize);
+
+    let err = block_on(Dummy::send_chunks_with(&transport, &ZondaxChunking::new(256), header, &[], &[0; 10]))
+        .expect_err("chunk longer than an APDU payload");
+    assert_eq!(err, LedgerAppError::InvalidMessageSize);
+
+    let err = block_on(Dummy::send_chunks_with(&transport, &P2Chunking::new(1), header, &[], &[]))
+        .expect_err("empty message");
+    assert_eq!(err, LedgerAppError::InvalidEmptyMessage);
+    assert!(transport.sent().is_empty());
+
+    let transport = MockTransport::ok(300);
+    block_on(Dummy::send_chunks_with(&transport, &CounterChunking::new(1), header, &[], &[0; 300]))
+        .expect("counter scheme supports more than 255 chunks");
+    assert_eq!(&transport.sent()[299][.. 4], &[0x55, 0x02, 0x01, 0x2B]);
+}
+
+#[test]
+fn send_chunks_progress() {
+    let transport = MockTransport::ok(4);
+    let header = ChunkHeader { cla: 0x55, ins: 0x02, p1: 0, p2: 0 };
+
+    let mut reports = Vec::new();
+    block_on(Dummy::send_chunks_with_progress(&transport, &ZondaxChunking::new(4), header, &[], &[0; 10], |p| {
+        reports.push(*p);
+        ControlFlow::Continue(())
+    }))
+    .expect("chunks sent");
+
+    assert_eq!(reports.len(), 3);
+    assert_eq!(reports[2], ChunkProgress {
+        chunk_index: 2,
+        total_chunks: Some(3),
+        bytes_sent: 10,
+        total_bytes: Some(10)
+    });
+}
+
+#[test]
+fn send_chunks_cancel() {
+    let transport = MockTransport::ok(4);
+    let header = ChunkHeader { cla: 0x55, ins: 0x02, p1: 0, p2: 0 };
+
+    let err =
+        block_on(Dummy::send_chunks_with_progress(&transport, &ZondaxChunking::new(4), header, &[], &[0; 10], |p| {
+            if p.chunk_index == 0 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }))
+        .expect_err("upload cancelled");
+
+    assert_eq!(err, LedgerAppError::Cancelled { chunks_sent: 1 });
+    // init + first chunk
+    assert_eq!(transport.sent().len(), 2);
+}
+
+#[test]
+fn send_chunks_rejected() {
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x90, 0x00], &[0x69, 0x84]]);
+    let header = ChunkHeader { cla: 0x55, ins: 0x02, p1: 0, p2: 0 };
+
+    let err =
+        block_on(Dummy::send_chunks_with_progress(&transport, &ZondaxChunking::new(4), header, &[], &[0; 10], |_| {
+            ControlFlow::Continue(())
+        }))
+        .expect_err("second chunk rejected");
+
+    match err {
+        LedgerAppError::ChunkRejected { index: 1, retcode: 0x6984, .. } => {},
+        err => panic!("unexpected error {err:?}"),
+    }
+
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x90, 0x00], &[0x69, 0x84]]);
+    let command = APDUCommand { cla: Dummy::CLA, ins: 0x02, p1: 0x00, p2: 0x00, data: vec![] };
+    let err = block_on(Dummy::send_chunks(&transport, command, &[0; 10])).expect_err("second chunk rejected");
+    assert_eq!(err, LedgerAppError::AppSpecific(0x6984, APDUErrorCode::DataInvalid.description()));
+}
+
+fn init_command() -> APDUCommand<Vec<u8>> {
+    APDUCommand { cla: Dummy::CLA, ins: 0x02, p1: 0x00, p2: 0x00, data: vec![0xAA] }
+}
+
+fn expected_chunks() -> Vec<Vec<u8>> {
+    let transport = MockTransport::ok(4);
+    block_on(Dummy::send_chunks(&transport, init_command(), &[1, 2, 3, 4, 5, 6, 7, 8, 9])).expect("chunks sent");
+    transport.sent()
+}
+
+#[test]
+fn send_chunks_from_iter() {
+    let transport = MockTransport::ok(4);
+    let pieces: [&[u8]; 4] = [&[1], &[], &[2, 3, 4, 5, 6, 7], &[8, 9]];
+
+    block_on(Dummy::send_chunks_from_iter(&transport, init_command(), pieces)).expect("chunks sent");
+    assert_eq!(transport.sent(), expected_chunks());
+
+    let transport = MockTransport::ok(1);
+    let pieces: [&[u8]; 1] = [&[]];
+    let err = block_on(Dummy::send_chunks_from_iter(&transport, init_command(), pieces)).expect_err("empty message");
+    assert_eq!(err, LedgerAppError::InvalidEmptyMessage);
+    assert!(transport.sent().is_empty());
+}
+
+#[test]
+fn send_chunks_from_source_unknown_length() {
+    let transport = MockTransport::ok(4);
+    let header = ChunkHeader { cla: 0x55, ins: 0x02, p1: 0, p2: 0 };
+    let pieces: [&[u8]; 2] = [&[0; 3], &[0; 5]];
+
+    let mut reports = Vec::new();
+    let err = block_on(Dummy::send_chunks_from_source(
+        &transport,
+        &ZondaxChunking::new(2),
+        header,
+        &[],
+        IterSource(pieces.into_iter()),
+        |p| {
+            reports.push(*p);
+            ControlFlow::Continue(())
+        },
+    ))
+    .expect_err("mock runs out of answers");
+
+    assert_eq!(err, LedgerAppError::TransportError(MockError));
+    assert_eq!(reports[2], ChunkProgress { chunk_index: 2, total_chunks: None, bytes_sent: 6, total_bytes: None });
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn send_chunks_from_reader_and_stream() {
+    let transport = MockTransport::ok(4);
+    let reader = futures::io::Cursor::new(vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    block_on(Dummy::send_chunks_from_reader(&transport, init_command(), reader)).expect("chunks sent");
+    assert_eq!(transport.sent(), expected_chunks());
+
+    let transport = MockTransport::ok(4);
+    let stream = futures::stream::iter(vec![
+        bytes::Bytes::from_static(&[1, 2, 3]),
+        bytes::Bytes::from_static(&[4, 5, 6, 7, 8, 9]),
+    ]);
+    block_on(Dummy::send_chunks_from_stream(&transport, init_command(), stream)).expect("chunks sent");
+    assert_eq!(transport.sent(), expected_chunks());
+}
+
+#[test]
+fn send_chunks_sessions_are_exclusive() {
+    let transport = MockTransport::ok(8);
+    let command = |p2| APDUCommand { cla: Dummy::CLA, ins: 0x02, p1: 0x00, p2, data: vec![] };
+
+    let (a, b) = block_on(futures::future::join(
+        Dummy::send_chunks(&transport, command(0xA), &[0; 10]),
+        Dummy::send_chunks(&transport, command(0xB), &[0; 10]),
+    ));
+    a.expect("first flow");
+    b.expect("second flow");
+
+    let p2s: Vec<u8> = transport
+        .sent()
+        .iter()
+        .map(|apdu| apdu[3])
+        .collect();
+    assert_eq!(p2s, vec![0xA, 0xA, 0xA, 0xA, 0xB, 0xB, 0xB, 0xB]);
+}
+
+/// Answer of [AppExt::get_app_info] for the given app
+pub(crate) fn app_info_answer(
+    name: &str,
+    version: &str,
+) -> Vec<u8> {
+    let mut answer = vec![1, name.len() as u8];
+    answer.extend_from_slice(name.as_bytes());
+    answer.push(version.len() as u8);
+    answer.extend_from_slice(version.as_bytes());
+    answer.extend_from_slice(&[1, 0, 0x90, 0x00]);
+    answer
+}
+
+#[test]
+fn get_app_info() {
+    let transport = MockTransport::new(&[&app_info_answer("Cosmos", "2.34.12")]);
+
+    let info = block_on(Dummy::get_app_info(&transport)).expect("valid app info");
+    assert_eq!(info.app_name, "Cosmos");
+    assert_eq!(info.app_version, "2.34.12");
+}
+
+#[test]
+fn get_app_info_name_length() {
+    // the name is read after its length byte, whatever its length
+    for name in ["A", "Cosmos Testnet"] {
+        let transport = MockTransport::new(&[&app_info_answer(name, "1.0.0")]);
+
+        let info = block_on(Dummy::get_app_info(&transport)).expect("valid app info");
+        assert_eq!((info.app_name.as_str(), info.app_version.as_str()), (name, "1.0.0"));
+    }
+}
+
+#[test]
+fn wait_for_app() {
+    let other_app = app_info_answer("Other", "1.0.0");
+    let dashboard = app_info_answer(dashboard::DASHBOARD_NAME, "2.1.0");
+    let target = app_info_answer("Cosmos", "2.34.12");
+
+    let mut connections = vec![
+        Ok(MockTransport::new(&[&target])),
+        Ok(MockTransport::new(&[&dashboard, &[0x90, 0x00]])),
+        Err(MockError),
+        Ok(MockTransport::new(&[&other_app, &[0x90, 0x00]])),
+    ];
+
+    let transport = block_on(wait_for_app_with_interval(
+        || {
+            connections
+                .pop()
+                .unwrap_or(Err(MockError))
+        },
+        "Cosmos",
+        std::time::Duration::from_secs(10),
+        std::time::Duration::from_millis(1),
+    ))
+    .expect("app opened");
+
+    assert_eq!(transport.sent(), vec![vec![0xb0, 0x01, 0, 0, 0]]);
+    assert!(connections.is_empty());
+}
+
+#[test]
+fn open_app_not_installed() {
+    let transport = MockTransport::new(&[&[0x68, 0x07]]);
+
+    let err = block_on(Dummy::open_app(&transport, "Cosmos")).expect_err("app not installed");
+    assert_eq!(err, LedgerAppError::AppNotInstalled("Cosmos".to_string()));
+    assert_eq!(transport.sent()[0], b"\xe0\xd8\x00\x00\x06Cosmos".to_vec());
+}
+
+#[test]
+fn wait_for_app_not_installed() {
+    let dashboard = app_info_answer(dashboard::DASHBOARD_NAME, "2.1.0");
+    let mut connections = vec![Ok(MockTransport::new(&[&dashboard, &[0x68, 0x07]]))];
+
+    let result = block_on(wait_for_app_with_interval(
+        || {
+            connections
+                .pop()
+                .unwrap_or(Err(MockError))
+        },
+        "Cosmos",
+        std::time::Duration::from_secs(10),
+        std::time::Duration::from_millis(1),
+    ));
+    assert!(matches!(result, Err(LedgerAppError::AppNotInstalled(name)) if name == "Cosmos"));
+}
+
+struct Guarded;
+
+impl App for Guarded {
+    const CLA: u8 = 0x55;
+    const APP_NAMES: &'static [&'static str] = &["Cosmos", "Cosmos Testnet"];
+    const SUPPORTED_VERSIONS: Option<&'static str> = Some(">=2.30, <3");
+}
+
+#[test]
+fn ensure_app() {
+    let transport = MockTransport::new(&[&app_info_answer("Cosmos", "2.34.12"), &[0, 2, 34, 12, 0x90, 0x00]]);
+
+    block_on(Guarded::ensure_app(&transport)).expect("expected app");
+    assert_eq!(transport.sent(), vec![vec![0xb0, 0x01, 0, 0, 0], vec![0x55, 0x00, 0, 0, 0]]);
+}
+
+#[test]
+fn ensure_app_is_exclusive() {
+    let transport = MockTransport::new(&[&app_info_answer("Cosmos", "2.34.12"), &[0, 2, 34, 12, 0x90, 0x00], &[
+        0, 2, 34, 12, 0x90, 0x00,
+    ]]);
+
+    let (checked, version) =
+        block_on(futures::future::join(Guarded::ensure_app(&transport), Guarded::get_version(&transport)));
+    checked.expect("expected app");
+    version.expect("valid version");
+
+    // the version query doesn't slip between the two APDUs of the check
+    assert_eq!(transport.sent(), vec![vec![0xb0, 0x01, 0, 0, 0], vec![0x55, 0x00, 0, 0, 0], vec![0x55, 0x00, 0, 0, 0]]);
+}
+
+#[test]
+fn ensure_app_wrong_app() {
+    let transport = MockTransport::new(&[&app_info_answer("Bitcoin", "2.1.0")]);
+
+    let err = block_on(Guarded::ensure_app(&transport)).expect_err("wrong app");
+    assert_eq!(err, LedgerAppError::WrongApp {
+        expected: vec!["Cosmos".to_string(), "Cosmos Testnet".to_string()],
+        actual: "Bitcoin".to_string(),
+    });
+    assert_eq!(transport.sent().len(), 1);
+}
+
+#[test]
+fn ensure_app_unsupported_version() {
+    let transport = MockTransport::new(&[&app_info_answer("Cosmos", "3.0.0"), &[0, 3, 0, 0, 0x90, 0x00]]);
+
+    match block_on(Guarded::ensure_app(&transport)) {
+        Err(LedgerAppError::UnsupportedVersion { required, actual }) => {
+            assert_eq!(required, ">=2.30, <3");
+            assert_eq!((actual.major, actual.minor, actual.patch), (3, 0, 0));
+        },
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let transport = MockTransport::new(&[&[0, 2, 34, 12, 0x90, 0x00]]);
+    let app = AppSession::new(&transport, AppConfig::new(0x55).with_supported_versions("2.x.y"));
+    assert_eq!(
+        block_on(app.ensure_app()),
+        Err(LedgerAppError::InvalidVersionRequirement(VersionParseError("2.x.y".to_string())))
+    );
+}
+
+#[test]
+fn ensure_app_cached() {
+    let transport = MockTransport::new(&[&app_info_answer("Cosmos", "2.34.12"), &[0, 2, 34, 12, 0x90, 0x00]]);
+    let cache = AppCheckCache::new();
+
+    block_on(Guarded::ensure_app_cached(&transport, &cache)).expect("expected app");
+    block_on(Guarded::ensure_app_cached(&transport, &cache)).expect("cached check");
+    assert_eq!(transport.sent().len(), 2);
+
+    cache.invalidate();
+    assert!(block_on(Guarded::ensure_app_cached(&transport, &cache)).is_err());
+}
+
+#[test]
+fn parse_ledger_versions() {
+    let parse = |version: &str| {
+        parse_version(version)
+            .expect("valid version")
+            .to_string()
+    };
+
+    assert_eq!(parse("2.34.12"), "2.34.12");
+    assert_eq!(parse("1.6"), "1.6.0");
+    assert_eq!(parse("v1.0.3-lns"), "1.0.3+lns");
+    assert_eq!(parse("2.1.0-rc1"), "2.1.0-rc1");
+    assert_eq!(parse("1.0.0-lns_s+plus"), "1.0.0+lns-s-plus");
+    assert!(parse_version("").is_err());
+    assert!(parse_version("1.x").is_err());
+    assert!(parse_version("1.2.3.4").is_err());
+}
+
+#[test]
+fn version_ordering_and_requirements() {
+    let version = |major, minor, patch| Version { mode: 0, major, minor, patch, locked: false, target_id: [0; 4] };
+
+    assert!(version(2, 10, 0) > version(2, 9, 14));
+    assert!(version(1, 0, 0) < version(1, 0, 1));
+    assert_eq!(version(2, 34, 12).to_string(), "2.34.12");
+    assert_eq!(version(2, 34, 12).satisfies(">=2.1, <3"), Ok(true));
+    assert_eq!(version(3, 0, 0).satisfies(">=2.1, <3"), Ok(false));
+    assert!(version(3, 0, 0)
+        .satisfies("not a requirement")
+        .is_err());
+
+    let transport = MockTransport::new(&[&app_info_answer("Cosmos", "2.34.12-lns")]);
+    let info = block_on(Dummy::get_app_info(&transport)).expect("valid app info");
+    assert_eq!(info.satisfies(">=2.1, <3"), Ok(true));
+
+    // release candidates match like their release
+    let transport = MockTransport::new(&[&app_info_answer("Cosmos", "2.1.0-rc1")]);
+    let info = block_on(Dummy::get_app_info(&transport)).expect("valid app info");
+    assert_eq!(info.satisfies(">=2.1, <3"), Ok(true));
+    assert_eq!(info.satisfies(">=2.2"), Ok(false));
+}
+
+#[test]
+fn get_device_info() {
+    let mut answer = vec![0x31, 0x10, 0x00, 0x04, 5];
+    answer.extend_from_slice(b"2.1.0");
+    answer.extend_from_slice(&[4, 0x86, 0, 0, 0, 4]);
+    answer.extend_from_slice(b"2.8\0");
+    answer.extend_from_slice(&[0x90, 0x00]);
+    let transport = MockTransport::new(&[&answer]);
+
+    let info = block_on(Dummy::get_device_info(&transport)).expect("valid device info");
+    assert_eq!(info.target_id, [0x31, 0x10, 0x00, 0x04]);
+    assert_eq!(info.se_version, "2.1.0");
+    assert_eq!(info.mcu_version, "2.8");
+    assert_eq!(info.flags, DeviceFlags::SIGNED_MCU_CODE | DeviceFlags::ONBOARDED | DeviceFlags::PIN_VALIDATED);
+    assert_eq!(info.mcu_bootloader_version, None);
+    assert!(!info.is_bootloader());
+
+    let transport = MockTransport::new(&[&[&[0x01, 0x10, 0x00, 0x01][..], &answer[4 ..]].concat()]);
+    let info = block_on(Dummy::get_device_info(&transport)).expect("valid device info");
+    assert!(info.is_bootloader());
+}
+
+#[test]
+fn get_device_info_extra_fields() {
+    let mut answer = vec![0x33, 0x20, 0x00, 0x04, 5];
+    answer.extend_from_slice(b"1.4.0");
+    answer.extend_from_slice(&[6, 0x84, 0, 0, 0x01, 0xff, 0xff, 4]);
+    answer.extend_from_slice(b"5.2\0");
+    answer.extend_from_slice(&[5]);
+    answer.extend_from_slice(b"3.12\0");
+    answer.extend_from_slice(&[1, 0x02, 1, 0x03, 0x90, 0x00]);
+    let transport = MockTransport::new(&[&answer]);
+
+    let info = block_on(Dummy::get_device_info(&transport)).expect("valid device info");
+    assert_eq!(info.flags.bits(), 0x0100_0084);
+    assert!(info
+        .flags
+        .contains(DeviceFlags::PIN_VALIDATED));
+    assert_eq!(info.mcu_version, "5.2");
+    assert_eq!(info.mcu_bootloader_version.as_deref(), Some("3.12"));
+    assert_eq!(info.hardware_version, Some(vec![0x02]));
+    assert_eq!(info.language_id, Some(0x03));
+
+    let truncated = [&answer[.. 12], &[0x90, 0x00]].concat();
+    let transport = MockTransport::new(&[&truncated]);
+    assert!(matches!(
+        block_on(Dummy::get_device_info(&transport)),
+        Err(LedgerAppError::InvalidResponse(DecodeError::TooShort { .. }))
+    ));
+}
+
+#[test]
+fn get_app_info_flags() {
+    let mut answer = vec![1, 6];
+    answer.extend_from_slice(b"Cosmos");
+    answer.extend_from_slice(&[1, b'2', 4, 0x85, 0x00, 0x00, 0x00, 0x90, 0x00]);
+    let transport = MockTransport::new(&[&answer]);
+
+    let info = block_on(Dummy::get_app_info(&transport)).expect("valid app info");
+    assert_eq!(info.flags, AppFlags::RECOVERY | AppFlags::ONBOARDED | AppFlags::PIN_VALIDATED);
+    assert!(info.flag_recovery && info.flag_onboarded && info.flag_pin_validated);
+    assert!(!info.flag_signed_mcu_code);
+}
+
+#[test]
+fn current_context() {
+    let transport = MockTransport::new(&[
+        &app_info_answer(dashboard::DASHBOARD_NAME, "2.2.3"),
+        &[0x6e, 0x00],
+        &app_info_answer("Cosmos", "2.34.12"),
+    ]);
+
+    let context = block_on(Dummy::current_context(&transport)).expect("dashboard");
+    assert!(matches!(&context, CurrentContext::Dashboard(os) if os.app_version == "2.2.3"));
+    assert_eq!(context.app_name(), None);
+
+    let context = block_on(Dummy::current_context(&transport)).expect("older dashboard");
+    assert_eq!(context, CurrentContext::Unknown { status_word: 0x6e00 });
+    assert!(!context.is_dashboard());
+
+    let context = block_on(Dummy::current_context(&transport)).expect("app");
+    assert_eq!(context.app_name(), Some("Cosmos"));
+}
+
+#[test]
+fn get_app_info_truncated() {
+    let transport = MockTransport::new(&[&[1, 6, b'C', b'o', 0x90, 0x00]]);
+
+    assert!(matches!(
+        block_on(Dummy::get_app_info(&transport)),
+        Err(LedgerAppError::InvalidResponse(DecodeError::TooShort { expected: 6, got: 2 }))
+    ));
+}
+
+#[test]
+fn derivation_path_parsing() {
+    let path: DerivationPath = "m/44'/118'/0'/0/0"
+        .parse()
+        .expect("valid path");
+    assert_eq!(path, DerivationPath::bip44(path::slip44::COSMOS, 0, 0, 0).expect("valid path"));
+    assert_eq!("44h/118H/0'/0/0".parse(), Ok(path.clone()));
+    assert_eq!(path.to_string(), "m/44'/118'/0'/0/0");
+    assert_eq!(path.coin_type(), Some(118));
+    assert_eq!(
+        "m".parse::<DerivationPath>()
+            .map(|p| p.depth()),
+        Ok(0)
+    );
+
+    assert_eq!("m/44'/x".parse::<DerivationPath>(), Err(PathError::InvalidComponent("x".to_string())));
+    assert_eq!("m/2147483648".parse::<DerivationPath>(), Err(PathError::InvalidComponent("2147483648".to_string())));
+    assert_eq!("m/1/2/3/4/5/6/7/8/9/10/11".parse::<DerivationPath>(), Err(PathError::TooDeep(11)));
+}
+
+#[test]
+fn derivation_path_checks() {
+    let path: DerivationPath = "m/44'/118'/0/0/0"
+        .parse()
+        .expect("valid path");
+    assert_eq!(path.check_hardened(2), Ok(()));
+    assert_eq!(path.check_bip44(path::slip44::COSMOS), Err(PathError::NotHardened(2)));
+
+    let path = DerivationPath::bip44(path::slip44::ETHEREUM, 0, 0, 1).expect("valid path");
+    assert_eq!(
+        DerivationPath::bip44(path::slip44::ETHEREUM, path::HARDENED, 0, 0),
+        Err(PathError::OutOfRange(path::HARDENED))
+    );
+    assert_eq!(path.check_bip44(path::slip44::ETHEREUM), Ok(()));
+    assert_eq!(path.check_bip44(path::slip44::COSMOS), Err(PathError::WrongCoinType { expected: 118, got: Some(60) }));
+    assert_eq!(path.check_depth(3), Err(PathError::WrongDepth { expected: 3, got: 5 }));
+}
+
+#[test]
+fn derivation_path_encodings() {
+    let path = DerivationPath::bip44(path::slip44::COSMOS, 1, 0, 2).expect("valid path");
+
+    assert_eq!(path.serialize_be(), vec![5, 0x80, 0, 0, 44, 0x80, 0, 0, 118, 0x80, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2]);
+    assert_eq!(path.serialize_zondax(), Ok([44, 0, 0, 0x80, 118, 0, 0, 0x80, 1, 0, 0, 0x80, 0, 0, 0, 0, 2, 0, 0, 0]));
+    assert!(DerivationPath::new(vec![0; 3])
+        .expect("valid path")
+        .serialize_zondax()
+        .is_err());
+
+    let json = serde_json::to_string(&path).expect("serializable");
+    assert_eq!(json, "\"m/44'/118'/1'/0/2\"");
+    assert_eq!(serde_json::from_str::<DerivationPath>(&json).expect("deserializable"), path);
+}
+
+/// secp256k1 generator, compressed
+const SECP256K1_G: [u8; 33] = [
+    0x02, 0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac, 0x55, 0xa0, 0x62, 0x95, 0xce, 0x87, 0x0b, 0x07, 0x02, 0x9b,
+    0xfc, 0xdb, 0x2d, 0xce, 0x28, 0xd9, 0x59, 0xf2, 0x81, 0x5b, 0x16, 0xf8, 0x17, 0x98,
+];
+
+#[test]
+fn get_pubkey() {
+    let answer = [&SECP256K1_G[..], b"cosmos1abc", &[0x90, 0x00]].concat();
+    let transport = MockTransport::new(&[&answer]);
+
+    let path = DerivationPath::bip44(path::slip44::COSMOS, 0, 0, 0).expect("valid path");
+    let info =
+        block_on(Dummy::get_pubkey(&transport, &AddressLayout::ZONDAX_SECP256K1, &path, true)).expect("valid address");
+
+    assert_eq!(info.public_key.as_bytes(), &SECP256K1_G[..]);
+    assert_eq!(info.address, "cosmos1abc");
+    assert_eq!(&transport.sent()[0][.. 5], &[0x55, 0x04, 0x01, 0x00, 20]);
+    #[cfg(feature = "secp256k1")]
+    assert!(info.public_key.to_secp256k1().is_some());
+}
+
+#[test]
+fn get_pubkey_layouts() {
+    let layout = AddressLayout {
+        ins: 0x02,
+        path: address::PathEncoding::LengthPrefixedBe,
+        pubkey: address::FieldLength::Prefixed,
+        address: address::FieldLength::Prefixed,
+    };
+    let answer = [&[3, 1, 2, 3, 2][..], b"ab", &[0x90, 0x00]].concat();
+    let transport = MockTransport::new(&[&answer, &[3, 1, 2, 3, 3, b'a', 0x90, 0x00]]);
+
+    let path: DerivationPath = "m/44'/0'".parse().expect("valid path");
+    let info = block_on(Dummy::get_pubkey(&transport, &layout, &path, false)).expect("valid address");
+    assert_eq!(info.public_key, PublicKey(vec![1, 2, 3]));
+    assert_eq!(info.address, "ab");
+    assert_eq!(transport.sent()[0], vec![0x55, 0x02, 0x00, 0x00, 9, 2, 0x80, 0, 0, 44, 0x80, 0, 0, 0]);
+
+    assert_eq!(
+        block_on(Dummy::get_pubkey(&transport, &layout, &path, false)),
+        Err(LedgerAppError::InvalidResponse(DecodeError::TooShort { expected: 3, got: 1 }))
+    );
+    assert_eq!(
+        block_on(Dummy::get_pubkey(&transport, &AddressLayout::ZONDAX_ED25519, &path, false)),
+        Err(LedgerAppError::InvalidDerivationPath(PathError::WrongDepth { expected: 5, got: 2 }))
+    );
+}
+
+#[test]
+fn get_pubkey_rejected() {
+    let transport = MockTransport::new(&[&[0x69, 0x86], &[0x69, 0x86]]);
+    let path = DerivationPath::bip44(path::slip44::COSMOS, 0, 0, 0).expect("valid path");
+
+    assert_eq!(
+        block_on(Dummy::get_pubkey(&transport, &AddressLayout::ZONDAX_SECP256K1, &path, true)),
+        Err(LedgerAppError::UserRejected)
+    );
+    assert!(matches!(
+        block_on(Dummy::get_pubkey(&transport, &AddressLayout::ZONDAX_SECP256K1, &path, false)),
+        Err(LedgerAppError::AppSpecific(0x6986, _))
+    ));
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn ed25519_public_key() {
+    let key = PublicKey(vec![
+        0xd7, 0x5a, 0x98, 0x01, 0x82, 0xb1, 0x0a, 0xb7, 0xd5, 0x4b, 0xfe, 0xd3, 0xc9, 0x64, 0x07, 0x3a, 0x0e, 0xe1,
+        0x72, 0xf3, 0xda, 0xa6, 0x23, 0x25, 0xaf, 0x02, 0x1a, 0x68, 0xf7, 0x07, 0x51, 0x1a,
+    ]);
+
+    assert!(key.to_ed25519().is_some());
+    assert!(PublicKey(vec![1; 31])
+        .to_ed25519()
+        .is_none());
+}
+
+#[test]
+fn signature_parsing() {
+    let mut der = vec![0x30, 0x45, 0x02, 0x21, 0x00, 0x80];
+    der.extend_from_slice(&[0x11; 31]);
+    der.extend_from_slice(&[0x02, 0x20]);
+    der.extend_from_slice(&[0x22; 32]);
+
+    let mut r = [0x11; 32];
+    r[0] = 0x80;
+    assert_eq!(
+        Signature::parse(SignatureFormat::Der, &der),
+        Some(Signature::Ecdsa { r, s: [0x22; 32], recovery_id: None })
+    );
+    assert_eq!(
+        Signature::parse(SignatureFormat::Der, &[0x30, 0x06, 0x02, 0x01, 0x05, 0x02, 0x01, 0x07]),
+        Some(Signature::Ecdsa {
+            r: [[0; 31].as_slice(), &[5]]
+                .concat()
+                .try_into()
+                .unwrap(),
+            s: [[0; 31].as_slice(), &[7]]
+                .concat()
+                .try_into()
+                .unwrap(),
+            recovery_id: None
+        })
+    );
+    assert_eq!(Signature::parse(SignatureFormat::Der, &der[.. 40]), None);
+
+    // only minimal encodings of positive integers
+    let der = |r: &[u8]| [&[0x30, r.len() as u8 + 5, 0x02, r.len() as u8], r, &[0x02, 0x01, 0x07]].concat();
+    assert!(Signature::parse(SignatureFormat::Der, &der(&[0x05])).is_some());
+    assert_eq!(Signature::parse(SignatureFormat::Der, &der(&[0x00, 0x05])), None);
+    assert_eq!(Signature::parse(SignatureFormat::Der, &der(&[0x85])), None);
+
+    let rsv = [[0x11; 32].as_slice(), &[0x22; 32], &[1]].concat();
+    assert_eq!(
+        Signature::parse(SignatureFormat::Recoverable, &rsv),
+        Some(Signature::Ecdsa { r: [0x11; 32], s: [0x22; 32], recovery_id: Some(1) })
+    );
+    assert_eq!(
+        Signature::parse(SignatureFormat::Compact, &rsv[.. 64]),
+        Some(Signature::Ecdsa { r: [0x11; 32], s: [0x22; 32], recovery_id: None })
+    );
+    assert_eq!(Signature::parse(SignatureFormat::Compact, &rsv), None);
+    assert_eq!(Signature::parse(SignatureFormat::Ed25519, &rsv[.. 64]), Some(Signature::Ed25519(rsv[.. 64].to_vec())));
+}
+
+#[test]
+fn sign() {
+    let layout = SignLayout { signature: SignatureFormat::Compact, ..SignLayout::ZONDAX_SECP256K1 };
+    let answer = [[0x11; 64].as_slice(), &[0x90, 0x00]].concat();
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x90, 0x00], &answer]);
+
+    let path = DerivationPath::bip44(path::slip44::COSMOS, 0, 0, 0).expect("valid path");
+    let signature = block_on(Dummy::sign(&transport, &layout, &path, b"hello!")).expect("valid signature");
+
+    assert_eq!(signature, Signature::Ecdsa { r: [0x11; 32], s: [0x11; 32], recovery_id: None });
+    let sent = transport.sent();
+    assert_eq!(&sent[0][.. 5], &[0x55, 0x02, 0x00, 0x00, 20]);
+    assert_eq!(sent[1], vec![0x55, 0x02, 0x01, 0x00, 4, b'h', b'e', b'l', b'l']);
+    assert_eq!(sent[2], vec![0x55, 0x02, 0x02, 0x00, 2, b'o', b'!']);
+}
+
+#[test]
+fn sign_errors() {
+    let path = DerivationPath::bip44(path::slip44::COSMOS, 0, 0, 0).expect("valid path");
+
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x69, 0x86]]);
+    assert_eq!(
+        block_on(Dummy::sign(&transport, &SignLayout::ZONDAX_SECP256K1, &path, b"msg")),
+        Err(LedgerAppError::UserRejected)
+    );
+
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x90, 0x00]]);
+    assert_eq!(
+        block_on(Dummy::sign(&transport, &SignLayout::ZONDAX_SECP256K1, &path, b"msg")),
+        Err(LedgerAppError::NoSignature)
+    );
+
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x30, 0x01, 0x90, 0x00]]);
+    assert_eq!(
+        block_on(Dummy::sign(&transport, &SignLayout::ZONDAX_SECP256K1, &path, b"msg")),
+        Err(LedgerAppError::InvalidSignature)
+    );
+}
+
+#[cfg(feature = "verify")]
+#[test]
+fn sign_verified() {
+    use ed25519_dalek::Signer as _;
+
+    let path = DerivationPath::bip44(path::slip44::COSMOS, 0, 0, 0).expect("valid path");
+    let message = b"hello!";
+
+    let secp = k256::ecdsa::SigningKey::from_bytes(&[1u8; 32].into()).expect("valid key");
+    let secp_pubkey = PublicKey(
+        secp.verifying_key()
+            .to_sec1_bytes()
+            .to_vec(),
+    );
+    let der: k256::ecdsa::Signature = k256::ecdsa::signature::Signer::sign(&secp, message);
+    let answer = [der.to_der().as_bytes(), &[0x90, 0x00]].concat();
+
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x90, 0x00], &answer, &[0x90, 0x00], &[0x90, 0x00], &answer]);
+    block_on(Dummy::sign_verified(
+        &transport,
+        &SignLayout::ZONDAX_SECP256K1,
+        &path,
+        message,
+        &secp_pubkey,
+        signature::sha256,
+    ))
+    .expect("valid signature");
+    assert_eq!(
+        block_on(Dummy::sign_verified(
+            &transport,
+            &SignLayout::ZONDAX_SECP256K1,
+            &path,
+            b"other!",
+            &secp_pubkey,
+            signature::sha256
+        )),
+        Err(LedgerAppError::InvalidSignature)
+    );
+
+    // apps hashing with something else than SHA-256
+    let reversed = |message: &[u8]| {
+        let mut digest = signature::sha256(message);
+        digest.reverse();
+        digest
+    };
+    let prehashed: k256::ecdsa::Signature =
+        k256::ecdsa::signature::hazmat::PrehashSigner::sign_prehash(&secp, &reversed(message)).expect("valid digest");
+    let answer = [prehashed.to_der().as_bytes(), &[0x90, 0x00]].concat();
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x90, 0x00], &answer]);
+    block_on(Dummy::sign_verified(&transport, &SignLayout::ZONDAX_SECP256K1, &path, message, &secp_pubkey, reversed))
+        .expect("valid signature");
+
+    let ed = ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]);
+    let ed_pubkey = PublicKey(ed.verifying_key().to_bytes().to_vec());
+    let answer = [ed.sign(message).to_bytes().as_slice(), &[0x90, 0x00]].concat();
+
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x90, 0x00], &answer]);
+    block_on(Dummy::sign_verified(
+        &transport,
+        &SignLayout::ZONDAX_ED25519,
+        &path,
+        message,
+        &ed_pubkey,
+        signature::sha256,
+    ))
+    .expect("valid signature");
+}
+
+#[test]
+fn discover_accounts() {
+    let layout = AddressLayout {
+        ins: 0x04,
+        path: address::PathEncoding::Zondax,
+        pubkey: address::FieldLength::Fixed(1),
+        address: address::FieldLength::Rest,
+    };
+    let answer: &[u8] = &[0x02, b'a', 0x90, 0x00];
+    let used_paths = ["m/44'/118'/0'/0/0", "m/44'/118'/0'/0/2"];
+    let is_used = |address: DiscoveredAddress| {
+        let used = used_paths.contains(&address.path.to_string().as_str());
+        async move { Ok(used) }
+    };
+    let cache = AddressCache::new();
+
+    // stop after 3 addresses, then resume
+    let mut discovery = AccountDiscovery::new(path::slip44::COSMOS, 2).with_changes(&[0]);
+    let transport = MockTransport::new(&[answer; 3]);
+    block_on(Dummy::discover_accounts(&transport, &layout, &mut discovery, &cache, "nano-1", is_used, |p| {
+        if p.scanned == 3 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }))
+    .expect("discovery");
+    assert!(!discovery.is_finished());
+
+    let state = serde_json::to_string(&discovery.state).expect("serializable");
+    let mut discovery = AccountDiscovery::new(path::slip44::COSMOS, 2)
+        .with_changes(&[0])
+        .resume(serde_json::from_str(&state).expect("deserializable"));
+    let transport = MockTransport::new(&[answer; 4]);
+    block_on(Dummy::discover_accounts(&transport, &layout, &mut discovery, &cache, "nano-1", is_used, |_| {
+        ControlFlow::Continue(())
+    }))
+    .expect("discovery");
+
+    assert!(discovery.is_finished());
+    assert_eq!(discovery.state.scanned, 7);
+    assert_eq!(transport.sent().len(), 4);
+    assert_eq!(
+        discovery
+            .used()
+            .iter()
+            .map(|address| address.path.to_string())
+            .collect::<Vec<_>>(),
+        used_paths
+    );
+
+    // rescans only hit the cache
+    let mut rescan = AccountDiscovery::new(path::slip44::COSMOS, 2).with_changes(&[0]);
+    let transport = MockTransport::new(&[]);
+    block_on(Dummy::discover_accounts(&transport, &layout, &mut rescan, &cache, "nano-1", is_used, |_| {
+        ControlFlow::Continue(())
+    }))
+    .expect("discovery");
+    assert_eq!(rescan.used(), discovery.used());
+    assert_eq!(cache.len(), 7);
+
+    // another device doesn't share the cached addresses
+    let mut other = AccountDiscovery::new(path::slip44::COSMOS, 2).with_changes(&[0]);
+    let result = block_on(Dummy::discover_accounts(&transport, &layout, &mut other, &cache, "nano-2", is_used, |_| {
+        ControlFlow::Continue(())
+    }));
+    assert!(matches!(result, Err(LedgerAppError::TransportError(_))));
+    assert_eq!(transport.sent().len(), 1);
+}
+
+#[test]
+fn discover_accounts_usage_check_error() {
+    let transport = MockTransport::new(&[&[0x02, b'a', 0x90, 0x00]]);
+    let mut discovery = AccountDiscovery::new(path::slip44::COSMOS, 20);
+
+    let result = block_on(Dummy::discover_accounts(
+        &transport,
+        &AddressLayout { pubkey: address::FieldLength::Fixed(1), ..AddressLayout::ZONDAX_SECP256K1 },
+        &mut discovery,
+        &AddressCache::new(),
+        "nano-1",
+        |_| async { Err("indexer unavailable".into()) },
+        |_| ControlFlow::Continue(()),
+    ));
+
+    assert_eq!(result, Err(LedgerAppError::UsageCheck("indexer unavailable".to_string())));
+    assert_eq!(discovery.state, DiscoveryState::default());
+}
+
+#[test]
+fn discover_accounts_invalid_state() {
+    let transport = MockTransport::new(&[]);
+    let mut discovery = AccountDiscovery::new(path::slip44::COSMOS, 20)
+        .with_changes(&[0])
+        .resume(DiscoveryState { change: 1, ..DiscoveryState::default() });
+
+    let result = block_on(Dummy::discover_accounts(
+        &transport,
+        &AddressLayout::ZONDAX_SECP256K1,
+        &mut discovery,
+        &AddressCache::new(),
+        "nano-1",
+        |_| async { Ok(false) },
+        |_| ControlFlow::Continue(()),
+    ));
+
+    assert_eq!(result, Err(LedgerAppError::InvalidDiscoveryState { change: 1, changes: 1 }));
+    assert!(transport.sent().is_empty());
+}
+
+#[test]
+fn app_session_runtime_config() {
+    let transport = MockTransport::new(&[&[0, 1, 2, 3, 0x90, 0x00], &[0x90, 0x00], &[0x90, 0x00], &[0x90, 0x00]]);
+    let app = AppSession::new(
+        &transport,
+        AppConfig::of::<Dummy>()
+            .with_cla(0x56)
+            .with_chunk_size(2),
+    );
+
+    let version = block_on(app.get_version()).expect("valid version");
+    assert_eq!(version.to_string(), "1.2.3");
+
+    let init = APDUCommand { cla: 0x56, ins: 0x02, p1: ChunkPayloadType::Init as u8, p2: 0, data: vec![] };
+    block_on(app.send_chunks(init, &[1, 2, 3])).expect("chunks sent");
+
+    let sent = transport.sent();
+    assert_eq!(sent[0], vec![0x56, 0x00, 0, 0, 0]);
+    assert_eq!(sent[2], vec![0x56, 0x02, 0x01, 0, 2, 1, 2]);
+    assert_eq!(sent[3], vec![0x56, 0x02, 0x02, 0, 1, 3]);
+}
+
+#[test]
+fn app_session_owns_transport() {
+    let transport = MockTransport::new(&[&app_info_answer("Cosmos Testnet", "2.34.12")]);
+    let app = AppSession::new(transport, AppConfig::new(0x55).with_app_names(["Cosmos"]));
+
+    assert_eq!(
+        block_on(app.ensure_app()),
+        Err(LedgerAppError::WrongApp { expected: vec!["Cosmos".to_string()], actual: "Cosmos Testnet".to_string() })
+    );
+    assert_eq!(app.into_transport().sent().len(), 1);
+}
+
+status_words! {
+    /// Status words of the test app
+    enum CustomStatus {
+        /// The transaction type is not supported
+        InvalidTxType = 0x6988 => "invalid transaction type",
+        /// Overrides a generic status word
+        Rejected = 0x6986 => "transaction rejected",
+    }
+}
+
+struct Custom;
+
+impl App for Custom {
+    const CLA: u8 = 0x55;
+    const CHUNK_SIZE: usize = 4;
+    const STATUS_WORDS: &'static [StatusWord] = CustomStatus::TABLE;
+}
+
+#[test]
+fn status_words_enum() {
+    assert_eq!(CustomStatus::try_from(0x6988), Ok(CustomStatus::InvalidTxType));
+    assert_eq!(CustomStatus::try_from(0x6984), Err(0x6984));
+    assert_eq!(CustomStatus::Rejected.code(), 0x6986);
+    assert_eq!(CustomStatus::Rejected.name(), "Rejected");
+    assert_eq!(CustomStatus::Rejected.description(), "transaction rejected");
+    assert_eq!(CustomStatus::TABLE[0], StatusWord::new(0x6988, "InvalidTxType", "invalid transaction type"));
+}
+
+#[test]
+fn status_words_errors() {
+    let invalid_tx = || LedgerAppError::<MockError>::AppStatus {
+        code: 0x6988,
+        name: "InvalidTxType".to_string(),
+        description: "invalid transaction type".to_string(),
+    };
+    let answer = APDUAnswer::from_answer(vec![0x69, 0x88]).expect("valid answer");
+
+    assert_eq!(<Custom as AppExt<MockTransport>>::handle_response_error(&answer), Err(invalid_tx()));
+    assert_eq!(<Custom as AppExt<MockTransport>>::handle_response_error_signature(&answer), Err(invalid_tx()));
+    assert_eq!(<Dummy as AppExt<MockTransport>>::handle_response_error(&answer), Err(LedgerAppError::Unknown(0x6988)));
+    assert_eq!(invalid_tx().status_word(), Some(0x6988));
+
+    // generic status words are still mapped
+    let answer = APDUAnswer::from_answer(vec![0x69, 0x84]).expect("valid answer");
+    assert_eq!(
+        <Custom as AppExt<MockTransport>>::handle_response_error(&answer),
+        Err(LedgerAppError::AppSpecific(0x6984, APDUErrorCode::DataInvalid.description()))
+    );
+
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x69, 0x88]]);
+    let command = APDUCommand { cla: Custom::CLA, ins: 0x02, p1: 0x00, p2: 0x00, data: vec![] };
+    let err = block_on(Custom::send_chunks(&transport, command, &[0; 4])).expect_err("chunk rejected");
+    assert_eq!(err, invalid_tx());
+
+    // the app table doesn't hide user rejections
+    let path = DerivationPath::bip44(path::slip44::COSMOS, 0, 0, 0).expect("valid path");
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x69, 0x86]]);
+    assert_eq!(
+        block_on(Custom::sign(&transport, &SignLayout::ZONDAX_SECP256K1, &path, b"msg")),
+        Err(LedgerAppError::UserRejected)
+    );
+}
+
+#[test]
+fn status_words_runtime() {
+    let transport = MockTransport::new(&[&[0x90, 0x00], &[0x6a, 0x01]]);
+    let app = AppSession::new(
+        &transport,
+        AppConfig::of::<Custom>().with_status_words([StatusWord {
+            code: 0x6a01,
+            name: "Locked".into(),
+            description: "account is locked".into(),
+        }]),
+    );
+
+    let init = APDUCommand { cla: 0x55, ins: 0x02, p1: ChunkPayloadType::Init as u8, p2: 0, data: vec![] };
+    let err = block_on(app.send_chunks(init, &[1, 2, 3])).expect_err("chunk rejected");
+    assert_eq!(err, LedgerAppError::AppStatus {
+        code: 0x6a01,
+        name: "Locked".to_string(),
+        description: "account is locked".to_string(),
+    });
+}
+
+#[test]
+fn transport_error_kind() {
+    // the mock runs out of answers like an unplugged device
+    let transport = MockTransport::new(&[]);
+    let err = block_on(Dummy::get_version(&transport)).expect_err("no answer");
+    assert_eq!(err.transport_error_kind(), Some(ledger_transport::TransportErrorKind::Disconnected));
+    assert_eq!(err.status_word(), None);
+
+    let err = LedgerAppError::<MockError>::Unknown(0x6f00);
+    assert_eq!(err.transport_error_kind(), None);
+}
+
+#[test]
+fn errors_display() {
+    let err = LedgerAppError::<MockError>::ChunkRejected {
+        index: 2,
+        retcode: 0x6984,
+        error: Box::new(LedgerAppError::Unknown(0x6984)),
+    };
+    assert_eq!(err.to_string(), "chunk 2 rejected with 0x6984 | Unknown error: 27012");
+}
@@ -0,0 +1,158 @@
+/*******************************************************************************
+*   (c) 2022 Zondax AG
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Semantic versioning of apps and firmwares
+
+use std::{cmp::Ordering, fmt};
+
+use semver::{BuildMetadata, Prerelease, VersionReq};
+
+use crate::{AppInfo, DeviceInfo, Version, VersionParseError};
+
+/// Parse a version string as reported by a device into a semantic version
+///
+/// Missing minor or patch numbers default to 0 and a leading `v` is ignored.
+/// Release candidate suffixes (`1.2.0-rc1`) become pre-releases, other suffixes
+/// such as the device in `1.0.3-lns` become build metadata
+pub fn parse_version(version: &str) -> Result<semver::Version, VersionParseError> {
+    let invalid = || VersionParseError(version.to_string());
+
+    let trimmed = version.trim();
+    let trimmed = trimmed
+        .strip_prefix('v')
+        .unwrap_or(trimmed);
+    let (core, suffix) = match trimmed.find(['-', '+']) {
+        Some(idx) => (&trimmed[.. idx], &trimmed[idx + 1 ..]),
+        None => (trimmed, ""),
+    };
+
+    let mut numbers = [0u64; 3];
+    let mut parts = core.split('.');
+    for number in numbers.iter_mut() {
+        match parts.next() {
+            Some(part) => *number = part.parse().map_err(|_| invalid())?,
+            None if core.is_empty() => return Err(invalid()),
+            None => break,
+        }
+    }
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+
+    let mut parsed = semver::Version::new(numbers[0], numbers[1], numbers[2]);
+    if suffix.starts_with("rc") {
+        parsed.pre = Prerelease::new(suffix).map_err(|_| invalid())?;
+    } else if !suffix.is_empty() {
+        let build: String = suffix
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' })
+            .collect();
+        parsed.build = BuildMetadata::new(&build).map_err(|_| invalid())?;
+    }
+
+    Ok(parsed)
+}
+
+/// Whether `version` matches the requirement, e.g. `">=2.1, <3"`
+///
+/// Release candidates are matched as their release: semver would otherwise only let
+/// requirements naming a pre-release of the same version match them
+fn matches(
+    version: &semver::Version,
+    requirement: &str,
+) -> Result<bool, VersionParseError> {
+    let requirement = VersionReq::parse(requirement).map_err(|_| VersionParseError(requirement.to_string()))?;
+    let release = semver::Version { pre: Prerelease::EMPTY, ..version.clone() };
+    Ok(requirement.matches(&release))
+}
+
+impl Version {
+    /// The version as a semantic version
+    pub fn semver(&self) -> semver::Version {
+        semver::Version::new(self.major.into(), self.minor.into(), self.patch.into())
+    }
+
+    /// Whether the version matches the requirement, e.g. `">=2.1, <3"`
+    pub fn satisfies(
+        &self,
+        requirement: &str,
+    ) -> Result<bool, VersionParseError> {
+        matches(&self.semver(), requirement)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(
+        &self,
+        other: &Self,
+    ) -> Ordering {
+        (self.major, self.minor, self.patch, self.mode, self.locked, self.target_id).cmp(&(
+            other.major,
+            other.minor,
+            other.patch,
+            other.mode,
+            other.locked,
+            other.target_id,
+        ))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(
+        &self,
+        other: &Self,
+    ) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl AppInfo {
+    /// The app version as a semantic version, see [parse_version]
+    pub fn app_semver(&self) -> Result<semver::Version, VersionParseError> {
+        parse_version(&self.app_version)
+    }
+
+    /// Whether the app version matches the requirement, e.g. `">=2.1, <3"`
+    pub fn satisfies(
+        &self,
+        requirement: &str,
+    ) -> Result<bool, VersionParseError> {
+        matches(&self.app_semver()?, requirement)
+    }
+}
+
+impl DeviceInfo {
+    /// The secure element (firmware) version as a semantic version, see [parse_version]
+    pub fn se_semver(&self) -> Result<semver::Version, VersionParseError> {
+        parse_version(&self.se_version)
+    }
+
+    /// Whether the firmware version matches the requirement, e.g. `">=2.1, <3"`
+    pub fn satisfies(
+        &self,
+        requirement: &str,
+    ) -> Result<bool, VersionParseError> {
+        matches(&self.se_semver()?, requirement)
+    }
+}